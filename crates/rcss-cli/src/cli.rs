@@ -1,4 +1,11 @@
 use clap::Parser;
+use rcss_core::emitter::{DarkMode, Nesting};
+use rcss_core::export::{ExportFormat, Naming};
+use rcss_core::targets::Targets;
+
+/// Theme directory used when `--theme` is not given; without it the
+/// built-in theme is used alone.
+pub const DEFAULT_THEME: &str = "theme";
 
 #[derive(Parser, Debug)]
 #[command(name = "rcss")]
@@ -14,5 +21,153 @@ pub enum Commands {
         input: String,
         #[arg(short, long)]
         output: Option<String>,
+        /// Merge rules sharing a media query into one mobile-first ordered block
+        /// (flattened output only)
+        #[arg(long)]
+        group_media: bool,
+        /// How nested rules are written: "flatten" or "native"
+        #[arg(long, default_value_t = Nesting::Flatten)]
+        nesting: Nesting,
+        /// Browserslist-style query deciding which vendor prefixes to emit
+        #[arg(long, default_value = "defaults")]
+        targets: Targets,
+        /// Drop hand-written vendor prefixes the targets don't need
+        #[arg(long)]
+        strip_prefixes: bool,
+        /// How dark styles switch on: "media", "class", "attribute" or "both"
+        #[arg(long, default_value_t = DarkMode::Media)]
+        dark_mode: DarkMode,
+        /// Write px lengths as rem of the root font size set by the base preset
+        #[arg(long)]
+        px_to_rem: bool,
+        /// With --px-to-rem, convert only these properties (comma-separated;
+        /// `margin*` matches by prefix, `*shadow` by suffix)
+        #[arg(
+            long,
+            value_delimiter = ',',
+            value_name = "PROPERTIES",
+            requires = "px_to_rem"
+        )]
+        rem_only: Vec<String>,
+        /// With --px-to-rem, keep these properties in px instead of the
+        /// default border*, outline* and *shadow
+        #[arg(
+            long,
+            value_delimiter = ',',
+            value_name = "PROPERTIES",
+            requires = "px_to_rem"
+        )]
+        keep_px: Option<Vec<String>>,
+        /// Theme directory, optionally named as NAME=DIR, layered over the
+        /// built-in theme; repeat to build against several themes (one output
+        /// file per theme)
+        #[arg(long = "theme", default_value = DEFAULT_THEME)]
+        themes: Vec<String>,
+        /// Use only the theme directories, without the built-in theme under them
+        #[arg(long)]
+        no_builtin_theme: bool,
+        /// Write one file instead: the first theme unscoped as the fallback,
+        /// each rule followed by its token-dependent declarations per theme
+        /// under [ATTRIBUTE="name"]
+        #[arg(long, value_name = "ATTRIBUTE")]
+        theme_scope: Option<String>,
+    },
+    /// Maintain theme files
+    Theme {
+        #[command(subcommand)]
+        command: ThemeCommand,
+    },
+    /// Work with the theme's tokens
+    Tokens {
+        #[command(subcommand)]
+        command: TokensCommand,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub enum TokensCommand {
+    /// Write the token collections as constants for another platform
+    Export {
+        /// ts, js, json, scss, css-vars, swift or kotlin
+        #[arg(long)]
+        format: ExportFormat,
+        /// keep, kebab, camel, snake or constant; defaults to the theme's
+        /// names, or camel for swift and kotlin
+        #[arg(long)]
+        naming: Option<Naming>,
+        /// Only these collections (comma-separated or repeated)
+        #[arg(long = "collection", value_delimiter = ',')]
+        collections: Vec<String>,
+        /// File to write instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        theme: ThemeSource,
+    },
+    /// List the collections, or the tokens of one collection
+    List {
+        /// Collection to list, e.g. colors or spacing
+        collection: Option<String>,
+        #[command(flatten)]
+        theme: ThemeSource,
+    },
+    /// Find tokens by name or value, fuzzily
+    Search {
+        /// Words to match, e.g. "blue 500" or "#3b82f6"
+        query: String,
+        /// Most results to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        #[command(flatten)]
+        theme: ThemeSource,
+    },
+    /// Resolve a token for a property and explain where its value comes from
+    Resolve {
+        /// Token as written in a stylesheet, e.g. @blue-500/50
+        token: String,
+        /// Property the token is used with
+        #[arg(long, default_value = "color")]
+        property: String,
+        #[command(flatten)]
+        theme: ThemeSource,
+    },
+}
+
+/// The theme a `tokens` command reads.
+#[derive(clap::Args, Debug)]
+pub struct ThemeSource {
+    /// Theme directory, layered over the built-in theme
+    #[arg(long, default_value = DEFAULT_THEME)]
+    pub theme: String,
+    /// Use only the theme directory, without the built-in theme under it
+    #[arg(long)]
+    pub no_builtin_theme: bool,
+}
+
+#[derive(Parser, Debug)]
+pub enum ThemeCommand {
+    /// Derive tokens.json from a Tailwind-resolved theme
+    Generate {
+        /// Tailwind-resolved theme JSON
+        #[arg(long)]
+        from: String,
+        /// tokens.json to write; its collection names, aliases and overrides are kept
+        #[arg(short, long, default_value = "theme/tokens.json")]
+        output: String,
+        /// Fail if the output file is not what would be generated, instead of writing it
+        #[arg(long)]
+        check: bool,
+    },
+    /// Validate a theme directory without building anything
+    Check {
+        /// Theme directory
+        #[arg(long, default_value = DEFAULT_THEME)]
+        theme: String,
+        /// Check the directory alone, without the built-in theme under it
+        #[arg(long)]
+        no_builtin_theme: bool,
+        /// Fail on warnings (unreachable collections, shadowed keys) too
+        #[arg(long)]
+        strict: bool,
     },
 }
@@ -1,44 +1,428 @@
 mod cli;
 
 use clap::Parser;
-use cli::{Cli, Commands};
+use cli::{Cli, Commands, DEFAULT_THEME, ThemeCommand, ThemeSource, TokensCommand};
 
-use std::{fs, path::Path};
+use std::{fs, path::Path, process};
 
-use rcss_core::{emitter, loader::load_with_imports, parser, resolver, theme::Theme};
+use rcss_core::{
+    ast::Stylesheet,
+    emitter,
+    export::{self, Naming},
+    loader::load_with_imports,
+    parser,
+    resolver::{self, ChosenBy, Opacity, Origin},
+    tailwind,
+    theme::Theme,
+    theme_check::{self, Severity},
+    theme_scope, token_search,
+    units::PxToRem,
+};
+use serde_json::Value;
 
 fn main() {
     let args = Cli::parse();
 
     match args.command {
-        Commands::Build { input, output } => {
-            run_build(&input, output);
+        Commands::Build {
+            input,
+            output,
+            group_media,
+            nesting,
+            targets,
+            strip_prefixes,
+            dark_mode,
+            px_to_rem,
+            rem_only,
+            keep_px,
+            themes,
+            no_builtin_theme,
+            theme_scope,
+        } => {
+            if group_media && nesting == emitter::Nesting::Native {
+                eprintln!(
+                    "✗ --group-media only applies to flattened output; drop it or use --nesting flatten"
+                );
+                process::exit(1);
+            }
+            let px_to_rem = px_to_rem.then(|| {
+                let defaults = PxToRem::default();
+                PxToRem {
+                    only: rem_only,
+                    keep_px: keep_px.unwrap_or(defaults.keep_px),
+                }
+            });
+            let options = emitter::EmitOptions {
+                group_media,
+                nesting,
+                targets,
+                strip_prefixes,
+                dark_mode,
+                px_to_rem,
+            };
+            let themes = ThemeArgs {
+                dirs: &themes,
+                scope: theme_scope.as_deref(),
+                builtin: !no_builtin_theme,
+            };
+            run_build(&input, output, &themes, &options);
         }
+        Commands::Theme { command } => match command {
+            ThemeCommand::Generate {
+                from,
+                output,
+                check,
+            } => run_generate(&from, &output, check),
+            ThemeCommand::Check {
+                theme,
+                no_builtin_theme,
+                strict,
+            } => run_check(&theme, !no_builtin_theme, strict),
+        },
+        Commands::Tokens { command } => match command {
+            TokensCommand::Export {
+                format,
+                naming,
+                collections,
+                output,
+                theme,
+            } => {
+                let options = export::ExportOptions {
+                    format,
+                    naming: naming.unwrap_or(Naming::default_for(format)),
+                    collections,
+                };
+                run_export(&theme, &options, output.as_deref());
+            }
+            TokensCommand::List { collection, theme } => run_list(&theme, collection.as_deref()),
+            TokensCommand::Search {
+                query,
+                limit,
+                theme,
+            } => run_search(&theme, &query, limit),
+            TokensCommand::Resolve {
+                token,
+                property,
+                theme,
+            } => run_resolve(&theme, &token, &property),
+        },
     }
 }
 
-fn run_build(input_path: &str, output_override: Option<String>) {
+fn source_theme(source: &ThemeSource) -> Theme {
+    load_theme(&source.theme, !source.no_builtin_theme)
+        .unwrap_or_else(|e| panic!("Failed to load theme {}: {}", source.theme, e))
+}
+
+fn run_export(source: &ThemeSource, options: &export::ExportOptions, output: Option<&str>) {
+    let theme = source_theme(source);
+    let text = export::export(&theme, options).unwrap_or_else(|e| {
+        eprintln!("✗ {}", e);
+        process::exit(1);
+    });
+    match output {
+        Some(path) => {
+            fs::write(path, text).expect("Failed to write tokens");
+            println!("✓ Exported {} tokens → {}", options.format, path);
+        }
+        None => print!("{}", text),
+    }
+}
+
+fn run_list(source: &ThemeSource, collection: Option<&str>) {
+    let theme = source_theme(source);
+    let Some(collection) = collection else {
+        let summaries = token_search::collections(&theme);
+        let width = summaries.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        for summary in summaries {
+            println!(
+                "{:width$}  {:>4} tokens  {}",
+                summary.name,
+                summary.tokens,
+                summary.properties.join(", "),
+            );
+        }
+        return;
+    };
+
+    let entries = token_search::list(&theme, collection).unwrap_or_else(|e| {
+        eprintln!("✗ {}", e);
+        process::exit(1);
+    });
+    let width = entries.iter().map(|e| e.key.len()).max().unwrap_or(0);
+    for entry in entries {
+        println!("{:width$}  {}", entry.key, entry_value(&entry));
+    }
+}
+
+fn run_search(source: &ThemeSource, query: &str, limit: usize) {
+    let theme = source_theme(source);
+    let entries = token_search::search(&theme, query);
+    if entries.is_empty() {
+        eprintln!("✗ No tokens match '{}'", query);
+        process::exit(1);
+    }
+    let names: Vec<String> = entries
+        .iter()
+        .take(limit)
+        .map(|entry| format!("{}.{}", entry.collection, entry.key))
+        .collect();
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+    for (name, entry) in names.iter().zip(&entries) {
+        println!("{:width$}  {}", name, entry_value(entry));
+    }
+    if entries.len() > limit {
+        println!("… {} more (use --limit)", entries.len() - limit);
+    }
+}
+
+/// A token's value as `tokens list` and `tokens search` print it.
+fn entry_value(entry: &token_search::TokenEntry) -> String {
+    let mut value = entry.css.clone();
+    if let Some(dark) = &entry.dark {
+        value.push_str(&format!("  (dark: {})", dark));
+    }
+    if let Some(reference) = &entry.alias_of {
+        value.push_str(&format!("  = {{{}}}", reference));
+    }
+    value
+}
+
+fn run_resolve(source: &ThemeSource, token: &str, property: &str) {
+    let theme = source_theme(source);
+    let token = token.strip_prefix('@').unwrap_or(token);
+    let explained = resolver::explain_token(property, token, &theme).unwrap_or_else(|e| {
+        eprintln!("✗ {}", e);
+        process::exit(1);
+    });
+
+    println!("{}: {};", property, explained.css);
+    match explained.chosen_by {
+        ChosenBy::Qualifier => println!("  chosen    by the token's qualifier"),
+        ChosenBy::CustomName => println!("  chosen    by the custom property's name"),
+        ChosenBy::TokenShape => println!("  chosen    as the collection holding the token"),
+        ChosenBy::Property | ChosenBy::ColorsFallback => {}
+    }
+    if let Some(name) = &explained.property {
+        match &explained.alias_for {
+            Some(target) => println!("  property  {} → {} (alias)", name, target),
+            None if name != property => println!("  property  {}", name),
+            None => {}
+        }
+    }
+    let mapped = explained.alias_for.as_ref().or(explained.property.as_ref());
+    if let (Some(name), Some(collection)) = (mapped, &explained.mapping) {
+        println!("  mapping   {} → {}", name, collection);
+    }
+    if explained.chosen_by == ChosenBy::ColorsFallback {
+        println!("  fallback  not in the mapped collection; taken from colors");
+    }
+    match &explained.origin {
+        Origin::Url => println!(
+            "  url       @({}) is written as url()",
+            token.trim_matches(['(', ')'])
+        ),
+        Origin::Override { property, key } => {
+            println!("  override  {}.overrides.{}", property, key)
+        }
+        Origin::Arbitrary(value) => println!("  value     {} (arbitrary)", value),
+        Origin::Token(origin) => println!("  token     {}", token_origin(origin)),
+    }
+    match &explained.opacity {
+        Some(Opacity::Token(opacity)) => println!("  opacity   {}", token_origin(opacity)),
+        Some(Opacity::Arbitrary(alpha)) => println!("  opacity   {} (arbitrary)", alpha),
+        None => {}
+    }
+    if let Some(dark) = &theme.dark
+        && let Ok(dark) = resolver::explain_token(property, token, dark)
+        && dark.css != explained.css
+    {
+        println!("  dark      {}", dark.css);
+    }
+}
+
+/// `colors.primary = {colors.blue-500} (theme/tokens.json)`
+fn token_origin(origin: &resolver::TokenOrigin) -> String {
+    let mut text = format!("{}.{}", origin.collection, origin.key);
+    if let Some(reference) = &origin.alias_of {
+        text.push_str(&format!(" = {{{}}}", reference));
+    }
+    if let Some(source) = &origin.source {
+        text.push_str(&format!(" ({})", source));
+    }
+    text
+}
+
+fn run_check(dir: &str, builtin: bool, strict: bool) {
+    let (label, issues) = if !builtin {
+        (dir, theme_check::check_dir(dir))
+    } else if dir == DEFAULT_THEME && !Path::new(dir).exists() {
+        ("the built-in theme", theme_check::check_over_builtin(None))
+    } else {
+        (dir, theme_check::check_over_builtin(Some(dir)))
+    };
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    let warnings = issues.len() - errors;
+    for issue in &issues {
+        let label = match issue.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        eprintln!("{}: {}", label, issue);
+    }
+
+    if errors > 0 || (strict && warnings > 0) {
+        eprintln!("✗ {}: {} error(s), {} warning(s)", label, errors, warnings);
+        process::exit(1);
+    }
+    match warnings {
+        0 => println!("✓ {} is valid", label),
+        _ => println!("✓ {} is valid, with {} warning(s)", label, warnings),
+    }
+}
+
+fn run_generate(from: &str, output: &str, check: bool) {
+    let source = read_json(from);
+    let committed = fs::read_to_string(output).ok();
+    let previous: Option<Value> = committed.as_deref().map(|text| {
+        serde_json::from_str(text).unwrap_or_else(|e| panic!("Invalid JSON in {}: {}", output, e))
+    });
+
+    let tokens = tailwind::generate(&source, previous.as_ref())
+        .unwrap_or_else(|e| panic!("Failed to generate tokens from {}: {}", from, e));
+    let text = tailwind::to_json(&tokens);
+
+    if check {
+        if committed.as_deref() == Some(text.as_str()) {
+            println!("✓ {} is up to date with {}", output, from);
+            return;
+        }
+        eprintln!(
+            "✗ {} is stale; run `rcss theme generate --from {}`",
+            output, from
+        );
+        if let Some(previous) = &previous {
+            for entry in tailwind::stale_entries(previous, &tokens) {
+                eprintln!("  {}", entry);
+            }
+        }
+        process::exit(1);
+    }
+
+    fs::write(output, text).expect("Failed to write tokens");
+    println!("✓ Generated {} from {}", output, from);
+}
+
+fn read_json(path: &str) -> Value {
+    let text =
+        fs::read_to_string(path).unwrap_or_else(|e| panic!("Could not read {}: {}", path, e));
+    serde_json::from_str(&text).unwrap_or_else(|e| panic!("Invalid JSON in {}: {}", path, e))
+}
+
+/// The `--theme` related options of `rcss build`.
+struct ThemeArgs<'a> {
+    dirs: &'a [String],
+    scope: Option<&'a str>,
+    builtin: bool,
+}
+
+fn run_build(
+    input_path: &str,
+    output_override: Option<String>,
+    themes: &ThemeArgs,
+    options: &emitter::EmitOptions,
+) {
     // Determine output path
     let output_path = match output_override {
         Some(custom) => custom,
         None => auto_output_name(input_path),
     };
 
-    let theme = Theme::load_from_dir("theme").expect("Failed to load theme");
     let src = load_with_imports(Path::new(input_path))
         .unwrap_or_else(|e| panic!("Failed to load RCSS imports: {}", e));
 
     let stylesheet = parser::parse(&src).expect("Failed to parse RCSS");
 
-    let stylesheet = resolver::resolve(stylesheet, &theme).expect("Failed to resolve tokens");
+    let resolved: Vec<(String, Stylesheet)> = themes
+        .dirs
+        .iter()
+        .map(|arg| {
+            let (name, dir) = theme_arg(arg);
+            let theme = load_theme(dir, themes.builtin)
+                .unwrap_or_else(|e| panic!("Failed to load theme {}: {}", dir, e));
+            let sheet = resolver::resolve(stylesheet.clone(), &theme)
+                .unwrap_or_else(|e| panic!("Failed to resolve tokens for theme {}: {}", name, e));
+            (name, sheet)
+        })
+        .collect();
 
-    let css = emitter::emit_css(&stylesheet);
+    if let Some(attribute) = themes.scope {
+        let combined =
+            theme_scope::scope_by_theme(resolved, attribute).expect("Failed to scope themes");
+        write_css(input_path, &output_path, &combined, options);
+        return;
+    }
 
-    fs::write(&output_path, css).expect("Failed to write CSS output");
+    let per_theme = resolved.len() > 1;
+    for (name, sheet) in &resolved {
+        let path = if per_theme {
+            themed_output_name(&output_path, name)
+        } else {
+            output_path.clone()
+        };
+        write_css(input_path, &path, sheet, options);
+    }
+}
+
+fn write_css(
+    input_path: &str,
+    output_path: &str,
+    stylesheet: &Stylesheet,
+    options: &emitter::EmitOptions,
+) {
+    let css = emitter::emit_css_with_options(stylesheet, options);
+
+    fs::write(output_path, css).expect("Failed to write CSS output");
 
     println!("✓ Built {} → {}", input_path, output_path);
 }
 
+/// A theme directory over the built-in theme, unless `builtin` is off. The
+/// default directory may be missing, leaving the built-in theme alone, so
+/// builds work outside a project with its own theme.
+fn load_theme(dir: &str, builtin: bool) -> Result<Theme, String> {
+    if !builtin {
+        Theme::load_from_dir(dir)
+    } else if dir == DEFAULT_THEME && !Path::new(dir).exists() {
+        Theme::builtin()
+    } else {
+        Theme::load_over_builtin(dir)
+    }
+}
+
+/// `NAME=DIR`, or a bare directory named after its last component.
+fn theme_arg(arg: &str) -> (String, &str) {
+    if let Some((name, dir)) = arg.split_once('=') {
+        return (name.to_string(), dir);
+    }
+    let name = Path::new(arg)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| arg.to_string());
+    (name, arg)
+}
+
+/// `out.css` → `out.brand-a.css`
+fn themed_output_name(output: &str, theme: &str) -> String {
+    match output.strip_suffix(".css") {
+        Some(stem) => format!("{}.{}.css", stem, theme),
+        None => format!("{}.{}", output, theme),
+    }
+}
+
 fn auto_output_name(input: &str) -> String {
     if let Some(stripped) = input.strip_suffix(".rcss") {
         format!("{}.css", stripped)
@@ -0,0 +1,175 @@
+//! Resolution benchmark on a generated 10k-declaration stylesheet.
+//!
+//! Run with `cargo bench -p rcss-core --bench resolve`. The `resolve` line
+//! times the whole resolver; run it on the commits before and after a
+//! resolver change to compare. The lookup lines time the token index
+//! against a reference copy of the lookup it replaced, on the same theme.
+
+use rcss_core::theme::{PropertyMapping, Theme};
+use rcss_core::{parser, resolver, tokens};
+use std::time::{Duration, Instant};
+
+const RULES: usize = 1_250;
+const ITERATIONS: usize = 10;
+
+/// Eight token-heavy declarations per rule, spread over colors, spacing,
+/// radii, typography and opacity so every lookup path is exercised.
+fn generate_input() -> String {
+    const COLORS: [&str; 6] = ["blue", "slate", "red", "green", "amber", "violet"];
+    const SHADES: [&str; 5] = ["100", "300", "500", "700", "900"];
+    const STEPS: [&str; 6] = ["1", "2", "4", "6", "8", "12"];
+    const RADII: [&str; 4] = ["sm", "md", "lg", "xl"];
+    const SIZES: [&str; 4] = ["sm", "base", "lg", "xl"];
+
+    let mut out = String::from("%no-base\n");
+    for i in 0..RULES {
+        let color = COLORS[i % COLORS.len()];
+        let shade = SHADES[i % SHADES.len()];
+        out.push_str(&format!(
+            ".rule-{i} {{ color: @{color}-{shade}; background-color: @{color}-{bg}; padding: @{p}; margin: @{m}; border-radius: @{r}; font-size: @{s}; opacity: @{o}; border-color: @{color}-{shade}/50; }}\n",
+            bg = SHADES[(i + 2) % SHADES.len()],
+            p = STEPS[i % STEPS.len()],
+            m = STEPS[(i + 3) % STEPS.len()],
+            r = RADII[i % RADII.len()],
+            s = SIZES[i % SIZES.len()],
+            o = (i % 20 + 1) * 5,
+        ));
+    }
+    out
+}
+
+/// Time `run` over `ITERATIONS` runs, returning (best, mean).
+fn measure(mut run: impl FnMut()) -> (Duration, Duration) {
+    run();
+    let mut best = Duration::MAX;
+    let mut total = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        run();
+        let elapsed = start.elapsed();
+        best = best.min(elapsed);
+        total += elapsed;
+    }
+    (best, total / ITERATIONS as u32)
+}
+
+/// The lookup the token index replaced, kept as it was: camel-case the
+/// property, walk the alias chain, then look the token up in the typed
+/// collection and render it.
+mod pre_index {
+    use super::*;
+
+    fn normalize_property(property: &str) -> String {
+        if !property.contains('-') {
+            return property.to_string();
+        }
+
+        let mut result = String::new();
+        for (i, part) in property.split('-').enumerate() {
+            if i == 0 {
+                result.push_str(part);
+            } else {
+                let mut chars = part.chars();
+                if let Some(first) = chars.next() {
+                    result.push(first.to_ascii_uppercase());
+                    result.push_str(chars.as_str());
+                }
+            }
+        }
+        result
+    }
+
+    fn property_mapping<'a>(property_key: &str, theme: &'a Theme) -> Option<&'a PropertyMapping> {
+        let alias = match property_key {
+            "color" => "textColor",
+            "background" => "backgroundColor",
+            "from" | "via" | "to" => "gradientColorStops",
+            "shadow" => "boxShadow",
+            "offsetWidth" => "ringOffsetWidth",
+            "offsetColor" => "ringOffsetColor",
+            "family" => "fontFamily",
+            "size" => "fontSize",
+            "weight" => "fontWeight",
+            "radius" => "borderRadius",
+            key if key.starts_with("border") && key.ends_with("Radius") => "borderRadius",
+            key => key,
+        };
+        theme.properties.get(alias)
+    }
+
+    pub fn lookup(property: &str, token: &str, theme: &Theme) -> Option<String> {
+        let key = normalize_property(property);
+        let mapping = property_mapping(&key, theme)?;
+        let value = match mapping.overrides.get(token) {
+            Some(value) => value,
+            None => theme.token(&mapping.collection, token).ok()?,
+        };
+        tokens::value_css(value, &key)
+    }
+}
+
+/// 10k property/token lookups through the precomputed index versus the
+/// pre-index lookup.
+fn bench_lookups(theme: &Theme) {
+    const LOOKUPS: [(&str, &str); 8] = [
+        ("color", "blue-500"),
+        ("background-color", "slate-100"),
+        ("padding", "4"),
+        ("margin", "2"),
+        ("border-radius", "lg"),
+        ("font-size", "lg"),
+        ("opacity", "50"),
+        ("border-color", "red-300"),
+    ];
+    let count = RULES * LOOKUPS.len();
+
+    for (property, token) in LOOKUPS {
+        let indexed = theme.index.property(property).and_then(|m| m.get(token));
+        assert_eq!(
+            indexed,
+            pre_index::lookup(property, token, theme).as_deref(),
+            "{property}: @{token}"
+        );
+    }
+
+    let (best, mean) = measure(|| {
+        for i in 0..RULES {
+            for (property, token) in LOOKUPS {
+                let css = theme.index.property(property).and_then(|m| m.get(token));
+                std::hint::black_box((i, css));
+            }
+        }
+    });
+    println!("lookup, token index: {count} lookups, best {best:.2?}, mean {mean:.2?}");
+
+    let (best, mean) = measure(|| {
+        for i in 0..RULES {
+            for (property, token) in LOOKUPS {
+                let css = pre_index::lookup(property, token, theme);
+                std::hint::black_box((i, css));
+            }
+        }
+    });
+    println!("lookup, pre-index: {count} lookups, best {best:.2?}, mean {mean:.2?}");
+}
+
+fn main() {
+    let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+    let theme = Theme::load_from_dir(&theme_dir).expect("load theme");
+    let input = generate_input();
+    let parse = || parser::parse(&input).expect("parse generated input");
+    let declarations: usize = parse().rules.iter().map(|r| r.declarations.len()).sum();
+
+    // Parse every run's input up front so only resolution is timed.
+    let mut sheets: Vec<_> = (0..=ITERATIONS).map(|_| parse()).collect();
+    let (best, mean) = measure(|| {
+        let sheet = sheets.pop().expect("one sheet per run");
+        resolver::resolve(sheet, &theme).expect("resolve");
+    });
+    println!(
+        "resolve: {} declarations, best {:.2?}, mean {:.2?} over {} runs",
+        declarations, best, mean, ITERATIONS
+    );
+
+    bench_lookups(&theme);
+}
@@ -1,11 +1,16 @@
 use crate::error::Span;
 use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Rule {
     pub selector: String,
     pub declarations: Vec<Declaration>,
     pub media: Vec<MediaBlock>,
+    /// Rules nested inside this one, in source order.
+    pub children: Vec<Rule>,
+    /// The selector as written inside the parent block (`&:hover`, `.icon`).
+    /// `None` for top-level rules.
+    pub nested_header: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -13,16 +18,30 @@ pub struct Declaration {
     pub property: String,
     pub value: String,
     pub span: Span,
+    /// Set by the resolver when the value names a theme token, directly or
+    /// through a variable, preset or shorthand, so another theme may render
+    /// it differently.
+    pub themed: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MediaBlock {
     pub query: String,
     pub declarations: Vec<Declaration>,
 }
 
-#[derive(Debug)]
+/// A `%name` directive; `dark` is set for `%dark name`.
+#[derive(Debug, Clone)]
+pub struct PresetUse {
+    pub name: String,
+    pub dark: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
 pub struct Stylesheet {
     pub rules: Vec<Rule>,
     pub variables: HashMap<String, String>,
+    /// Preset directives in source order, expanded by the resolver.
+    pub presets: Vec<PresetUse>,
 }
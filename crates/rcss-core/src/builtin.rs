@@ -0,0 +1,126 @@
+use crate::theme::{self, ThemeFiles};
+
+/// `(path, contents)` of a file from the repository's `theme/` directory,
+/// embedded at compile time. Paths are prefixed with `builtin:` so token
+/// sources and errors say where a value came from.
+macro_rules! theme_file {
+    ($path:literal) => {
+        (
+            concat!("builtin:", $path),
+            include_str!(concat!("../../../theme/", $path)),
+        )
+    };
+}
+
+const TOKENS_JSON: (&str, &str) = theme_file!("tokens.json");
+const SHORTHANDS: (&str, &str) = theme_file!("shorthands.json");
+const PRESETS: (&str, &str) = theme_file!("presets.json");
+
+/// Every file of `theme/tokens/`; keep in sync with the directory (a test
+/// checks it).
+const TOKEN_FILES: &[(&str, &str)] = &[
+    theme_file!("tokens/backdrop-blur.json"),
+    theme_file!("tokens/background-image.json"),
+    theme_file!("tokens/border-radius.json"),
+    theme_file!("tokens/box-shadow.json"),
+    theme_file!("tokens/colors.json"),
+    theme_file!("tokens/colors.semantic.json"),
+    theme_file!("tokens/font-size.json"),
+    theme_file!("tokens/grid.json"),
+    theme_file!("tokens/letter-spacing.json"),
+    theme_file!("tokens/line-height.json"),
+    theme_file!("tokens/opacity.json"),
+    theme_file!("tokens/ring-offset-color.json"),
+    theme_file!("tokens/ring-offset-width.json"),
+    theme_file!("tokens/ring-opacity.json"),
+    theme_file!("tokens/ring-width.json"),
+    theme_file!("tokens/screens.json"),
+    theme_file!("tokens/spacing.json"),
+];
+
+/// The built-in theme as one layer, in the order a directory is read.
+pub(crate) fn files() -> Result<ThemeFiles, String> {
+    let owned = |(path, data): (&str, &str)| Some((path.to_string(), data.to_string()));
+    let mut token_files = Vec::new();
+    for (path, data) in TOKEN_FILES {
+        let stem = path
+            .rsplit('/')
+            .next()
+            .and_then(|name| name.strip_suffix(".json"))
+            .unwrap_or_default();
+        if let Some(file) = theme::token_file(path.to_string(), stem, data)? {
+            token_files.push(file);
+        }
+    }
+    token_files.sort_by_key(|file| file.layered);
+
+    Ok(ThemeFiles {
+        tokens_json: owned(TOKENS_JSON),
+        dtcg: Vec::new(),
+        token_files,
+        shorthands: owned(SHORTHANDS),
+        presets: owned(PRESETS),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::Theme;
+
+    #[test]
+    fn builtin_embeds_every_theme_file() {
+        let dir = format!("{}/../../theme/tokens", env!("CARGO_MANIFEST_DIR"));
+        let mut on_disk: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                format!(
+                    "builtin:tokens/{}",
+                    entry.unwrap().file_name().to_string_lossy()
+                )
+            })
+            .collect();
+        on_disk.sort();
+        let embedded: Vec<&str> = TOKEN_FILES.iter().map(|(path, _)| *path).collect();
+        assert_eq!(embedded, on_disk);
+
+        let theme = Theme::builtin().expect("built-in theme loads");
+        assert_eq!(
+            theme.token_source("colors", "primary"),
+            Some("builtin:tokens/colors.semantic.json")
+        );
+    }
+
+    #[test]
+    fn the_checkout_theme_is_the_builtin_theme() {
+        let dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let on_disk = ThemeFiles::read(std::path::Path::new(&dir), true).unwrap();
+        assert!(files().unwrap().same_contents(&on_disk));
+
+        // Layering it over the built-in theme would define every token twice.
+        let theme = Theme::load_over_builtin(&dir).unwrap();
+        assert_eq!(
+            theme.token_source("colors", "primary"),
+            Some("builtin:tokens/colors.semantic.json")
+        );
+    }
+
+    #[test]
+    fn directories_layer_over_the_builtin_theme() {
+        let dir = std::env::temp_dir().join(format!("rcss-over-builtin-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("tokens")).unwrap();
+        std::fs::write(
+            dir.join("tokens/colors.json"),
+            r##"{ "blue": { "500": "#0000ff" } }"##,
+        )
+        .unwrap();
+
+        let theme = Theme::load_over_builtin(&dir.display().to_string()).unwrap();
+        assert_eq!(
+            theme.token("colors", "blue-500").unwrap().to_css(),
+            "#0000ff"
+        );
+        assert_eq!(theme.token("spacing", "4").unwrap().to_css(), "1rem");
+    }
+}
@@ -0,0 +1,896 @@
+use std::f64::consts::PI;
+
+/// A color stored as gamma-encoded sRGB channels in `0.0..=1.0`. Channels may
+/// fall outside that range for wide-gamut inputs such as `oklch(...)`; they
+/// are only gamut mapped when serialized to sRGB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub alpha: f64,
+}
+
+/// Interpolation space for mixing, as in `color-mix(in <space>, ...)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    SrgbLinear,
+    Hsl,
+    Oklab,
+    Oklch,
+}
+
+impl ColorSpace {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "srgb" => Some(ColorSpace::Srgb),
+            "srgb-linear" => Some(ColorSpace::SrgbLinear),
+            "hsl" => Some(ColorSpace::Hsl),
+            "oklab" => Some(ColorSpace::Oklab),
+            "oklch" => Some(ColorSpace::Oklch),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorSpace::Srgb => "srgb",
+            ColorSpace::SrgbLinear => "srgb-linear",
+            ColorSpace::Hsl => "hsl",
+            ColorSpace::Oklab => "oklab",
+            ColorSpace::Oklch => "oklch",
+        }
+    }
+}
+
+/// Just-noticeable difference in OKLab used by the CSS gamut mapping
+/// algorithm.
+const JND: f64 = 0.02;
+
+impl Color {
+    pub const fn rgb(r: f64, g: f64, b: f64) -> Self {
+        Color {
+            r,
+            g,
+            b,
+            alpha: 1.0,
+        }
+    }
+
+    /// Parse a static CSS color: hex, `rgb()`, `hsl()`, `oklch()`, `oklab()`,
+    /// `color-mix()` or a basic named color.
+    pub fn parse(input: &str) -> Result<Color, String> {
+        let trimmed = input.trim();
+        if let Some(hex) = trimmed.strip_prefix('#') {
+            return parse_hex(hex).ok_or_else(|| format!("Invalid hex color '{}'", trimmed));
+        }
+
+        if let Some(open) = trimmed.find('(') {
+            if !trimmed.ends_with(')') {
+                return Err(format!("Invalid color '{}'", trimmed));
+            }
+            let name = trimmed[..open].trim().to_ascii_lowercase();
+            let inner = &trimmed[open + 1..trimmed.len() - 1];
+            return match name.as_str() {
+                "rgb" | "rgba" => parse_rgb(inner),
+                "hsl" | "hsla" => parse_hsl(inner),
+                "oklch" => parse_oklch(inner),
+                "oklab" => parse_oklab(inner),
+                "color-mix" => parse_color_mix(inner),
+                _ => Err(format!("Unsupported color function '{}()'", name)),
+            }
+            .map_err(|e| format!("{} in '{}'", e, trimmed));
+        }
+
+        named_color(&trimmed.to_ascii_lowercase())
+            .ok_or_else(|| format!("Unknown color '{}'", trimmed))
+    }
+
+    pub fn from_oklab(lab: [f64; 3], alpha: f64) -> Self {
+        let [r, g, b] = oklab_to_linear_srgb(lab).map(linear_to_gamma);
+        Color { r, g, b, alpha }
+    }
+
+    pub fn from_oklch(lch: [f64; 3], alpha: f64) -> Self {
+        Self::from_oklab(oklch_to_oklab(lch), alpha)
+    }
+
+    pub fn from_hsl(hsl: [f64; 3], alpha: f64) -> Self {
+        let [r, g, b] = hsl_to_srgb(hsl);
+        Color { r, g, b, alpha }
+    }
+
+    pub fn to_oklab(&self) -> [f64; 3] {
+        linear_srgb_to_oklab([self.r, self.g, self.b].map(gamma_to_linear))
+    }
+
+    pub fn to_oklch(&self) -> [f64; 3] {
+        oklab_to_oklch(self.to_oklab())
+    }
+
+    /// HSL of the gamut-mapped color; HSL cannot describe colors outside
+    /// sRGB.
+    pub fn to_hsl(&self) -> [f64; 3] {
+        let mapped = self.to_srgb_gamut();
+        srgb_to_hsl([mapped.r, mapped.g, mapped.b])
+    }
+
+    pub fn with_alpha(&self, alpha: f64) -> Self {
+        Color {
+            alpha: alpha.clamp(0.0, 1.0),
+            ..*self
+        }
+    }
+
+    pub fn in_srgb_gamut(&self) -> bool {
+        const EPS: f64 = 1e-6;
+        [self.r, self.g, self.b]
+            .iter()
+            .all(|c| (-EPS..=1.0 + EPS).contains(c))
+    }
+
+    /// Bring the color into sRGB by reducing OKLCH chroma until clipping is
+    /// imperceptible (CSS Color 4 gamut mapping).
+    pub fn to_srgb_gamut(&self) -> Color {
+        if self.in_srgb_gamut() {
+            return self.clip();
+        }
+
+        let [l, c, h] = self.to_oklch();
+        if l >= 1.0 {
+            return Color::rgb(1.0, 1.0, 1.0).with_alpha(self.alpha);
+        }
+        if l <= 0.0 {
+            return Color::rgb(0.0, 0.0, 0.0).with_alpha(self.alpha);
+        }
+
+        let mut clipped = self.clip();
+        if delta_eok(clipped.to_oklab(), self.to_oklab()) < JND {
+            return clipped;
+        }
+
+        let mut min = 0.0;
+        let mut max = c;
+        let mut min_in_gamut = true;
+        while max - min > 1e-4 {
+            let chroma = (min + max) / 2.0;
+            let current = Color::from_oklch([l, chroma, h], self.alpha);
+            if min_in_gamut && current.in_srgb_gamut() {
+                min = chroma;
+                continue;
+            }
+            clipped = current.clip();
+            let e = delta_eok(clipped.to_oklab(), current.to_oklab());
+            if e < JND {
+                if JND - e < 1e-4 {
+                    return clipped;
+                }
+                min_in_gamut = false;
+                min = chroma;
+            } else {
+                max = chroma;
+            }
+        }
+        clipped
+    }
+
+    fn clip(&self) -> Color {
+        Color {
+            r: self.r.clamp(0.0, 1.0),
+            g: self.g.clamp(0.0, 1.0),
+            b: self.b.clamp(0.0, 1.0),
+            alpha: self.alpha.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Serialize as `#rrggbb`, or `rgba(r, g, b, a)` when translucent, after
+    /// gamut mapping into sRGB.
+    pub fn to_srgb_string(&self) -> String {
+        let mapped = self.to_srgb_gamut();
+        let [r, g, b] = [mapped.r, mapped.g, mapped.b].map(|c| (c * 255.0).round() as u8);
+        if mapped.alpha >= 1.0 {
+            format!("#{:02x}{:02x}{:02x}", r, g, b)
+        } else {
+            format!(
+                "rgba({}, {}, {}, {})",
+                r,
+                g,
+                b,
+                format_number(mapped.alpha, 3)
+            )
+        }
+    }
+
+    pub fn to_oklch_string(&self) -> String {
+        let [l, c, h] = self.to_oklch();
+        let mut out = format!(
+            "oklch({}% {} {}",
+            format_number(l * 100.0, 2),
+            format_number(c, 4),
+            format_number(h, 3)
+        );
+        if self.alpha < 1.0 {
+            out.push_str(&format!(" / {}", format_number(self.alpha, 3)));
+        }
+        out.push(')');
+        out
+    }
+
+    /// WCAG 2 relative luminance of the gamut-mapped color.
+    pub fn luminance(&self) -> f64 {
+        let mapped = self.to_srgb_gamut();
+        let [r, g, b] = [mapped.r, mapped.g, mapped.b].map(gamma_to_linear);
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
+
+    /// WCAG 2 contrast ratio between two colors, from 1 to 21.
+    pub fn contrast_ratio(&self, other: &Color) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    /// Rotate the hue by `degrees` in HSL, or in OKLCH for the OK spaces and
+    /// for colors outside sRGB, which HSL would gamut map first.
+    pub fn adjust_hue(&self, degrees: f64, space: ColorSpace) -> Color {
+        match space {
+            ColorSpace::Oklch | ColorSpace::Oklab => {
+                let [l, c, h] = self.to_oklch();
+                Color::from_oklch([l, c, (h + degrees).rem_euclid(360.0)], self.alpha)
+            }
+            _ if !self.in_srgb_gamut() => self.adjust_hue(degrees, ColorSpace::Oklch),
+            _ => {
+                let [h, s, l] = self.to_hsl();
+                Color::from_hsl([(h + degrees).rem_euclid(360.0), s, l], self.alpha)
+            }
+        }
+    }
+
+    /// Add `amount` (a fraction, negative to desaturate) to the HSL
+    /// saturation, or to OKLCH chroma where 100% is a chroma of 0.4. Colors
+    /// outside sRGB always change chroma: their HSL saturation is already
+    /// clipped at the gamut edge.
+    pub fn saturate(&self, amount: f64, space: ColorSpace) -> Color {
+        match space {
+            ColorSpace::Oklch | ColorSpace::Oklab => {
+                let [l, c, h] = self.to_oklch();
+                Color::from_oklch([l, (c + amount * 0.4).max(0.0), h], self.alpha)
+            }
+            _ if !self.in_srgb_gamut() => self.saturate(amount, ColorSpace::Oklch),
+            _ => {
+                let [h, s, l] = self.to_hsl();
+                Color::from_hsl([h, (s + amount).clamp(0.0, 1.0), l], self.alpha)
+            }
+        }
+    }
+
+    /// Interpolate towards `other` by `amount` (0 keeps `self`, 1 yields
+    /// `other`) using premultiplied alpha, like `color-mix()`.
+    pub fn mix(&self, other: &Color, amount: f64, space: ColorSpace) -> Color {
+        let t = amount.clamp(0.0, 1.0);
+        let alpha = lerp(self.alpha, other.alpha, t);
+        let a = to_space(self, space);
+        let b = to_space(other, space);
+        let (a, b) = match space {
+            ColorSpace::Hsl => fix_hue(a, b, 0, self, other),
+            ColorSpace::Oklch => fix_hue(a, b, 2, self, other),
+            _ => (a, b),
+        };
+
+        let hue_index = match space {
+            ColorSpace::Hsl => Some(0),
+            ColorSpace::Oklch => Some(2),
+            _ => None,
+        };
+
+        let mut channels = [0.0; 3];
+        for i in 0..3 {
+            if Some(i) == hue_index {
+                channels[i] = lerp(a[i], b[i], t).rem_euclid(360.0);
+            } else if alpha > 0.0 {
+                let premultiplied = lerp(a[i] * self.alpha, b[i] * other.alpha, t);
+                channels[i] = premultiplied / alpha;
+            } else {
+                channels[i] = lerp(a[i], b[i], t);
+            }
+        }
+
+        from_space(channels, alpha, space)
+    }
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn to_space(color: &Color, space: ColorSpace) -> [f64; 3] {
+    match space {
+        ColorSpace::Srgb => [color.r, color.g, color.b],
+        ColorSpace::SrgbLinear => [color.r, color.g, color.b].map(gamma_to_linear),
+        ColorSpace::Hsl => color.to_hsl(),
+        ColorSpace::Oklab => color.to_oklab(),
+        ColorSpace::Oklch => color.to_oklch(),
+    }
+}
+
+fn from_space(channels: [f64; 3], alpha: f64, space: ColorSpace) -> Color {
+    match space {
+        ColorSpace::Srgb => Color {
+            r: channels[0],
+            g: channels[1],
+            b: channels[2],
+            alpha,
+        },
+        ColorSpace::SrgbLinear => {
+            let [r, g, b] = channels.map(linear_to_gamma);
+            Color { r, g, b, alpha }
+        }
+        ColorSpace::Hsl => Color::from_hsl(channels, alpha),
+        ColorSpace::Oklab => Color::from_oklab(channels, alpha),
+        ColorSpace::Oklch => Color::from_oklch(channels, alpha),
+    }
+}
+
+/// Achromatic colors have no meaningful hue; borrow the other color's hue and
+/// take the shorter arc otherwise.
+fn fix_hue(
+    mut a: [f64; 3],
+    mut b: [f64; 3],
+    hue: usize,
+    first: &Color,
+    second: &Color,
+) -> ([f64; 3], [f64; 3]) {
+    // Saturation (HSL) and chroma (OKLCH) both sit at index 1.
+    let achromatic = |c: &[f64; 3], color: &Color| {
+        let lightness = color.to_hsl()[2];
+        c[1] < 1e-4 || (hue == 0 && (lightness <= 1e-4 || lightness >= 1.0 - 1e-4))
+    };
+    if achromatic(&a, first) {
+        a[hue] = b[hue];
+    } else if achromatic(&b, second) {
+        b[hue] = a[hue];
+    }
+    let diff = b[hue] - a[hue];
+    if diff > 180.0 {
+        a[hue] += 360.0;
+    } else if diff < -180.0 {
+        b[hue] += 360.0;
+    }
+    (a, b)
+}
+
+pub fn format_number(value: f64, decimals: usize) -> String {
+    let mut out = format!("{:.*}", decimals, value);
+    if out.contains('.') {
+        while out.ends_with('0') {
+            out.pop();
+        }
+        if out.ends_with('.') {
+            out.pop();
+        }
+    }
+    if out == "-0" {
+        out = "0".to_string();
+    }
+    out
+}
+
+fn delta_eok(a: [f64; 3], b: [f64; 3]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+fn gamma_to_linear(c: f64) -> f64 {
+    let abs = c.abs();
+    if abs <= 0.04045 {
+        c / 12.92
+    } else {
+        c.signum() * ((abs + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_gamma(c: f64) -> f64 {
+    let abs = c.abs();
+    if abs <= 0.0031308 {
+        c * 12.92
+    } else {
+        c.signum() * (1.055 * abs.powf(1.0 / 2.4) - 0.055)
+    }
+}
+
+fn linear_srgb_to_oklab([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let l = 0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b;
+    let m = 0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b;
+    let s = 0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b;
+    let (l, m, s) = (l.cbrt(), m.cbrt(), s.cbrt());
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+fn oklab_to_linear_srgb([l, a, b]: [f64; 3]) -> [f64; 3] {
+    let l_ = l + 0.3963377774 * a + 0.2158037573 * b;
+    let m_ = l - 0.1055613458 * a - 0.0638541728 * b;
+    let s_ = l - 0.0894841775 * a - 1.2914855480 * b;
+    let (l, m, s) = (l_.powi(3), m_.powi(3), s_.powi(3));
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ]
+}
+
+fn oklab_to_oklch([l, a, b]: [f64; 3]) -> [f64; 3] {
+    let c = (a * a + b * b).sqrt();
+    let h = if c < 1e-8 {
+        0.0
+    } else {
+        b.atan2(a).to_degrees().rem_euclid(360.0)
+    };
+    [l, c, h]
+}
+
+fn oklch_to_oklab([l, c, h]: [f64; 3]) -> [f64; 3] {
+    let rad = h * PI / 180.0;
+    [l, c * rad.cos(), c * rad.sin()]
+}
+
+fn srgb_to_hsl([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+    if d.abs() < 1e-10 {
+        return [0.0, 0.0, l];
+    }
+    let s = if l == 0.0 || l == 1.0 {
+        0.0
+    } else {
+        (max - l) / l.min(1.0 - l)
+    };
+    let h = if max == r {
+        (g - b) / d + if g < b { 6.0 } else { 0.0 }
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    [h * 60.0, s, l]
+}
+
+fn hsl_to_srgb([h, s, l]: [f64; 3]) -> [f64; 3] {
+    let h = h.rem_euclid(360.0);
+    let f = |n: f64| {
+        let k = (n + h / 30.0) % 12.0;
+        let a = s * l.min(1.0 - l);
+        l - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    [f(0.0), f(8.0), f(4.0)]
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let expanded: String = match hex.len() {
+        3 | 4 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 | 8 => hex.to_string(),
+        _ => return None,
+    };
+    let channel = |i: usize| {
+        u8::from_str_radix(&expanded[i * 2..i * 2 + 2], 16)
+            .ok()
+            .map(|v| v as f64 / 255.0)
+    };
+    Some(Color {
+        r: channel(0)?,
+        g: channel(1)?,
+        b: channel(2)?,
+        alpha: if expanded.len() == 8 {
+            channel(3)?
+        } else {
+            1.0
+        },
+    })
+}
+
+/// Split function arguments on commas, whitespace and the `/` alpha separator.
+fn split_channels(inner: &str) -> Result<(Vec<String>, Option<String>), String> {
+    let (main, alpha) = match inner.split_once('/') {
+        Some((main, alpha)) => (main, Some(alpha.trim().to_string())),
+        None => (inner, None),
+    };
+    let mut parts: Vec<String> = main
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+        .collect();
+    let alpha = match alpha {
+        Some(a) => Some(a),
+        None if parts.len() == 4 => parts.pop(),
+        None => None,
+    };
+    if parts.len() != 3 {
+        return Err("expected three channels".to_string());
+    }
+    Ok((parts, alpha))
+}
+
+fn parse_number(raw: &str, percent_scale: f64) -> Result<f64, String> {
+    let raw = raw.trim();
+    if raw == "none" {
+        return Ok(0.0);
+    }
+    if let Some(p) = raw.strip_suffix('%') {
+        return p
+            .parse::<f64>()
+            .map(|v| v / 100.0 * percent_scale)
+            .map_err(|_| format!("invalid number '{}'", raw));
+    }
+    raw.parse::<f64>()
+        .map_err(|_| format!("invalid number '{}'", raw))
+}
+
+fn parse_hue(raw: &str) -> Result<f64, String> {
+    let raw = raw.trim();
+    let (num, factor) = if let Some(v) = raw.strip_suffix("deg") {
+        (v, 1.0)
+    } else if let Some(v) = raw.strip_suffix("grad") {
+        (v, 0.9)
+    } else if let Some(v) = raw.strip_suffix("rad") {
+        (v, 180.0 / PI)
+    } else if let Some(v) = raw.strip_suffix("turn") {
+        (v, 360.0)
+    } else {
+        (raw, 1.0)
+    };
+    if num == "none" {
+        return Ok(0.0);
+    }
+    num.parse::<f64>()
+        .map(|v| v * factor)
+        .map_err(|_| format!("invalid hue '{}'", raw))
+}
+
+fn parse_alpha(alpha: Option<String>) -> Result<f64, String> {
+    match alpha {
+        Some(a) => parse_number(&a, 1.0).map(|v| v.clamp(0.0, 1.0)),
+        None => Ok(1.0),
+    }
+}
+
+fn parse_rgb(inner: &str) -> Result<Color, String> {
+    let (parts, alpha) = split_channels(inner)?;
+    let channel = |raw: &str| -> Result<f64, String> {
+        if raw.ends_with('%') {
+            parse_number(raw, 1.0)
+        } else {
+            parse_number(raw, 1.0).map(|v| v / 255.0)
+        }
+    };
+    Ok(Color {
+        r: channel(&parts[0])?,
+        g: channel(&parts[1])?,
+        b: channel(&parts[2])?,
+        alpha: parse_alpha(alpha)?,
+    })
+}
+
+fn parse_hsl(inner: &str) -> Result<Color, String> {
+    let (parts, alpha) = split_channels(inner)?;
+    let h = parse_hue(&parts[0])?;
+    let s = parse_number(&parts[1], 100.0)? / 100.0;
+    let l = parse_number(&parts[2], 100.0)? / 100.0;
+    Ok(Color::from_hsl([h, s, l], parse_alpha(alpha)?))
+}
+
+fn parse_oklch(inner: &str) -> Result<Color, String> {
+    let (parts, alpha) = split_channels(inner)?;
+    let l = parse_number(&parts[0], 1.0)?;
+    let c = parse_number(&parts[1], 0.4)?;
+    let h = parse_hue(&parts[2])?;
+    Ok(Color::from_oklch([l, c, h], parse_alpha(alpha)?))
+}
+
+fn parse_oklab(inner: &str) -> Result<Color, String> {
+    let (parts, alpha) = split_channels(inner)?;
+    let l = parse_number(&parts[0], 1.0)?;
+    let a = parse_number(&parts[1], 0.4)?;
+    let b = parse_number(&parts[2], 0.4)?;
+    Ok(Color::from_oklab([l, a, b], parse_alpha(alpha)?))
+}
+
+/// `color-mix(in <space>, <color> [<p>%], <color> [<p>%])`
+fn parse_color_mix(inner: &str) -> Result<Color, String> {
+    let args = split_top_level(inner, ',');
+    if args.len() != 3 {
+        return Err("color-mix() expects a color space and two colors".to_string());
+    }
+    let space_name = args[0]
+        .trim()
+        .strip_prefix("in ")
+        .ok_or_else(|| "color-mix() must start with 'in <space>'".to_string())?;
+    let space_name = space_name.split_whitespace().next().unwrap_or_default();
+    let space = ColorSpace::parse(space_name)
+        .ok_or_else(|| format!("unsupported color-mix() space '{}'", space_name))?;
+
+    let (c1, p1) = split_mix_stop(&args[1])?;
+    let (c2, p2) = split_mix_stop(&args[2])?;
+    let (p1, p2) = match (p1, p2) {
+        (None, None) => (0.5, 0.5),
+        (Some(p1), None) => (p1, 1.0 - p1),
+        (None, Some(p2)) => (1.0 - p2, p2),
+        (Some(p1), Some(p2)) => (p1, p2),
+    };
+    let sum = p1 + p2;
+    if sum <= 0.0 {
+        return Err("color-mix() percentages must not sum to zero".to_string());
+    }
+
+    let mixed = c1.mix(&c2, p2 / sum, space);
+    if sum < 1.0 {
+        Ok(mixed.with_alpha(mixed.alpha * sum))
+    } else {
+        Ok(mixed)
+    }
+}
+
+fn split_mix_stop(arg: &str) -> Result<(Color, Option<f64>), String> {
+    let arg = arg.trim();
+    let parts = split_top_level(arg, ' ');
+    let mut color = None;
+    let mut percent = None;
+    for part in parts.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        if let Some(p) = part.strip_suffix('%')
+            && let Ok(value) = p.parse::<f64>()
+        {
+            percent = Some(value / 100.0);
+            continue;
+        }
+        color = Some(Color::parse(part)?);
+    }
+    let color = color.ok_or_else(|| format!("missing color in '{}'", arg))?;
+    Ok((color, percent))
+}
+
+/// Split `input` on `separator` outside of parentheses.
+pub fn split_top_level(input: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+    for ch in input.chars() {
+        match ch {
+            '(' => {
+                depth += 1;
+                current.push(ch);
+            }
+            ')' => {
+                depth = depth.saturating_sub(1);
+                current.push(ch);
+            }
+            c if c == separator && depth == 0 => {
+                parts.push(std::mem::take(&mut current));
+            }
+            _ => current.push(ch),
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+fn named_color(name: &str) -> Option<Color> {
+    let hex = match name {
+        "transparent" => return Some(Color::rgb(0.0, 0.0, 0.0).with_alpha(0.0)),
+        "black" => "000000",
+        "white" => "ffffff",
+        "gray" | "grey" => "808080",
+        "silver" => "c0c0c0",
+        "red" => "ff0000",
+        "maroon" => "800000",
+        "orange" => "ffa500",
+        "yellow" => "ffff00",
+        "olive" => "808000",
+        "lime" => "00ff00",
+        "green" => "008000",
+        "aqua" | "cyan" => "00ffff",
+        "teal" => "008080",
+        "blue" => "0000ff",
+        "navy" => "000080",
+        "fuchsia" | "magenta" => "ff00ff",
+        "purple" => "800080",
+        _ => return None,
+    };
+    parse_hex(hex)
+}
+
+/// Replace every call to one of `names` in `value` with `replace(call)`.
+/// Returns `None` if any replacement fails, so callers never emit a
+/// half-converted value.
+pub fn replace_color_functions(
+    value: &str,
+    names: &[&str],
+    replace: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let mut out = String::new();
+    let mut idx = 0;
+    let lower = value.to_ascii_lowercase();
+
+    'outer: while idx < value.len() {
+        for name in names {
+            let at_boundary = value[..idx]
+                .chars()
+                .next_back()
+                .map(|c| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                .unwrap_or(true);
+            if at_boundary
+                && lower[idx..].starts_with(name)
+                && lower[idx + name.len()..].starts_with('(')
+            {
+                let end = matching_paren(value, idx + name.len())?;
+                out.push_str(&replace(&value[idx..=end])?);
+                idx = end + 1;
+                continue 'outer;
+            }
+        }
+        let ch = value[idx..].chars().next()?;
+        out.push(ch);
+        idx += ch.len_utf8();
+    }
+
+    Some(out)
+}
+
+/// Add `opacity_str` as the alpha of a color written in CSS: inside a
+/// color function (`oklch(… / 0.5)`) or as `rgba()` for hex colors.
+pub fn inject_alpha(base_str: &str, opacity_str: &str) -> Result<String, String> {
+    if base_str.starts_with("oklch(") || base_str.contains('(') {
+        let base = base_str.trim_end_matches(')');
+        return Ok(format!("{} / {})", base, opacity_str));
+    }
+
+    if base_str.starts_with('#') {
+        return hex_to_rgba(base_str, opacity_str);
+    }
+
+    Err(format!("Unsupported color format: {}", base_str))
+}
+
+fn hex_to_rgba(hex: &str, opacity: &str) -> Result<String, String> {
+    let hex = hex.trim_start_matches('#');
+
+    let (r, g, b) = match hex.len() {
+        6 => {
+            let r = u8::from_str_radix(&hex[0..2], 16).unwrap();
+            let g = u8::from_str_radix(&hex[2..4], 16).unwrap();
+            let b = u8::from_str_radix(&hex[4..6], 16).unwrap();
+            (r, g, b)
+        }
+        3 => {
+            let r = u8::from_str_radix(&hex[0..1].repeat(2), 16).unwrap();
+            let g = u8::from_str_radix(&hex[1..2].repeat(2), 16).unwrap();
+            let b = u8::from_str_radix(&hex[2..3].repeat(2), 16).unwrap();
+            (r, g, b)
+        }
+        _ => return Err(format!("Invalid hex color: {}", hex)),
+    };
+
+    Ok(format!("rgba({},{},{},{})", r, g, b, opacity))
+}
+
+fn matching_paren(value: &str, open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (offset, ch) in value[open..].char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + offset);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_formats() {
+        assert_eq!(Color::parse("#fff").unwrap().to_srgb_string(), "#ffffff");
+        assert_eq!(
+            Color::parse("rgb(255 0 0 / 50%)").unwrap().to_srgb_string(),
+            "rgba(255, 0, 0, 0.5)"
+        );
+        assert_eq!(
+            Color::parse("hsl(120, 100%, 25%)")
+                .unwrap()
+                .to_srgb_string(),
+            "#008000"
+        );
+        assert_eq!(
+            Color::parse("oklch(62.8% 0.2577 29.23)")
+                .unwrap()
+                .to_srgb_string(),
+            "#ff0000"
+        );
+    }
+
+    #[test]
+    fn gamut_maps_wide_colors() {
+        // Tailwind blue-500 sits just outside sRGB.
+        let color = Color::parse("oklch(62.3% 0.214 259.815)").unwrap();
+        assert!(!color.in_srgb_gamut());
+        let mapped = color.to_srgb_gamut();
+        assert!(mapped.in_srgb_gamut());
+        let [l, _, h] = mapped.to_oklch();
+        assert!((l - 0.623).abs() < 0.02);
+        assert!((h - 259.815).abs() < 2.0);
+    }
+
+    #[test]
+    fn color_mix_matches_css() {
+        let mixed = Color::parse("color-mix(in srgb, white 50%, #000000)").unwrap();
+        assert_eq!(mixed.to_srgb_string(), "#808080");
+        let faded = Color::parse("color-mix(in srgb, #ff0000 25%, transparent)").unwrap();
+        assert_eq!(faded.to_srgb_string(), "rgba(255, 0, 0, 0.25)");
+    }
+
+    #[test]
+    fn hue_saturation_and_contrast() {
+        let red = Color::parse("#ff0000").unwrap();
+        assert_eq!(
+            red.adjust_hue(180.0, ColorSpace::Hsl).to_srgb_string(),
+            "#00ffff"
+        );
+        assert_eq!(
+            red.saturate(-1.0, ColorSpace::Hsl).to_srgb_string(),
+            "#808080"
+        );
+        let white = Color::parse("white").unwrap();
+        let black = Color::parse("black").unwrap();
+        assert!((white.contrast_ratio(&black) - 21.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn hue_and_saturation_keep_wide_colors_wide() {
+        // Tailwind green-500 and emerald-500 are outside sRGB.
+        for input in ["oklch(72.3% 0.219 149.579)", "oklch(69.6% 0.17 162.48)"] {
+            let color = Color::parse(input).unwrap();
+            assert!(!color.in_srgb_gamut());
+            let [l, c, h] = color.to_oklch();
+            let saturated = color.saturate(0.1, ColorSpace::Hsl).to_oklch();
+            assert!(saturated[1] > c, "{input}: {saturated:?}");
+            assert!((saturated[0] - l).abs() < 1e-3 && (saturated[2] - h).abs() < 1e-3);
+            let desaturated = color.saturate(-0.1, ColorSpace::Hsl).to_oklch();
+            assert!(desaturated[1] < c, "{input}: {desaturated:?}");
+        }
+
+        let complement = Color::parse("oklch(70% 0.4 30)")
+            .unwrap()
+            .adjust_hue(180.0, ColorSpace::Hsl);
+        let [l, c, h] = complement.to_oklch();
+        assert!(l <= 1.0 && (l - 0.7).abs() < 1e-3, "{l}");
+        assert!((c - 0.4).abs() < 1e-3 && (h - 210.0).abs() < 1e-3);
+        assert!(complement.to_oklch_string().starts_with("oklch(70% "));
+
+        // HSL mixing starts from the gamut-mapped color.
+        let [_, s, l] = Color::parse("oklch(70% 0.4 30)").unwrap().to_hsl();
+        assert!((0.0..=1.0).contains(&s) && (0.0..=1.0).contains(&l));
+    }
+
+    #[test]
+    fn replace_only_whole_functions() {
+        let out = replace_color_functions(
+            "0 0 2px oklch(0% 0 0), linear-gradient(red, blue)",
+            &["oklch"],
+            |call| Color::parse(call).ok().map(|c| c.to_srgb_string()),
+        )
+        .unwrap();
+        assert_eq!(out, "0 0 2px #000000, linear-gradient(red, blue)");
+    }
+}
@@ -0,0 +1,688 @@
+use crate::tokens;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// A collection DTCG tokens can land in: tokens of one of `types` inside a
+/// top-level group named like one of `groups` (camelCase) go there, without
+/// that group in their path.
+struct Target {
+    collection: &'static str,
+    groups: &'static [&'static str],
+    types: &'static [&'static str],
+}
+
+const TARGETS: &[Target] = &[
+    Target {
+        collection: "colors",
+        groups: &["color", "colors"],
+        types: &["color"],
+    },
+    Target {
+        collection: "spacing",
+        groups: &["spacing", "space"],
+        types: &["dimension"],
+    },
+    Target {
+        collection: "borderRadius",
+        groups: &["radius", "radii", "borderRadius"],
+        types: &["dimension"],
+    },
+    Target {
+        collection: "borderWidth",
+        groups: &["borderWidth", "borderWidths"],
+        types: &["dimension"],
+    },
+    Target {
+        collection: "fontSize",
+        groups: &["fontSize", "fontSizes", "typography"],
+        types: &["typography", "dimension"],
+    },
+    Target {
+        collection: "letterSpacing",
+        groups: &["letterSpacing"],
+        types: &["dimension"],
+    },
+    Target {
+        collection: "lineHeight",
+        groups: &["lineHeight", "lineHeights"],
+        types: &["number", "dimension"],
+    },
+    Target {
+        collection: "screens",
+        groups: &["screens", "breakpoints"],
+        types: &["dimension"],
+    },
+    Target {
+        collection: "fontFamily",
+        groups: &["font", "fontFamily", "fontFamilies"],
+        types: &["fontFamily"],
+    },
+    Target {
+        collection: "fontWeight",
+        groups: &["fontWeight", "fontWeights"],
+        types: &["fontWeight", "number"],
+    },
+    Target {
+        collection: "boxShadow",
+        groups: &["shadow", "shadows", "boxShadow"],
+        types: &["shadow"],
+    },
+    Target {
+        collection: "transitionDuration",
+        groups: &["duration", "durations"],
+        types: &["duration"],
+    },
+    Target {
+        collection: "transitionTimingFunction",
+        groups: &["easing", "ease", "cubicBezier"],
+        types: &["cubicBezier"],
+    },
+    Target {
+        collection: "opacity",
+        groups: &["opacity"],
+        types: &["number"],
+    },
+];
+
+/// Where a token of `$type` goes when its top-level group names no
+/// collection.
+fn default_collection(ty: &str) -> Option<&'static str> {
+    Some(match ty {
+        "color" => "colors",
+        "dimension" => "spacing",
+        "fontFamily" => "fontFamily",
+        "fontWeight" => "fontWeight",
+        "shadow" => "boxShadow",
+        "duration" => "transitionDuration",
+        "cubicBezier" => "transitionTimingFunction",
+        "typography" => "fontSize",
+        _ => return None,
+    })
+}
+
+/// Properties mapped to a collection imported from DTCG when the theme does
+/// not map them already.
+pub fn default_properties(collection: &str) -> &'static [&'static str] {
+    match collection {
+        "colors" => &[
+            "color",
+            "backgroundColor",
+            "borderColor",
+            "outlineColor",
+            "textDecorationColor",
+            "accentColor",
+            "caretColor",
+            "fill",
+            "stroke",
+        ],
+        "spacing" => &[
+            "padding",
+            "margin",
+            "gap",
+            "rowGap",
+            "columnGap",
+            "inset",
+            "top",
+            "right",
+            "bottom",
+            "left",
+            "width",
+            "height",
+            "minWidth",
+            "minHeight",
+            "maxWidth",
+            "maxHeight",
+            "scrollMargin",
+            "scrollPadding",
+            "textIndent",
+        ],
+        "borderRadius" => &["borderRadius"],
+        "borderWidth" => &["borderWidth", "outlineWidth"],
+        "fontSize" => &["fontSize"],
+        "letterSpacing" => &["letterSpacing"],
+        "lineHeight" => &["lineHeight"],
+        "fontFamily" => &["fontFamily"],
+        "fontWeight" => &["fontWeight"],
+        "boxShadow" => &["boxShadow"],
+        "transitionDuration" => &[
+            "transitionDuration",
+            "transitionDelay",
+            "animationDuration",
+            "animationDelay",
+        ],
+        "transitionTimingFunction" => &["transitionTimingFunction", "animationTimingFunction"],
+        "opacity" => &["opacity"],
+        _ => &[],
+    }
+}
+
+/// A token found in a DTCG document, with the `$type` it declares or
+/// inherits from its groups.
+struct DtcgToken {
+    document: usize,
+    path: Vec<String>,
+    ty: Option<String>,
+    value: Value,
+}
+
+struct Documents {
+    tokens: Vec<DtcgToken>,
+    /// Dotted path → token, shared by all documents so aliases may cross
+    /// files.
+    index: HashMap<String, usize>,
+}
+
+/// Convert W3C Design Tokens (DTCG) documents, given as `(path, json)`, into
+/// collections in the `tokens.json` layout, one set per document. Aliases to
+/// whole tokens become `{collection.path}` references; aliases inside
+/// composite values are resolved in place. Problems are reported as
+/// `path: token.path: problem`.
+pub fn convert(documents: &[(String, Value)]) -> (Vec<Map<String, Value>>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut docs = Documents {
+        tokens: Vec::new(),
+        index: HashMap::new(),
+    };
+    for (document, (path, json)) in documents.iter().enumerate() {
+        match json {
+            Value::Object(map) => docs.collect(document, map, &mut Vec::new(), None, &mut errors),
+            _ => errors.push(format!("{}: expected an object of token groups", path)),
+        }
+    }
+
+    let mut converted = vec![Map::new(); documents.len()];
+    for (id, token) in docs.tokens.iter().enumerate() {
+        let located = |e: String| {
+            format!(
+                "{}: {}: {}",
+                documents[token.document].0,
+                token.path.join("."),
+                e
+            )
+        };
+        let placed = docs
+            .placed(id, &mut Vec::new())
+            .and_then(|(ty, collection, path)| {
+                docs.raw_value(id, &ty)
+                    .map(|value| (ty, collection, path, value))
+            });
+        match placed {
+            Ok((ty, collection, path, value)) => {
+                let target = &mut converted[token.document];
+                // A typography token also provides its font family.
+                if ty == "typography"
+                    && let Some(family) = value.get("fontFamily")
+                {
+                    insert(target, "fontFamily", &path, family.clone());
+                }
+                let value = match value {
+                    Value::Object(mut map) if ty == "typography" => {
+                        map.remove("fontFamily");
+                        Value::Object(map)
+                    }
+                    other => other,
+                };
+                insert(target, collection, &path, value);
+            }
+            Err(e) => errors.push(located(e)),
+        }
+    }
+    (converted, errors)
+}
+
+fn insert(collections: &mut Map<String, Value>, collection: &str, path: &[String], value: Value) {
+    let mut node = collections
+        .entry(collection.to_string())
+        .or_insert_with(|| Value::Object(Map::new()));
+    for segment in &path[..path.len() - 1] {
+        node = node
+            .as_object_mut()
+            .expect("token groups are objects")
+            .entry(segment.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !node.is_object() {
+            // A token and a group share this path; the token wins.
+            return;
+        }
+    }
+    if let Some(group) = node.as_object_mut() {
+        group.insert(path[path.len() - 1].clone(), value);
+    }
+}
+
+/// `{group.token}`
+fn alias(value: &Value) -> Option<&str> {
+    value.as_str()?.trim().strip_prefix('{')?.strip_suffix('}')
+}
+
+impl Documents {
+    fn collect(
+        &mut self,
+        document: usize,
+        group: &Map<String, Value>,
+        path: &mut Vec<String>,
+        inherited: Option<&str>,
+        errors: &mut Vec<String>,
+    ) {
+        for (key, child) in group {
+            if key.starts_with('$') {
+                continue;
+            }
+            path.push(key.clone());
+            match child {
+                Value::Object(map) => {
+                    let ty = map.get("$type").and_then(Value::as_str).or(inherited);
+                    if let Some(value) = map.get("$value") {
+                        self.index.insert(path.join("."), self.tokens.len());
+                        self.tokens.push(DtcgToken {
+                            document,
+                            path: path.clone(),
+                            ty: ty.map(str::to_string),
+                            value: value.clone(),
+                        });
+                    } else {
+                        self.collect(document, map, path, ty, errors);
+                    }
+                }
+                _ => errors.push(format!(
+                    "{}: expected a token with $value or a group",
+                    path.join(".")
+                )),
+            }
+            path.pop();
+        }
+    }
+
+    fn target(&self, name: &str) -> Result<usize, String> {
+        self.index
+            .get(name)
+            .copied()
+            .ok_or_else(|| format!("unknown alias {{{}}}", name))
+    }
+
+    /// The token's `$type`, its collection and its path inside it. A token
+    /// without `$type` takes its alias target's.
+    fn placed(
+        &self,
+        id: usize,
+        stack: &mut Vec<usize>,
+    ) -> Result<(String, &'static str, Vec<String>), String> {
+        let token = &self.tokens[id];
+        let ty = match (&token.ty, alias(&token.value)) {
+            (Some(ty), _) => ty.clone(),
+            (None, Some(name)) => {
+                let target = self.target(name)?;
+                self.guard(id, stack)?;
+                let (ty, ..) = self.placed(target, stack)?;
+                stack.pop();
+                ty
+            }
+            (None, None) => return Err("no $type on the token or its groups".to_string()),
+        };
+
+        let group = tokens::camel_case(&token.path[0]);
+        if token.path.len() > 1
+            && let Some(target) = TARGETS
+                .iter()
+                .find(|t| t.types.contains(&ty.as_str()) && t.groups.contains(&group.as_str()))
+        {
+            return Ok((ty, target.collection, token.path[1..].to_vec()));
+        }
+        match default_collection(&ty) {
+            Some(collection) => Ok((ty, collection, token.path.clone())),
+            None if ty == "number" => Err(
+                "number tokens need a top-level group naming their use (opacity, lineHeight or fontWeight)"
+                    .to_string(),
+            ),
+            None => Err(format!(
+                "unsupported $type '{}' (expected color, dimension, fontFamily, fontWeight, number, shadow, duration, cubicBezier or typography)",
+                ty
+            )),
+        }
+    }
+
+    fn guard(&self, id: usize, stack: &mut Vec<usize>) -> Result<(), String> {
+        if let Some(start) = stack.iter().position(|seen| *seen == id) {
+            let chain: Vec<String> = stack[start..]
+                .iter()
+                .chain([&id])
+                .map(|seen| self.tokens[*seen].path.join("."))
+                .collect();
+            return Err(format!("alias cycle {}", chain.join(" → ")));
+        }
+        stack.push(id);
+        Ok(())
+    }
+
+    /// The token's value as `tokens.json` would spell it.
+    fn raw_value(&self, id: usize, ty: &str) -> Result<Value, String> {
+        let token = &self.tokens[id];
+        if let Some(name) = alias(&token.value) {
+            let (_, collection, path) = self.placed(self.target(name)?, &mut vec![id])?;
+            return Ok(Value::String(format!(
+                "{{{}.{}}}",
+                collection,
+                path.join(".")
+            )));
+        }
+        self.convert(ty, &token.value, &mut vec![id])
+    }
+
+    /// CSS for a value that may itself be an alias, as used inside composite
+    /// values.
+    fn css(&self, ty: &str, value: &Value, stack: &mut Vec<usize>) -> Result<String, String> {
+        let converted = match alias(value) {
+            Some(name) => {
+                let target = self.target(name)?;
+                self.guard(target, stack)?;
+                let token = &self.tokens[target];
+                let converted = self.convert(ty, &token.value, stack)?;
+                stack.pop();
+                converted
+            }
+            None => self.convert(ty, value, stack)?,
+        };
+        Ok(match converted {
+            Value::String(s) => s,
+            Value::Array(items) => items
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(", "),
+            other => other.to_string(),
+        })
+    }
+
+    fn convert(&self, ty: &str, value: &Value, stack: &mut Vec<usize>) -> Result<Value, String> {
+        if alias(value).is_some() {
+            return self.css(ty, value, stack).map(Value::String);
+        }
+        let text = |s: String| Ok(Value::String(s));
+        match ty {
+            "color" => text(color(value)?),
+            "dimension" | "duration" => text(dimension(value)?),
+            "number" => match value {
+                Value::Number(n) => text(n.to_string()),
+                other => Err(format!("expected a number, got {}", other)),
+            },
+            "fontWeight" => text(font_weight(value)?),
+            "fontFamily" => font_family(value),
+            "cubicBezier" => match value.as_array().map(Vec::as_slice) {
+                Some(points @ [_, _, _, _]) => {
+                    let points = points
+                        .iter()
+                        .map(|p| {
+                            self.css("number", p, stack)
+                                .map_err(|_| format!("expected numbers, got {}", p))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    text(format!("cubic-bezier({})", points.join(", ")))
+                }
+                _ => Err(format!("expected [x1, y1, x2, y2], got {}", value)),
+            },
+            "shadow" => {
+                let layers = match value {
+                    Value::Array(layers) => layers.iter().collect(),
+                    other => vec![other],
+                };
+                let layers = layers
+                    .into_iter()
+                    .map(|layer| self.shadow_layer(layer, stack))
+                    .collect::<Result<Vec<_>, _>>()?;
+                text(layers.join(", "))
+            }
+            "typography" => {
+                let Value::Object(parts) = value else {
+                    return Err(format!("expected a typography object, got {}", value));
+                };
+                let mut out = Map::new();
+                for (key, part) in parts {
+                    let (name, ty) = match key.as_str() {
+                        "fontSize" => ("size", "dimension"),
+                        "lineHeight" if part.is_number() => ("lineHeight", "number"),
+                        "lineHeight" => ("lineHeight", "dimension"),
+                        "letterSpacing" => ("letterSpacing", "dimension"),
+                        "fontWeight" => ("fontWeight", "fontWeight"),
+                        "fontFamily" => ("fontFamily", "fontFamily"),
+                        other => return Err(format!("unknown typography property '{}'", other)),
+                    };
+                    let css = self
+                        .css(ty, part, stack)
+                        .map_err(|e| format!("{}: {}", key, e))?;
+                    out.insert(name.to_string(), Value::String(css));
+                }
+                if !out.contains_key("size") {
+                    return Err("typography needs a fontSize".to_string());
+                }
+                Ok(Value::Object(out))
+            }
+            other => Err(format!("unsupported $type '{}'", other)),
+        }
+    }
+
+    fn shadow_layer(&self, layer: &Value, stack: &mut Vec<usize>) -> Result<String, String> {
+        let Value::Object(parts) = layer else {
+            return Err(format!("expected a shadow object, got {}", layer));
+        };
+        let part =
+            |key: &str, ty: &str, stack: &mut Vec<usize>| -> Result<Option<String>, String> {
+                parts
+                    .get(key)
+                    .map(|value| {
+                        self.css(ty, value, stack)
+                            .map_err(|e| format!("{}: {}", key, e))
+                    })
+                    .transpose()
+            };
+        let mut out = Vec::new();
+        if parts.get("inset").and_then(Value::as_bool) == Some(true) {
+            out.push("inset".to_string());
+        }
+        for key in ["offsetX", "offsetY", "blur", "spread"] {
+            out.push(part(key, "dimension", stack)?.unwrap_or_else(|| "0".to_string()));
+        }
+        if let Some(color) = part("color", "color", stack)? {
+            out.push(color);
+        }
+        Ok(out.join(" "))
+    }
+}
+
+fn dimension(value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Object(map) => match (map.get("value"), map.get("unit").and_then(Value::as_str)) {
+            (Some(Value::Number(n)), Some(unit)) => Ok(format!("{}{}", n, unit)),
+            _ => Err(format!("expected {{ \"value\", \"unit\" }}, got {}", value)),
+        },
+        other => Err(format!("expected a dimension, got {}", other)),
+    }
+}
+
+/// Hex and CSS strings pass through; `{ colorSpace, components, alpha }`
+/// objects become the matching CSS function, or their `hex` fallback.
+fn color(value: &Value) -> Result<String, String> {
+    let map = match value {
+        Value::String(s) => return Ok(s.clone()),
+        Value::Object(map) => map,
+        other => return Err(format!("expected a color, got {}", other)),
+    };
+    let alpha = map.get("alpha").and_then(Value::as_f64).unwrap_or(1.0);
+    let hex = map.get("hex").and_then(Value::as_str);
+    if let Some(hex) = hex
+        && alpha >= 1.0
+    {
+        return Ok(hex.to_string());
+    }
+
+    let space = map.get("colorSpace").and_then(Value::as_str).unwrap_or("");
+    let components: Vec<String> = map
+        .get("components")
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .map(|c| match c {
+                    Value::Number(n) => n.as_f64().unwrap_or_default(),
+                    _ => 0.0,
+                })
+                .enumerate()
+                .map(|(i, c)| match space {
+                    "srgb" => number((c * 255.0).round()),
+                    "hsl" if i > 0 => format!("{}%", number(c)),
+                    _ => number(c),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if components.len() != 3 {
+        return Err(format!("expected three color components, got {}", value));
+    }
+    let alpha = if alpha < 1.0 {
+        format!(" / {}", number(alpha))
+    } else {
+        String::new()
+    };
+    let function = match space {
+        "srgb" => "rgb",
+        "hsl" | "oklch" | "oklab" => space,
+        other => match hex {
+            Some(hex) => return Ok(hex.to_string()),
+            None => return Err(format!("unsupported colorSpace '{}' without hex", other)),
+        },
+    };
+    Ok(format!("{}({}{})", function, components.join(" "), alpha))
+}
+
+fn number(n: f64) -> String {
+    let rounded = (n * 10_000.0).round() / 10_000.0;
+    rounded.to_string()
+}
+
+fn font_weight(value: &Value) -> Result<String, String> {
+    let weight = match value {
+        Value::Number(n) => return Ok(n.to_string()),
+        Value::String(s) => match s.to_ascii_lowercase().as_str() {
+            "thin" | "hairline" => 100,
+            "extra-light" | "ultra-light" => 200,
+            "light" => 300,
+            "normal" | "regular" | "book" => 400,
+            "medium" => 500,
+            "semi-bold" | "demi-bold" => 600,
+            "bold" => 700,
+            "extra-bold" | "ultra-bold" => 800,
+            "black" | "heavy" => 900,
+            "extra-black" | "ultra-black" => 950,
+            _ => return Err(format!("unknown font weight '{}'", s)),
+        },
+        other => return Err(format!("expected a font weight, got {}", other)),
+    };
+    Ok(weight.to_string())
+}
+
+/// Family names with spaces are quoted for CSS.
+fn font_family(value: &Value) -> Result<Value, String> {
+    let names = match value {
+        Value::String(s) => vec![s.as_str()],
+        Value::Array(items) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .ok_or_else(|| format!("font family entries must be strings, got {}", item))
+            })
+            .collect::<Result<_, _>>()?,
+        other => return Err(format!("expected a font family, got {}", other)),
+    };
+    Ok(Value::Array(
+        names
+            .into_iter()
+            .map(|name| {
+                let quoted = name.contains(' ') && !name.starts_with(['"', '\'']);
+                Value::String(if quoted {
+                    format!("\"{}\"", name)
+                } else {
+                    name.to_string()
+                })
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn convert_one(document: Value) -> (Map<String, Value>, Vec<String>) {
+        let (mut converted, errors) = convert(&[("figma.tokens.json".to_string(), document)]);
+        (converted.remove(0), errors)
+    }
+
+    #[test]
+    fn types_map_to_collections() {
+        let (collections, errors) = convert_one(json!({
+            "color": {
+                "$type": "color",
+                "brand": { "$value": "#155dfc" },
+                "accent": { "$value": { "colorSpace": "oklch", "components": [0.62, 0.21, 259.8], "alpha": 0.5 } },
+                "primary": { "$value": "{color.brand}" }
+            },
+            "radius": { "md": { "$type": "dimension", "$value": { "value": 6, "unit": "px" } } },
+            "space": { "$type": "dimension", "4": { "$value": "1rem" } },
+            "shadow": {
+                "card": {
+                    "$type": "shadow",
+                    "$value": { "offsetX": "0px", "offsetY": "1px", "blur": "3px", "spread": "0px", "color": "{color.brand}" }
+                }
+            },
+            "motion": {
+                "fast": { "$type": "duration", "$value": "150ms" },
+                "ease-out": { "$type": "cubicBezier", "$value": [0, 0, 0.2, 1] }
+            },
+            "text": {
+                "body": {
+                    "$type": "typography",
+                    "$value": { "fontFamily": ["Inter", "Helvetica Neue"], "fontSize": "1rem", "lineHeight": 1.5, "fontWeight": "bold" }
+                }
+            }
+        }));
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(
+            Value::Object(collections),
+            json!({
+                "colors": {
+                    "brand": "#155dfc",
+                    "accent": "oklch(0.62 0.21 259.8 / 0.5)",
+                    "primary": "{colors.brand}"
+                },
+                "borderRadius": { "md": "6px" },
+                "spacing": { "4": "1rem" },
+                "boxShadow": { "card": "0px 1px 3px 0px #155dfc" },
+                "transitionDuration": { "motion": { "fast": "150ms" } },
+                "transitionTimingFunction": { "motion": { "ease-out": "cubic-bezier(0, 0, 0.2, 1)" } },
+                "fontFamily": { "text": { "body": "Inter, \"Helvetica Neue\"" } },
+                "fontSize": { "text": { "body": { "size": "1rem", "lineHeight": "1.5", "fontWeight": "700" } } }
+            })
+        );
+    }
+
+    #[test]
+    fn problems_name_the_file_and_token() {
+        let (_, errors) = convert_one(json!({
+            "color": { "ghost": { "$value": "{color.nope}" }, "loop": { "$value": "{color.loop2}" }, "loop2": { "$value": "{color.loop}" } },
+            "border": { "thin": { "$type": "border", "$value": {} } },
+            "misc": { "untyped": { "$value": "1" } }
+        }));
+        assert_eq!(
+            errors,
+            vec![
+                "figma.tokens.json: border.thin: unsupported $type 'border' (expected color, dimension, fontFamily, fontWeight, number, shadow, duration, cubicBezier or typography)",
+                "figma.tokens.json: color.ghost: unknown alias {color.nope}",
+                "figma.tokens.json: color.loop: alias cycle color.loop → color.loop2 → color.loop",
+                "figma.tokens.json: color.loop2: alias cycle color.loop2 → color.loop → color.loop2",
+                "figma.tokens.json: misc.untyped: no $type on the token or its groups",
+            ]
+        );
+    }
+}
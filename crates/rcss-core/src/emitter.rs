@@ -1,73 +1,960 @@
-use crate::ast::{Declaration, Rule, Stylesheet};
+use crate::ast::{Declaration, MediaBlock, Rule, Stylesheet};
+use crate::color::{self, Color};
+use crate::targets::Targets;
+use crate::units::{self, PxToRem};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 use std::sync::OnceLock;
 
+#[derive(Debug, Clone, Default)]
+pub struct EmitOptions {
+    /// Collect every rule that shares a media query into a single `@media`
+    /// block emitted after the base rules, with `min-width` breakpoints
+    /// ordered mobile-first. A later base rule that sets the same property
+    /// on the same selector ends the groups collected so far, so they are
+    /// written before it. Only applies to flattened output: with
+    /// [`Nesting::Native`] media blocks stay inside their rules and this is
+    /// ignored.
+    pub group_media: bool,
+    pub nesting: Nesting,
+    /// Browsers the output must support; decides which vendor prefixes and
+    /// color fallbacks are emitted.
+    pub targets: Targets,
+    /// Drop vendor-prefixed declarations and selectors written by hand when
+    /// none of the targets need them.
+    pub strip_prefixes: bool,
+    /// How `(prefers-color-scheme: dark)` blocks are switched on.
+    pub dark_mode: DarkMode,
+    /// Write px lengths as rem of the root font size, for the properties
+    /// it allows.
+    pub px_to_rem: Option<PxToRem>,
+}
+
+/// How nested rules are written to CSS.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Nesting {
+    /// Expand every nested selector into its own top-level rule.
+    #[default]
+    Flatten,
+    /// Keep the nesting tree and rely on native CSS nesting.
+    Native,
+}
+
+impl FromStr for Nesting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flatten" => Ok(Nesting::Flatten),
+            "native" => Ok(Nesting::Native),
+            other => Err(format!(
+                "Unknown nesting mode '{}' (expected \"native\" or \"flatten\")",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Nesting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Nesting::Flatten => write!(f, "flatten"),
+            Nesting::Native => write!(f, "native"),
+        }
+    }
+}
+
+/// How dark-scheme declarations (`dark { }` blocks, dark presets and
+/// light/dark tokens) are applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DarkMode {
+    /// `@media (prefers-color-scheme: dark)`, following the OS setting.
+    #[default]
+    Media,
+    /// Under a `.dark` class on an ancestor (`.dark &`).
+    Class,
+    /// Under `[data-theme="dark"]` on an ancestor.
+    Attribute,
+    /// The media query unless `[data-theme="light"]` is set on the root, plus
+    /// `[data-theme="dark"]` to force dark.
+    Both,
+}
+
+impl FromStr for DarkMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "media" => Ok(DarkMode::Media),
+            "class" => Ok(DarkMode::Class),
+            "attribute" => Ok(DarkMode::Attribute),
+            "both" => Ok(DarkMode::Both),
+            other => Err(format!(
+                "Unknown dark mode '{}' (expected \"media\", \"class\", \"attribute\" or \"both\")",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DarkMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DarkMode::Media => write!(f, "media"),
+            DarkMode::Class => write!(f, "class"),
+            DarkMode::Attribute => write!(f, "attribute"),
+            DarkMode::Both => write!(f, "both"),
+        }
+    }
+}
+
+const DARK_CONDITION: &str = "(prefers-color-scheme: dark)";
+
+/// Where a dark variant lives in the document: glued onto a root selector
+/// (`:root.dark`) or as an ancestor of anything else (`.dark .card`).
+#[derive(Debug, Clone, Copy)]
+struct DarkScope {
+    root_suffix: &'static str,
+    ancestor: &'static str,
+}
+
+const CLASS_SCOPE: DarkScope = DarkScope {
+    root_suffix: ".dark",
+    ancestor: ".dark",
+};
+const ATTRIBUTE_SCOPE: DarkScope = DarkScope {
+    root_suffix: "[data-theme=\"dark\"]",
+    ancestor: "[data-theme=\"dark\"]",
+};
+const NOT_LIGHT_SCOPE: DarkScope = DarkScope {
+    root_suffix: ":not([data-theme=\"light\"])",
+    ancestor: ":root:not([data-theme=\"light\"])",
+};
+
+impl DarkScope {
+    fn apply(&self, selector: &str) -> String {
+        scope_selector(selector, self.root_suffix, self.ancestor)
+    }
+
+    /// Header of a native nested rule scoping its parent.
+    fn nested(&self, parent: &str) -> String {
+        let all_root = split_selector_list(parent)
+            .iter()
+            .all(|term| root_compound_len(term) == Some(term.len()));
+        if all_root {
+            format!("&{}", self.root_suffix)
+        } else {
+            format!("{} &", self.ancestor)
+        }
+    }
+}
+
+/// Scope every term of a flat selector list: `root_suffix` is glued onto
+/// terms that start at the document root, `ancestor` is put in front of the
+/// rest.
+pub(crate) fn scope_selector(selector: &str, root_suffix: &str, ancestor: &str) -> String {
+    split_selector_list(selector)
+        .into_iter()
+        .map(|term| match root_compound_len(term) {
+            Some(len) => format!("{}{}{}", &term[..len], root_suffix, &term[len..]),
+            None => format!("{} {}", ancestor, term),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Length of the leading compound selector when the term starts at the
+/// document root (`:root`, `html`), where the dark marker itself is set.
+fn root_compound_len(term: &str) -> Option<usize> {
+    let rest = term
+        .strip_prefix(":root")
+        .or_else(|| term.strip_prefix("html"))?;
+    if rest
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    let end = term
+        .find(|c: char| c.is_whitespace() || matches!(c, '>' | '+' | '~'))
+        .unwrap_or(term.len());
+    Some(end)
+}
+
+/// The `(media query, scope)` pairs a media block is written under. Blocks
+/// without the dark condition, and every block in media mode, keep their
+/// query as is; otherwise the condition is replaced by a selector scope and
+/// whatever else the query required stays as a media query.
+fn media_variants(query: &str, mode: DarkMode) -> Vec<(Option<String>, Option<DarkScope>)> {
+    let unchanged = vec![(Some(query.to_string()), None)];
+    let Some(rest) = without_dark_condition(query) else {
+        return unchanged;
+    };
+    match mode {
+        DarkMode::Media => unchanged,
+        DarkMode::Class => vec![(rest, Some(CLASS_SCOPE))],
+        DarkMode::Attribute => vec![(rest, Some(ATTRIBUTE_SCOPE))],
+        DarkMode::Both => vec![
+            (Some(query.to_string()), Some(NOT_LIGHT_SCOPE)),
+            (rest, Some(ATTRIBUTE_SCOPE)),
+        ],
+    }
+}
+
+/// The rest of an `and`-joined query once `(prefers-color-scheme: dark)` is
+/// removed, or `None` inside when nothing is left. Returns `None` when the
+/// query does not require the dark scheme.
+fn without_dark_condition(query: &str) -> Option<Option<String>> {
+    if query.contains(',') {
+        return None;
+    }
+    let parts: Vec<&str> = query.split(" and ").map(str::trim).collect();
+    let compact = |text: &str| text.split_whitespace().collect::<String>();
+    let is_dark = |part: &&str| compact(part) == compact(DARK_CONDITION);
+    if !parts.iter().any(is_dark) {
+        return None;
+    }
+    let rest: Vec<&str> = parts.into_iter().filter(|part| !is_dark(part)).collect();
+    Some((!rest.is_empty()).then(|| rest.join(" and ")))
+}
+
+/// A rule with its selector fully expanded, ready for flat output.
+struct FlatRule<'a> {
+    selector: &'a str,
+    declarations: &'a [Declaration],
+    media: &'a [MediaBlock],
+    /// The frames of an `@keyframes` rule, which stay nested inside it.
+    frames: Option<&'a [Rule]>,
+}
+
 pub fn emit_css(stylesheet: &Stylesheet) -> String {
+    emit_css_with_options(stylesheet, &EmitOptions::default())
+}
+
+pub fn emit_css_with_options(stylesheet: &Stylesheet, options: &EmitOptions) -> String {
+    if let Some(px_to_rem) = &options.px_to_rem {
+        let converted = units::convert_stylesheet(stylesheet, px_to_rem);
+        let options = EmitOptions {
+            px_to_rem: None,
+            ..options.clone()
+        };
+        return emit_css_with_options(&converted, &options);
+    }
+    if options.nesting == Nesting::Native {
+        return emit_native(stylesheet, options);
+    }
+
+    let mut flat = Vec::new();
+    flatten_rules(&stylesheet.rules, &mut flat);
+
+    if options.group_media {
+        return emit_grouped(&flat, options);
+    }
+
     let mut out = String::new();
 
-    for rule in &stylesheet.rules {
-        if !rule.declarations.is_empty() {
-            emit_rule(rule, &mut out);
-            out.push('\n');
+    for rule in &flat {
+        for selector in selectors_to_emit(rule.selector, options) {
+            if let Some(frames) = rule.frames {
+                emit_keyframes(&selector, frames, options, &mut out);
+                continue;
+            }
+            if !rule.declarations.is_empty() {
+                emit_rule(&selector, rule.declarations, options, &mut out);
+                out.push('\n');
+            }
+            for media in rule.media {
+                for (query, scope) in media_variants(&media.query, options.dark_mode) {
+                    let selector = match scope {
+                        Some(scope) => scope.apply(&selector),
+                        None => selector.clone(),
+                    };
+                    match query {
+                        Some(query) => emit_media_rule(
+                            &selector,
+                            &query,
+                            &media.declarations,
+                            options,
+                            &mut out,
+                        ),
+                        None => emit_rule(&selector, &media.declarations, options, &mut out),
+                    }
+                    out.push('\n');
+                }
+            }
+        }
+    }
+    out
+}
+
+fn flatten_rules<'a>(rules: &'a [Rule], out: &mut Vec<FlatRule<'a>>) {
+    for rule in rules {
+        if is_keyframes(&rule.selector) {
+            out.push(FlatRule {
+                selector: &rule.selector,
+                declarations: &rule.declarations,
+                media: &rule.media,
+                frames: Some(&rule.children),
+            });
+            continue;
         }
-        for media in &rule.media {
-            emit_media_rule(rule, media, &mut out);
-            out.push('\n');
+        if rule.nested_header.is_some() {
+            // Nested selector lists are the product of parent and child terms;
+            // each combination becomes its own rule.
+            for selector in split_selector_list(&rule.selector) {
+                out.push(FlatRule {
+                    selector,
+                    declarations: &rule.declarations,
+                    media: &rule.media,
+                    frames: None,
+                });
+            }
+        } else {
+            out.push(FlatRule {
+                selector: &rule.selector,
+                declarations: &rule.declarations,
+                media: &rule.media,
+                frames: None,
+            });
         }
+        flatten_rules(&rule.children, out);
     }
+}
+
+fn split_selector_list(selector: &str) -> Vec<&str> {
+    let mut terms = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (idx, ch) in selector.char_indices() {
+        match ch {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                terms.push(selector[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    terms.push(selector[start..].trim());
+    terms.retain(|term| !term.is_empty());
+    terms
+}
+
+/// Media blocks waiting to be written by [`emit_grouped`]: each query with
+/// the selectors and blocks that use it, in source order.
+type MediaGroups<'a> = Vec<(String, Vec<(String, &'a MediaBlock)>)>;
+
+fn emit_grouped(rules: &[FlatRule<'_>], options: &EmitOptions) -> String {
+    let mut out = String::new();
+    let mut groups: MediaGroups<'_> = Vec::new();
+
+    for rule in rules {
+        for selector in selectors_to_emit(rule.selector, options) {
+            if let Some(frames) = rule.frames {
+                emit_keyframes(&selector, frames, options, &mut out);
+                continue;
+            }
+            if !rule.declarations.is_empty() {
+                emit_base_rule(&selector, rule.declarations, &mut groups, options, &mut out);
+            }
+            for media in rule.media {
+                if media.declarations.is_empty() {
+                    continue;
+                }
+                for (query, scope) in media_variants(&media.query, options.dark_mode) {
+                    let selector = match scope {
+                        Some(scope) => scope.apply(&selector),
+                        None => selector.clone(),
+                    };
+                    let Some(query) = query else {
+                        emit_base_rule(
+                            &selector,
+                            &media.declarations,
+                            &mut groups,
+                            options,
+                            &mut out,
+                        );
+                        continue;
+                    };
+                    let entry = (selector, media);
+                    match groups.iter_mut().find(|(existing, _)| *existing == query) {
+                        Some((_, entries)) => entries.push(entry),
+                        None => groups.push((query, vec![entry])),
+                    }
+                }
+            }
+        }
+    }
+
+    emit_media_groups(groups, options, &mut out);
     out
 }
 
-fn emit_rule(rule: &Rule, out: &mut String) {
-    out.push_str(&rule.selector);
-    out.push_str(" {\n");
+/// Emit a rule outside any media query. Grouped blocks are hoisted past the
+/// base rules, which would let a block win over a later rule that sets the
+/// same property on the same selector, so such blocks are written first.
+fn emit_base_rule(
+    selector: &str,
+    declarations: &[Declaration],
+    groups: &mut MediaGroups<'_>,
+    options: &EmitOptions,
+    out: &mut String,
+) {
+    let overridden = groups
+        .iter()
+        .flat_map(|(_, entries)| entries)
+        .any(|(grouped, media)| {
+            grouped == selector
+                && media.declarations.iter().any(|earlier| {
+                    declarations
+                        .iter()
+                        .any(|later| same_property(&earlier.property, &later.property))
+                })
+        });
+    if overridden {
+        emit_media_groups(std::mem::take(groups), options, out);
+    }
+    emit_rule(selector, declarations, options, out);
+    out.push('\n');
+}
+
+/// Whether two properties set the same value, one being a longhand of the
+/// other (`margin` and `margin-top`).
+fn same_property(a: &str, b: &str) -> bool {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    long.strip_prefix(short)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+}
+
+fn emit_media_groups(mut groups: MediaGroups<'_>, options: &EmitOptions, out: &mut String) {
+    // Stable sort: breakpoints ascend by width, everything else keeps the
+    // order in which its query first appeared.
+    groups.sort_by(
+        |(a, _), (b, _)| match (media_min_width(a), media_min_width(b)) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        },
+    );
+
+    for (query, entries) in groups {
+        out.push_str("@media ");
+        out.push_str(&prefix_media_query(&query, &options.targets));
+        out.push_str(" {\n");
+        for (selector, media) in entries {
+            out.push_str("  ");
+            out.push_str(&selector);
+            out.push_str(" {\n");
+            for decl in &media.declarations {
+                emit_declaration(decl, options, out);
+            }
+            out.push_str("  }\n");
+        }
+        out.push_str("}\n\n");
+    }
+}
+
+/// Width in px of a query that is exactly `(min-width: <length>)`.
+fn media_min_width(query: &str) -> Option<f64> {
+    let inner = query
+        .trim()
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .trim()
+        .strip_prefix("min-width")?
+        .trim_start()
+        .strip_prefix(':')?
+        .trim();
+
+    let split = inner
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(inner.len());
+    let number: f64 = inner[..split].parse().ok()?;
+    match &inner[split..] {
+        "px" | "" => Some(number),
+        "rem" | "em" => Some(number * 16.0),
+        _ => None,
+    }
+}
+
+fn emit_native(stylesheet: &Stylesheet, options: &EmitOptions) -> String {
+    let mut out = String::new();
+    let mut writer = NativeWriter {
+        out: &mut out,
+        chain: Vec::new(),
+        open: 0,
+    };
+    for rule in &stylesheet.rules {
+        emit_native_top(rule, options, &mut writer);
+    }
+    out
+}
+
+/// Output of [`emit_native`]. The blocks of a rule and its ancestors are
+/// opened only when something is written into them, so a hoisted rule can
+/// close them and the siblings after it reopen them.
+struct NativeWriter<'o> {
+    out: &'o mut String,
+    /// Selectors of the rule being written and of its ancestors.
+    chain: Vec<String>,
+    /// How many blocks of `chain` are open in `out`.
+    open: usize,
+}
+
+impl NativeWriter<'_> {
+    fn open_all(&mut self) {
+        while self.open < self.chain.len() {
+            self.out.push_str(&"    ".repeat(self.open));
+            self.out.push_str(&self.chain[self.open]);
+            self.out.push_str(" {\n");
+            self.open += 1;
+        }
+    }
 
+    fn close_to(&mut self, depth: usize) {
+        while self.open > depth {
+            self.open -= 1;
+            self.out.push_str(&"    ".repeat(self.open));
+            self.out.push_str("}\n");
+            if self.open == 0 {
+                self.out.push('\n');
+            }
+        }
+    }
+}
+
+fn emit_native_top(rule: &Rule, options: &EmitOptions, writer: &mut NativeWriter<'_>) {
+    for (idx, selector) in selectors_to_emit(&rule.selector, options)
+        .into_iter()
+        .enumerate()
+    {
+        emit_native_rule(rule, selector, idx == 0, options, writer);
+    }
+}
+
+/// `hoist` writes the children native nesting cannot express as top-level
+/// rules where they appear; it is off for the extra selector variants of a
+/// rule, which would repeat them.
+fn emit_native_rule(
+    rule: &Rule,
+    selector: String,
+    hoist: bool,
+    options: &EmitOptions,
+    writer: &mut NativeWriter<'_>,
+) {
+    if is_empty_tree(rule) {
+        return;
+    }
+    let depth = writer.chain.len();
+    let indent = "    ".repeat(depth);
+    writer.chain.push(selector.clone());
+
+    if !rule.declarations.is_empty() {
+        writer.open_all();
+    }
     for decl in &rule.declarations {
-        emit_declaration(decl, out);
+        emit_declaration_at(decl, depth + 1, options, writer.out);
+    }
+
+    for media in &rule.media {
+        if media.declarations.is_empty() {
+            continue;
+        }
+        writer.open_all();
+        let out = &mut *writer.out;
+        for (query, scope) in media_variants(&media.query, options.dark_mode) {
+            let mut inner = depth + 1;
+            if let Some(query) = &query {
+                out.push_str(&indent);
+                out.push_str("    @media ");
+                out.push_str(&prefix_media_query(query, &options.targets));
+                out.push_str(" {\n");
+                inner += 1;
+            }
+            let scope_indent = "    ".repeat(inner);
+            if let Some(scope) = scope {
+                out.push_str(&scope_indent);
+                out.push_str(&scope.nested(&selector));
+                out.push_str(" {\n");
+                inner += 1;
+            }
+            for decl in &media.declarations {
+                emit_declaration_at(decl, inner, options, out);
+            }
+            if scope.is_some() {
+                out.push_str(&scope_indent);
+                out.push_str("}\n");
+            }
+            if query.is_some() {
+                out.push_str(&indent);
+                out.push_str("    }\n");
+            }
+        }
+    }
+
+    for child in &rule.children {
+        match child.nested_header.as_deref() {
+            Some(header) if !concatenates_parent(header) => {
+                for variant in selectors_to_emit(header, options) {
+                    emit_native_rule(child, variant, hoist, options, writer);
+                }
+            }
+            _ if hoist => {
+                let chain = std::mem::take(&mut writer.chain);
+                writer.close_to(0);
+                emit_native_top(child, options, writer);
+                writer.chain = chain;
+            }
+            _ => {}
+        }
+    }
+
+    writer.close_to(depth);
+    writer.chain.pop();
+}
+
+fn is_empty_tree(rule: &Rule) -> bool {
+    rule.declarations.is_empty()
+        && rule.media.iter().all(|m| m.declarations.is_empty())
+        && rule.children.iter().all(is_empty_tree)
+}
+
+/// `&-primary` glues text onto the parent selector, which native nesting
+/// cannot express.
+fn concatenates_parent(header: &str) -> bool {
+    header.match_indices('&').any(|(idx, _)| {
+        header[idx + 1..]
+            .chars()
+            .next()
+            .map(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            .unwrap_or(false)
+    })
+}
+
+fn emit_rule(
+    selector: &str,
+    declarations: &[Declaration],
+    options: &EmitOptions,
+    out: &mut String,
+) {
+    out.push_str(selector);
+    out.push_str(" {\n");
+
+    for decl in declarations {
+        emit_declaration(decl, options, out);
     }
     out.push_str("}\n");
 }
 
-fn emit_declaration(decl: &Declaration, out: &mut String) {
+/// An `@keyframes` block (or one of its prefixed copies) with each frame
+/// nested under its header.
+fn emit_keyframes(selector: &str, frames: &[Rule], options: &EmitOptions, out: &mut String) {
+    out.push_str(selector);
+    out.push_str(" {\n");
+    for frame in frames {
+        out.push_str("    ");
+        out.push_str(frame.nested_header.as_deref().unwrap_or(&frame.selector));
+        out.push_str(" {\n");
+        for decl in &frame.declarations {
+            emit_declaration_at(decl, 2, options, out);
+        }
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n\n");
+}
+
+pub(crate) fn is_keyframes(selector: &str) -> bool {
+    let selector = selector.trim_start();
+    selector.starts_with("@keyframes ") || selector.starts_with("@-webkit-keyframes ")
+}
+
+fn emit_declaration(decl: &Declaration, options: &EmitOptions, out: &mut String) {
+    emit_declaration_at(decl, 1, options, out);
+}
+
+fn emit_declaration_at(decl: &Declaration, depth: usize, options: &EmitOptions, out: &mut String) {
+    if options.strip_prefixes && is_unneeded_prefix(decl, &options.targets) {
+        return;
+    }
+
+    let folded = fold_color_mix(&decl.value, &options.targets);
+    let value = folded.as_deref().unwrap_or(&decl.value);
+    if let Some(fallback) = srgb_fallback(value, &options.targets) {
+        emit_single_declaration(&decl.property, &fallback, depth, out);
+        // Custom properties accept any value, so the OKLCH declaration
+        // would always win over the fallback: keep the sRGB value alone.
+        if decl.property.starts_with("--") {
+            return;
+        }
+    }
+
     let mut prefixed = false;
     if let Some(actions) = autoprefix_rules().get(decl.property.as_str()) {
         for action in actions {
-            if (action.condition)(&decl.value) {
-                emit_single_declaration(action.property, &(action.transform)(&decl.value), out);
+            if (action.condition)(value)
+                && options.targets.needs_prefix(action.feature, action.prefix)
+            {
+                emit_single_declaration(action.property, &(action.transform)(value), depth, out);
                 prefixed = true;
             }
         }
     }
 
-    emit_single_declaration(&decl.property, &decl.value, out);
+    emit_single_declaration(&decl.property, value, depth, out);
 
     if prefixed {
         out.push('\n');
     }
 }
 
-fn emit_single_declaration(property: &str, value: &str, out: &mut String) {
-    out.push_str("    ");
+/// Replace `color-mix()` calls with the mixed color when a target cannot
+/// evaluate them. Colors that stay inside sRGB are written as hex/rgba; wider
+/// results keep `oklch()` so the sRGB fallback below can still apply.
+fn fold_color_mix(value: &str, targets: &Targets) -> Option<String> {
+    if targets.supports("css-color-mix") || !value.contains("color-mix(") {
+        return None;
+    }
+    color::replace_color_functions(value, &["color-mix"], |call| {
+        let mixed = Color::parse(call).ok()?;
+        Some(if mixed.in_srgb_gamut() {
+            mixed.to_srgb_string()
+        } else {
+            mixed.to_oklch_string()
+        })
+    })
+}
+
+/// A copy of `value` with `oklch()`/`oklab()` colors gamut mapped to sRGB,
+/// emitted before the original (or instead of it, for custom properties)
+/// for targets without OKLCH support. `None`
+/// when no target needs it or a color cannot be evaluated statically.
+fn srgb_fallback(value: &str, targets: &Targets) -> Option<String> {
+    if targets.supports("css-oklch") || !(value.contains("oklch(") || value.contains("oklab(")) {
+        return None;
+    }
+    color::replace_color_functions(value, &["oklch", "oklab", "color-mix"], |call| {
+        Color::parse(call).ok().map(|c| c.to_srgb_string())
+    })
+    .filter(|fallback| fallback != value)
+}
+
+fn emit_single_declaration(property: &str, value: &str, depth: usize, out: &mut String) {
+    for _ in 0..depth {
+        out.push_str("    ");
+    }
     out.push_str(property);
     out.push_str(": ");
     out.push_str(value);
     out.push_str(";\n");
 }
 
-fn emit_media_rule(rule: &Rule, media: &crate::ast::MediaBlock, out: &mut String) {
+fn emit_media_rule(
+    selector: &str,
+    query: &str,
+    declarations: &[Declaration],
+    options: &EmitOptions,
+    out: &mut String,
+) {
     out.push_str("@media ");
-    out.push_str(&media.query);
+    out.push_str(&prefix_media_query(query, &options.targets));
     out.push_str(" {\n");
     out.push_str("  ");
-    out.push_str(&rule.selector);
+    out.push_str(selector);
     out.push_str(" {\n");
-    for decl in &media.declarations {
-        emit_declaration(decl, out);
+    for decl in declarations {
+        emit_declaration(decl, options, out);
     }
     out.push_str("  }\n");
     out.push_str("}\n");
 }
 
+/// Whether `decl` is a vendor-prefixed copy this emitter knows how to
+/// generate, and no target still needs it.
+fn is_unneeded_prefix(decl: &Declaration, targets: &Targets) -> bool {
+    if !decl.property.starts_with('-') && !decl.value.trim_start().starts_with('-') {
+        return false;
+    }
+    autoprefix_rules()
+        .values()
+        .flatten()
+        .find(|action| action.property == decl.property && (action.produced)(&decl.value))
+        .map(|action| !targets.needs_prefix(action.feature, action.prefix))
+        .unwrap_or(false)
+}
+
+/// Prefixed selector variants that must precede the standard one. Each
+/// variant is its own rule because one unknown pseudo invalidates a whole
+/// selector list. Returns nothing when the selector itself is an unneeded
+/// hand-written prefix and stripping is enabled.
+fn selectors_to_emit(selector: &str, options: &EmitOptions) -> Vec<String> {
+    if is_keyframes(selector) {
+        return keyframes_to_emit(selector, options);
+    }
+    if options.strip_prefixes
+        && SELECTOR_PREFIXES.iter().any(|entry| {
+            selector.contains(entry.prefixed)
+                && !options.targets.needs_prefix(entry.feature, entry.prefix)
+        })
+    {
+        return Vec::new();
+    }
+
+    let mut selectors: Vec<String> = SELECTOR_PREFIXES
+        .iter()
+        .filter(|entry| {
+            contains_pseudo(selector, entry.standard)
+                && options.targets.needs_prefix(entry.feature, entry.prefix)
+        })
+        .map(|entry| selector.replace(entry.standard, entry.prefixed))
+        .collect();
+    selectors.push(selector.to_string());
+    selectors
+}
+
+/// `@keyframes` preceded by an `@-webkit-keyframes` copy when a target
+/// needs the prefixed animation properties, which only see prefixed
+/// keyframes.
+fn keyframes_to_emit(selector: &str, options: &EmitOptions) -> Vec<String> {
+    let needed = options.targets.needs_prefix("css-animation", "-webkit-");
+    let selector = selector.trim();
+    match selector.strip_prefix("@keyframes ") {
+        Some(name) if needed => vec![
+            format!("@-webkit-keyframes {}", name.trim()),
+            selector.to_string(),
+        ],
+        Some(_) => vec![selector.to_string()],
+        None if options.strip_prefixes && !needed => Vec::new(),
+        None => vec![selector.to_string()],
+    }
+}
+
+/// Match `pseudo` only when it is not the tail of a longer pseudo, so
+/// `:fullscreen` does not match inside `::fullscreen-foo`.
+fn contains_pseudo(selector: &str, pseudo: &str) -> bool {
+    selector.match_indices(pseudo).any(|(idx, _)| {
+        let after = selector[idx + pseudo.len()..].chars().next();
+        let before = selector[..idx].chars().next_back();
+        !after
+            .map(|c| c.is_ascii_alphanumeric() || c == '-')
+            .unwrap_or(false)
+            && (pseudo.starts_with("::") || before != Some(':'))
+    })
+}
+
+struct SelectorPrefix {
+    feature: &'static str,
+    prefix: &'static str,
+    standard: &'static str,
+    prefixed: &'static str,
+}
+
+const SELECTOR_PREFIXES: &[SelectorPrefix] = &[
+    SelectorPrefix {
+        feature: "css-placeholder",
+        prefix: "-webkit-",
+        standard: "::placeholder",
+        prefixed: "::-webkit-input-placeholder",
+    },
+    SelectorPrefix {
+        feature: "css-placeholder",
+        prefix: "-moz-",
+        standard: "::placeholder",
+        prefixed: "::-moz-placeholder",
+    },
+    SelectorPrefix {
+        feature: "css-placeholder",
+        prefix: "-ms-",
+        standard: "::placeholder",
+        prefixed: ":-ms-input-placeholder",
+    },
+    SelectorPrefix {
+        feature: "fullscreen",
+        prefix: "-webkit-",
+        standard: ":fullscreen",
+        prefixed: ":-webkit-full-screen",
+    },
+    SelectorPrefix {
+        feature: "fullscreen",
+        prefix: "-moz-",
+        standard: ":fullscreen",
+        prefixed: ":-moz-full-screen",
+    },
+    SelectorPrefix {
+        feature: "fullscreen",
+        prefix: "-ms-",
+        standard: ":fullscreen",
+        prefixed: ":-ms-fullscreen",
+    },
+    SelectorPrefix {
+        feature: "css-selection",
+        prefix: "-moz-",
+        standard: "::selection",
+        prefixed: "::-moz-selection",
+    },
+];
+
+/// Prepend a `-webkit-device-pixel-ratio` equivalent to resolution queries
+/// for targets that predate the standard `resolution` media feature.
+fn prefix_media_query(query: &str, targets: &Targets) -> String {
+    if !query.contains("resolution") || !targets.needs_prefix("css-media-resolution", "-webkit-") {
+        return query.to_string();
+    }
+
+    let mut prefixed = String::new();
+    let mut rest = query;
+    let mut changed = false;
+    while let Some(open) = rest.find('(') {
+        let Some(close) = rest[open..].find(')').map(|rel| open + rel) else {
+            break;
+        };
+        prefixed.push_str(&rest[..open]);
+        let feature = &rest[open + 1..close];
+        match webkit_resolution_feature(feature) {
+            Some(converted) => {
+                prefixed.push_str(&format!("({})", converted));
+                changed = true;
+            }
+            None => prefixed.push_str(&rest[open..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+    prefixed.push_str(rest);
+
+    if changed {
+        format!("{}, {}", prefixed, query)
+    } else {
+        query.to_string()
+    }
+}
+
+fn webkit_resolution_feature(feature: &str) -> Option<String> {
+    let (name, value) = feature.split_once(':')?;
+    let name = match name.trim() {
+        "min-resolution" => "-webkit-min-device-pixel-ratio",
+        "max-resolution" => "-webkit-max-device-pixel-ratio",
+        "resolution" => "-webkit-device-pixel-ratio",
+        _ => return None,
+    };
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let number: f64 = value[..split].parse().ok()?;
+    let ratio = match &value[split..] {
+        "dppx" | "x" => number,
+        "dpi" => number / 96.0,
+        "dpcm" => number * 2.54 / 96.0,
+        _ => return None,
+    };
+    let ratio = (ratio * 100.0).round() / 100.0;
+    Some(format!("{}: {}", name, ratio))
+}
+
 fn autoprefix_rules() -> &'static HashMap<&'static str, Vec<PrefixAction>> {
     static RULES: OnceLock<HashMap<&'static str, Vec<PrefixAction>>> = OnceLock::new();
     RULES.get_or_init(build_autoprefix_map)
@@ -77,86 +964,208 @@ fn build_autoprefix_map() -> HashMap<&'static str, Vec<PrefixAction>> {
     let mut map: HashMap<&'static str, Vec<PrefixAction>> = HashMap::new();
 
     map.entry("border-radius").or_default().extend(vec![
-        PrefixAction::new("-webkit-border-radius", always_true, identity),
-        PrefixAction::new("-moz-border-radius", always_true, identity),
+        PrefixAction::prefixed_property("css-border-radius", "-webkit-border-radius"),
+        PrefixAction::prefixed_property("css-border-radius", "-moz-border-radius"),
     ]);
 
-    map.entry("box-shadow").or_default().push(PrefixAction::new(
-        "-webkit-box-shadow",
-        always_true,
-        identity,
-    ));
+    for (property, prefixed) in [
+        ("animation", "-webkit-animation"),
+        ("animation-name", "-webkit-animation-name"),
+        ("animation-duration", "-webkit-animation-duration"),
+        (
+            "animation-timing-function",
+            "-webkit-animation-timing-function",
+        ),
+        ("animation-delay", "-webkit-animation-delay"),
+        (
+            "animation-iteration-count",
+            "-webkit-animation-iteration-count",
+        ),
+        ("animation-direction", "-webkit-animation-direction"),
+        ("animation-fill-mode", "-webkit-animation-fill-mode"),
+        ("animation-play-state", "-webkit-animation-play-state"),
+    ] {
+        map.entry(property)
+            .or_default()
+            .push(PrefixAction::prefixed_property("css-animation", prefixed));
+    }
+
+    map.entry("box-shadow")
+        .or_default()
+        .push(PrefixAction::prefixed_property(
+            "css-boxshadow",
+            "-webkit-box-shadow",
+        ));
 
     map.entry("transform").or_default().extend(vec![
-        PrefixAction::new("-webkit-transform", always_true, identity),
-        PrefixAction::new("-ms-transform", always_true, identity),
+        PrefixAction::prefixed_property("transforms2d", "-webkit-transform"),
+        PrefixAction::prefixed_property("transforms2d", "-ms-transform"),
     ]);
 
-    map.entry("filter").or_default().push(PrefixAction::new(
-        "-webkit-filter",
-        always_true,
-        identity,
-    ));
+    map.entry("transform-origin").or_default().extend(vec![
+        PrefixAction::prefixed_property("transforms2d", "-webkit-transform-origin"),
+        PrefixAction::prefixed_property("transforms2d", "-ms-transform-origin"),
+    ]);
+
+    map.entry("filter")
+        .or_default()
+        .push(PrefixAction::prefixed_property(
+            "css-filters",
+            "-webkit-filter",
+        ));
 
     map.entry("backdrop-filter")
         .or_default()
-        .push(PrefixAction::new(
+        .push(PrefixAction::prefixed_property(
+            "css-backdrop-filter",
             "-webkit-backdrop-filter",
-            always_true,
-            identity,
         ));
 
     map.entry("appearance").or_default().extend(vec![
-        PrefixAction::new("-webkit-appearance", always_true, identity),
-        PrefixAction::new("-moz-appearance", always_true, identity),
+        PrefixAction::prefixed_property("css-appearance", "-webkit-appearance"),
+        PrefixAction::prefixed_property("css-appearance", "-moz-appearance"),
+    ]);
+
+    map.entry("user-select").or_default().extend(vec![
+        PrefixAction::prefixed_property("user-select-none", "-webkit-user-select"),
+        PrefixAction::prefixed_property("user-select-none", "-moz-user-select"),
+        PrefixAction::prefixed_property("user-select-none", "-ms-user-select"),
     ]);
 
+    map.entry("hyphens")
+        .or_default()
+        .push(PrefixAction::prefixed_property(
+            "css-hyphens",
+            "-webkit-hyphens",
+        ));
+
+    map.entry("mask-image")
+        .or_default()
+        .push(PrefixAction::prefixed_property(
+            "css-masks",
+            "-webkit-mask-image",
+        ));
+
+    map.entry("mask")
+        .or_default()
+        .push(PrefixAction::prefixed_property("css-masks", "-webkit-mask"));
+
+    map.entry("background-clip")
+        .or_default()
+        .push(PrefixAction::new(
+            "background-clip-text",
+            "-webkit-",
+            "-webkit-background-clip",
+            is_text_clip,
+            identity,
+            is_text_clip,
+        ));
+
     map.entry("background").or_default().push(PrefixAction::new(
+        "css-gradients",
+        "-webkit-",
         "background",
         gradient_condition,
         gradient_transform,
+        is_prefixed_gradient,
     ));
 
     map.entry("background-image")
         .or_default()
         .push(PrefixAction::new(
+            "css-gradients",
+            "-webkit-",
             "background-image",
             gradient_condition,
             gradient_transform,
+            is_prefixed_gradient,
         ));
 
     map.entry("display").or_default().extend(vec![
-        PrefixAction::new("display", is_flex_display, flex_webkit_box),
-        PrefixAction::new("display", is_flex_display, flex_ms_flexbox),
+        PrefixAction::new(
+            "flexbox-2009",
+            "-webkit-",
+            "display",
+            is_flex_display,
+            flex_webkit_box,
+            is_webkit_box,
+        ),
+        PrefixAction::new(
+            "flexbox-2012",
+            "-ms-",
+            "display",
+            is_flex_display,
+            flex_ms_flexbox,
+            is_ms_flexbox,
+        ),
     ]);
 
+    map.entry("position").or_default().push(PrefixAction::new(
+        "css-sticky",
+        "-webkit-",
+        "position",
+        is_sticky,
+        webkit_value,
+        is_webkit_sticky,
+    ));
+
     map.entry("cursor").or_default().push(PrefixAction::new(
+        "css3-cursors-grab",
+        "-webkit-",
         "cursor",
         is_grab_cursor,
-        grab_cursor_prefix,
+        webkit_value,
+        is_webkit_grab_cursor,
     ));
 
     map
 }
 
 struct PrefixAction {
+    /// Key into the `features` table of the compat data.
+    feature: &'static str,
+    prefix: &'static str,
     property: &'static str,
     condition: fn(&str) -> bool,
     transform: fn(&str) -> String,
+    /// Recognizes a value this action produces, so hand-written copies can be
+    /// stripped.
+    produced: fn(&str) -> bool,
 }
 
 impl PrefixAction {
     const fn new(
+        feature: &'static str,
+        prefix: &'static str,
         property: &'static str,
         condition: fn(&str) -> bool,
         transform: fn(&str) -> String,
+        produced: fn(&str) -> bool,
     ) -> Self {
         PrefixAction {
+            feature,
+            prefix,
             property,
             condition,
             transform,
+            produced,
         }
     }
+
+    fn prefixed_property(feature: &'static str, property: &'static str) -> Self {
+        let prefix = ["-webkit-", "-moz-", "-ms-", "-o-"]
+            .into_iter()
+            .find(|prefix| property.starts_with(prefix))
+            .unwrap_or("");
+        Self::new(
+            feature,
+            prefix,
+            property,
+            always_true,
+            identity,
+            always_true,
+        )
+    }
 }
 
 fn always_true(_value: &str) -> bool {
@@ -185,6 +1194,14 @@ fn gradient_transform(value: &str) -> String {
     format!("-webkit-{}", trimmed)
 }
 
+fn is_prefixed_gradient(value: &str) -> bool {
+    value
+        .trim_start()
+        .strip_prefix("-webkit-")
+        .map(gradient_condition)
+        .unwrap_or(false)
+}
+
 fn is_flex_display(value: &str) -> bool {
     value.trim() == "flex"
 }
@@ -197,10 +1214,308 @@ fn flex_ms_flexbox(_: &str) -> String {
     "-ms-flexbox".to_string()
 }
 
+fn is_webkit_box(value: &str) -> bool {
+    value.trim() == "-webkit-box"
+}
+
+fn is_ms_flexbox(value: &str) -> bool {
+    value.trim() == "-ms-flexbox"
+}
+
 fn is_grab_cursor(value: &str) -> bool {
     matches!(value.trim(), "grab" | "grabbing")
 }
 
-fn grab_cursor_prefix(value: &str) -> String {
+fn is_webkit_grab_cursor(value: &str) -> bool {
+    matches!(value.trim(), "-webkit-grab" | "-webkit-grabbing")
+}
+
+fn is_sticky(value: &str) -> bool {
+    value.trim() == "sticky"
+}
+
+fn is_webkit_sticky(value: &str) -> bool {
+    value.trim() == "-webkit-sticky"
+}
+
+fn is_text_clip(value: &str) -> bool {
+    value.trim() == "text"
+}
+
+fn webkit_value(value: &str) -> String {
     format!("-webkit-{}", value.trim())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser, resolver, theme::Theme};
+
+    fn render_with(input: &str, options: &EmitOptions) -> String {
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let theme = Theme::load_from_dir(&theme_dir).expect("load theme");
+        let stylesheet = parser::parse(input).expect("parse rc");
+        let resolved = resolver::resolve(stylesheet, &theme).expect("resolve");
+        emit_css_with_options(&resolved, options)
+    }
+
+    fn render_grouped(input: &str) -> String {
+        render_with(
+            input,
+            &EmitOptions {
+                group_media: true,
+                ..EmitOptions::default()
+            },
+        )
+    }
+
+    fn render_native(input: &str) -> String {
+        render_with(
+            input,
+            &EmitOptions {
+                nesting: Nesting::Native,
+                ..EmitOptions::default()
+            },
+        )
+    }
+
+    #[test]
+    fn group_shared_media_queries() {
+        let css = render_grouped(
+            "%no-base\n.a { width: @4; screen(@md) { width: @8; } }\n.b { color: red; screen(@md) { color: blue; } }",
+        );
+        assert_eq!(css.matches("@media (min-width: 48rem)").count(), 1);
+        let a = css.find("  .a {").expect("grouped .a");
+        let b = css.find("  .b {").expect("grouped .b");
+        assert!(a < b);
+        assert!(css.find(".b {\n").unwrap() < css.find("@media").unwrap());
+    }
+
+    #[test]
+    fn grouping_keeps_later_base_rules_winning() {
+        let css = render_grouped(
+            "%no-base\n.a { screen(@md) { margin-top: 2rem; } }\n.b { color: red; screen(@md) { color: blue; } }\n.a { margin: 0; }\n.c { screen(@md) { width: @4; } }",
+        );
+        let first = css.find("@media (min-width: 48rem)").expect("first group");
+        let later = css.find(".a {\n    margin: 0;").expect("later .a");
+        let second = css
+            .rfind("@media (min-width: 48rem)")
+            .expect("second group");
+        assert!(first < later && later < second, "{css}");
+        assert!(css[first..later].contains("  .b {"), "{css}");
+        assert!(css[second..].contains("  .c {"), "{css}");
+    }
+
+    #[test]
+    fn order_breakpoints_mobile_first() {
+        let css = render_grouped(
+            "%no-base\n.a { screen(@lg) { width: @8; } dark { color: white; } screen(@sm) { width: @4; } }",
+        );
+        let sm = css.find("(min-width: 40rem)").expect("sm");
+        let lg = css.find("(min-width: 64rem)").expect("lg");
+        let dark = css.find("(prefers-color-scheme: dark)").expect("dark");
+        assert!(sm < lg);
+        assert!(lg < dark);
+    }
+
+    #[test]
+    fn native_nesting_keeps_tree() {
+        let css = render_native(
+            "%no-base\n.card { color: red; screen(@md) { padding: @4; } &:hover { color: blue; .icon { opacity: 0.5; } } }",
+        );
+        assert!(css.contains(".card {\n    color: red;\n"));
+        assert!(css.contains("    @media (min-width: 48rem) {\n        padding: 1rem;\n    }\n"));
+        assert!(css.contains("    &:hover {\n        color: blue;\n"));
+        assert!(css.contains("        .icon {\n            opacity: 0.5;\n        }\n"));
+        assert!(!css.contains(".card:hover"));
+    }
+
+    #[test]
+    fn native_nesting_hoists_concatenated_selectors() {
+        let css = render_native("%no-base\n.btn { color: red; &-primary { color: blue; } }");
+        assert!(css.contains(".btn {\n    color: red;\n}\n"));
+        assert!(css.contains("\n.btn-primary {\n    color: blue;\n}\n"));
+
+        // A hoisted rule keeps its place among its siblings.
+        let css = render_native(
+            "%no-base\n.btn { color: red; &:hover { color: green; } &-primary { color: blue; } &:focus { color: black; } }",
+        );
+        assert_eq!(
+            css,
+            ".btn {\n    color: red;\n    &:hover {\n        color: green;\n    }\n}\n\n\
+             .btn-primary {\n    color: blue;\n}\n\n\
+             .btn {\n    &:focus {\n        color: black;\n    }\n}\n\n"
+        );
+    }
+
+    fn render_for(input: &str, query: &str, strip_prefixes: bool) -> String {
+        render_with(
+            input,
+            &EmitOptions {
+                targets: Targets::parse(query).expect("parse targets"),
+                strip_prefixes,
+                ..EmitOptions::default()
+            },
+        )
+    }
+
+    #[test]
+    fn modern_targets_skip_obsolete_prefixes() {
+        let css = render_for(
+            "%no-base\n.a { border-radius: 4px; box-shadow: none; backdrop-filter: blur(2px); }",
+            "last 2 chrome versions, safari 17.6",
+            false,
+        );
+        assert!(!css.contains("-moz-border-radius"));
+        assert!(!css.contains("-webkit-box-shadow"));
+        assert!(css.contains("-webkit-backdrop-filter: blur(2px);"));
+    }
+
+    #[test]
+    fn legacy_targets_prefix_values_selectors_and_media() {
+        let css = render_for(
+            "%no-base\n.a { display: flex; &::placeholder { color: gray; } }\n.b { screen(@md) { color: red; } }\n.c { @media (min-resolution: 2dppx) { color: blue; } }",
+            "safari 6, firefox 50, ie 10",
+            false,
+        );
+        assert!(css.contains("display: -webkit-box;"));
+        assert!(css.contains("display: -ms-flexbox;"));
+        assert!(css.contains(".a::-webkit-input-placeholder {"));
+        assert!(css.contains(".a::-moz-placeholder {"));
+        assert!(css.contains(".a::placeholder {"));
+        assert!(
+            css.contains("@media (-webkit-min-device-pixel-ratio: 2), (min-resolution: 2dppx) {")
+        );
+        assert!(css.contains("@media (min-width: 48rem) {"));
+    }
+
+    #[test]
+    fn prefixed_animations_get_prefixed_keyframes() {
+        let input = "%no-base\n@keyframes fade {\n  from { opacity: 0; }\n  to { opacity: 1; }\n}\n.a { animation: fade 1s; }";
+        let frames = " fade {\n    from {\n        opacity: 0;\n    }\n    to {\n        opacity: 1;\n    }\n}\n";
+
+        let legacy = render_for(input, "safari 8", false);
+        assert!(
+            legacy.starts_with(&format!("@-webkit-keyframes{frames}\n@keyframes{frames}")),
+            "{legacy}"
+        );
+        assert!(legacy.contains("-webkit-animation: fade 1s;\n    animation: fade 1s;"));
+
+        let modern = render_for(input, "last 2 chrome versions", false);
+        assert!(
+            modern.starts_with(&format!("@keyframes{frames}")),
+            "{modern}"
+        );
+        assert!(!modern.contains("-webkit-"), "{modern}");
+
+        let hand_written = input.replace("@keyframes", "@-webkit-keyframes");
+        let stripped = render_for(&hand_written, "last 2 chrome versions", true);
+        assert!(!stripped.contains("keyframes"), "{stripped}");
+    }
+
+    #[test]
+    fn legacy_targets_get_srgb_color_fallbacks() {
+        let input = "%no-base\n.a { color: oklch(62.8% 0.2577 29.23); background: color-mix(in srgb, #ff0000 50%, #0000ff); }";
+
+        let modern = render_for(input, "last 2 chrome versions", false);
+        assert!(modern.contains("{\n    color: oklch(62.8% 0.2577 29.23);"));
+        assert!(modern.contains("color-mix(in srgb"));
+
+        let legacy = render_for(input, "safari 15.2", false);
+        assert!(legacy.contains("color: #ff0000;\n    color: oklch(62.8% 0.2577 29.23);"));
+        assert!(legacy.contains("background: #800080;"));
+        assert!(!legacy.contains("color-mix("));
+
+        let custom = render_for(
+            "%no-base\n.a { --brand: oklch(62.8% 0.2577 29.23); }",
+            "safari 15.2",
+            false,
+        );
+        assert!(custom.contains("--brand: #ff0000;\n}"), "{custom}");
+        assert!(!custom.contains("oklch("), "{custom}");
+    }
+
+    #[test]
+    fn strip_unneeded_hand_written_prefixes() {
+        let css = render_for(
+            "%no-base\n.a { -webkit-box-shadow: none; box-shadow: none; display: -webkit-box; -webkit-unknown: 1; }\n.a::-moz-selection { color: red; }",
+            "last 2 chrome versions",
+            true,
+        );
+        assert!(!css.contains("-webkit-box-shadow"));
+        assert!(!css.contains("-webkit-box;"));
+        assert!(!css.contains("-moz-selection"));
+        assert!(css.contains("box-shadow: none;"));
+        assert!(css.contains("-webkit-unknown: 1;"));
+    }
+
+    fn render_dark(input: &str, dark_mode: DarkMode, nesting: Nesting) -> String {
+        render_with(
+            input,
+            &EmitOptions {
+                dark_mode,
+                nesting,
+                targets: Targets::parse("chrome 120").expect("parse targets"),
+                ..EmitOptions::default()
+            },
+        )
+    }
+
+    #[test]
+    fn dark_mode_class_and_attribute_scope_selectors() {
+        let input = "%no-base\n%dark light-ui\n.a, .b { color: @surface; screen(@md) { color: @foreground; } }";
+
+        let class = render_dark(input, DarkMode::Class, Nesting::Flatten);
+        assert!(!class.contains("prefers-color-scheme"), "{class}");
+        assert!(class.contains(":root.dark {\n"), "{class}");
+        assert!(
+            class.contains(".dark .a, .dark .b {\n    color: oklch(21%"),
+            "{class}"
+        );
+        assert!(
+            class.contains(
+                "@media (min-width: 48rem) {\n  .dark .a, .dark .b {\n    color: oklch(98.5%"
+            ),
+            "{class}"
+        );
+
+        let attribute = render_dark(input, DarkMode::Attribute, Nesting::Native);
+        assert!(
+            attribute.contains(":root {\n    &[data-theme=\"dark\"] {\n"),
+            "{attribute}"
+        );
+        assert!(
+            attribute.contains("    [data-theme=\"dark\"] & {\n        color: oklch(21%"),
+            "{attribute}"
+        );
+    }
+
+    #[test]
+    fn dark_mode_both_keeps_the_query_and_adds_an_override() {
+        let css = render_dark(
+            "%no-base\n.a { dark { color: red; } }",
+            DarkMode::Both,
+            Nesting::Flatten,
+        );
+        assert_eq!(
+            css,
+            "@media (prefers-color-scheme: dark) {\n  :root:not([data-theme=\"light\"]) .a {\n    color: red;\n  }\n}\n\n[data-theme=\"dark\"] .a {\n    color: red;\n}\n\n"
+        );
+    }
+
+    #[test]
+    fn px_to_rem_uses_the_base_preset_and_keeps_borders() {
+        let css = render_with(
+            "%base-18\n.a { padding: 9px 18px; border: 1px solid red; box-shadow: 0 1px 2px red; screen(@md) { gap: 27px; } }",
+            &EmitOptions {
+                px_to_rem: Some(PxToRem::default()),
+                ..EmitOptions::default()
+            },
+        );
+        assert!(css.contains(":root {\n    font-size: 18px;"), "{css}");
+        assert!(css.contains("padding: 0.5rem 1rem;"), "{css}");
+        assert!(css.contains("border: 1px solid red;"), "{css}");
+        assert!(css.contains("box-shadow: 0 1px 2px red;"), "{css}");
+        assert!(css.contains("gap: 1.5rem;"), "{css}");
+    }
+}
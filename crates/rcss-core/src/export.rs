@@ -0,0 +1,734 @@
+use crate::theme::Theme;
+use crate::tokens::TokenValue;
+use serde_json::{Map, Value, json};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+const HEADER: &str = "Generated by `rcss tokens export`. Do not edit.";
+
+/// A file format `rcss tokens export` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// An `as const` object plus a key type per collection.
+    Ts,
+    Js,
+    Json,
+    /// `$collection-token` variables.
+    Scss,
+    /// `--collection-token` custom properties on `:root`.
+    CssVars,
+    /// Nested `enum`s of `static let` strings.
+    Swift,
+    /// Nested `object`s of `const val` strings.
+    Kotlin,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ts" => Ok(ExportFormat::Ts),
+            "js" => Ok(ExportFormat::Js),
+            "json" => Ok(ExportFormat::Json),
+            "scss" => Ok(ExportFormat::Scss),
+            "css-vars" => Ok(ExportFormat::CssVars),
+            "swift" => Ok(ExportFormat::Swift),
+            "kotlin" => Ok(ExportFormat::Kotlin),
+            other => Err(format!(
+                "Unknown export format '{}' (expected ts, js, json, scss, css-vars, swift or kotlin)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ExportFormat::Ts => "ts",
+            ExportFormat::Js => "js",
+            ExportFormat::Json => "json",
+            ExportFormat::Scss => "scss",
+            ExportFormat::CssVars => "css-vars",
+            ExportFormat::Swift => "swift",
+            ExportFormat::Kotlin => "kotlin",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How collection and token names are written. Names are split into words
+/// at dashes, underscores, dots and camelCase humps (`borderRadius`,
+/// `slate-500`, `0.5`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Naming {
+    /// As in the theme (`borderRadius`, `slate-500`).
+    Keep,
+    Kebab,
+    Camel,
+    Snake,
+    /// `SCREAMING_SNAKE_CASE`.
+    Constant,
+}
+
+impl FromStr for Naming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Naming::Keep),
+            "kebab" => Ok(Naming::Kebab),
+            "camel" => Ok(Naming::Camel),
+            "snake" => Ok(Naming::Snake),
+            "constant" => Ok(Naming::Constant),
+            other => Err(format!(
+                "Unknown naming '{}' (expected keep, kebab, camel, snake or constant)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Naming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Naming::Keep => "keep",
+            Naming::Kebab => "kebab",
+            Naming::Camel => "camel",
+            Naming::Snake => "snake",
+            Naming::Constant => "constant",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Naming {
+    /// The convention a format uses when none is asked for: theme names
+    /// where keys are strings, camelCase for Swift and Kotlin identifiers.
+    pub fn default_for(format: ExportFormat) -> Self {
+        match format {
+            ExportFormat::Swift | ExportFormat::Kotlin => Naming::Camel,
+            _ => Naming::Keep,
+        }
+    }
+
+    fn apply(self, name: &str) -> String {
+        let words = words(name);
+        match self {
+            Naming::Keep => name.to_string(),
+            Naming::Kebab => words.join("-"),
+            Naming::Snake => words.join("_"),
+            Naming::Constant => words.join("_").to_uppercase(),
+            Naming::Camel => {
+                let mut out = String::new();
+                for (i, word) in words.iter().enumerate() {
+                    if i == 0 {
+                        out.push_str(word);
+                        continue;
+                    }
+                    // `0.5` → `0_5`, not `05`.
+                    if out.ends_with(|c: char| c.is_ascii_digit())
+                        && word.starts_with(|c: char| c.is_ascii_digit())
+                    {
+                        out.push('_');
+                    }
+                    out.push_str(&capitalize(word));
+                }
+                out
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub naming: Naming,
+    /// Collections to export; all of them when empty.
+    pub collections: Vec<String>,
+}
+
+/// One exported token: its name in the theme and its light and dark CSS.
+struct Entry {
+    key: String,
+    value: String,
+    dark: Option<String>,
+}
+
+/// Write the theme's token collections in another platform's format.
+/// Keyframes are skipped (they are not values) and font sizes export their
+/// size only; light/dark tokens export both values.
+pub fn export(theme: &Theme, options: &ExportOptions) -> Result<String, String> {
+    let collections = select(theme, &options.collections)?;
+    let naming = options.naming;
+    match options.format {
+        ExportFormat::Ts | ExportFormat::Js => Ok(render_js(&collections, naming, options.format)),
+        ExportFormat::Json => Ok(render_json(&collections, naming)),
+        ExportFormat::Scss => render_flat(&collections, naming, FlatStyle::Scss),
+        ExportFormat::CssVars => render_flat(&collections, naming, FlatStyle::CssVars),
+        ExportFormat::Swift => render_nested(&collections, naming, NestedStyle::Swift),
+        ExportFormat::Kotlin => render_nested(&collections, naming, NestedStyle::Kotlin),
+    }
+}
+
+fn select<'a>(theme: &'a Theme, wanted: &[String]) -> Result<Vec<(&'a str, Vec<Entry>)>, String> {
+    for name in wanted {
+        if !theme.collections.contains_key(name) {
+            return Err(format!("Unknown collection '{}'", name));
+        }
+    }
+
+    let mut names: Vec<&String> = theme
+        .collections
+        .keys()
+        .filter(|name| wanted.is_empty() || wanted.contains(name))
+        .collect();
+    names.sort();
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            let mut entries: Vec<Entry> = theme.collections[name]
+                .iter()
+                .filter(|(_, token)| !matches!(token.value, TokenValue::Keyframes(_)))
+                .map(|(key, token)| {
+                    let dark = match &token.value {
+                        TokenValue::Modes { dark, .. } => Some(dark.to_css()),
+                        _ => None,
+                    };
+                    Entry {
+                        key: key.clone(),
+                        value: token.value.to_css(),
+                        dark,
+                    }
+                })
+                .collect();
+            entries.sort_by(|a, b| natural_cmp(&a.key, &b.key));
+            (name.as_str(), entries)
+        })
+        .filter(|(_, entries)| !entries.is_empty())
+        .collect())
+}
+
+fn render_js(collections: &[(&str, Vec<Entry>)], naming: Naming, format: ExportFormat) -> String {
+    let mut out = format!("// {}\n\nexport const tokens = {{\n", HEADER);
+    for (collection, entries) in collections {
+        out.push_str(&format!("  {}: {{\n", js_key(&naming.apply(collection))));
+        for entry in entries {
+            let value = match &entry.dark {
+                Some(dark) => format!(
+                    "{{ light: {}, dark: {} }}",
+                    js_string(&entry.value),
+                    js_string(dark)
+                ),
+                None => js_string(&entry.value),
+            };
+            out.push_str(&format!(
+                "    {}: {},\n",
+                js_key(&naming.apply(&entry.key)),
+                value
+            ));
+        }
+        out.push_str("  },\n");
+    }
+
+    if format == ExportFormat::Js {
+        out.push_str("};\n");
+        return out;
+    }
+    out.push_str("} as const;\n\nexport type Tokens = typeof tokens;\n");
+    for (collection, _) in collections {
+        out.push_str(&format!(
+            "export type {}Token = keyof Tokens[{}];\n",
+            pascal_case(collection),
+            js_string(&naming.apply(collection))
+        ));
+    }
+    out
+}
+
+fn render_json(collections: &[(&str, Vec<Entry>)], naming: Naming) -> String {
+    let mut root = Map::new();
+    for (collection, entries) in collections {
+        let tokens: Map<String, Value> = entries
+            .iter()
+            .map(|entry| {
+                let value = match &entry.dark {
+                    Some(dark) => json!({ "light": entry.value, "dark": dark }),
+                    None => json!(entry.value),
+                };
+                (naming.apply(&entry.key), value)
+            })
+            .collect();
+        root.insert(naming.apply(collection), Value::Object(tokens));
+    }
+    let mut text = serde_json::to_string_pretty(&root).expect("JSON values serialize");
+    text.push('\n');
+    text
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlatStyle {
+    Scss,
+    CssVars,
+}
+
+/// One variable per token, named `collection-token` in the chosen
+/// convention; dark values get a `dark` variable (SCSS) or a
+/// `prefers-color-scheme` block (custom properties).
+fn render_flat(
+    collections: &[(&str, Vec<Entry>)],
+    naming: Naming,
+    style: FlatStyle,
+) -> Result<String, String> {
+    let mut light = Vec::new();
+    let mut dark = Vec::new();
+    for (collection, entries) in collections {
+        let mut names = Names::new(collection);
+        let mut block = Vec::new();
+        for entry in entries {
+            let name = css_ident(&joined(naming, &[collection, &entry.key]));
+            names.claim(&name, &entry.key)?;
+            block.push((name.clone(), entry.value.clone()));
+            if let Some(value) = &entry.dark {
+                match style {
+                    FlatStyle::Scss => {
+                        let dark_name =
+                            css_ident(&joined(naming, &[collection, &entry.key, "dark"]));
+                        names.claim(&dark_name, &entry.key)?;
+                        block.push((dark_name, value.clone()));
+                    }
+                    FlatStyle::CssVars => dark.push((name, value.clone())),
+                }
+            }
+        }
+        light.push(block);
+    }
+
+    let line = |(name, value): &(String, String), indent: &str| match style {
+        FlatStyle::Scss => format!("{}${}: {};\n", indent, name, value),
+        FlatStyle::CssVars => format!("{}--{}: {};\n", indent, name, value),
+    };
+    let blocks: Vec<String> = light
+        .iter()
+        .map(|block| {
+            let indent = if style == FlatStyle::CssVars {
+                "    "
+            } else {
+                ""
+            };
+            block.iter().map(|entry| line(entry, indent)).collect()
+        })
+        .collect();
+
+    Ok(match style {
+        FlatStyle::Scss => format!("// {}\n\n{}", HEADER, blocks.join("\n")),
+        FlatStyle::CssVars => {
+            let mut out = format!("/* {} */\n\n:root {{\n{}}}\n", HEADER, blocks.join("\n"));
+            if !dark.is_empty() {
+                out.push_str("\n@media (prefers-color-scheme: dark) {\n    :root {\n");
+                for entry in &dark {
+                    out.push_str(&line(entry, "        "));
+                }
+                out.push_str("    }\n}\n");
+            }
+            out
+        }
+    })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum NestedStyle {
+    Swift,
+    Kotlin,
+}
+
+/// A `Tokens` namespace with one nested namespace per collection; dark
+/// values get a second constant with a `dark` suffix.
+fn render_nested(
+    collections: &[(&str, Vec<Entry>)],
+    naming: Naming,
+    style: NestedStyle,
+) -> Result<String, String> {
+    let (open, nested, constant) = match style {
+        NestedStyle::Swift => ("public enum Tokens {", "public enum", "public static let"),
+        NestedStyle::Kotlin => ("object Tokens {", "object", "const val"),
+    };
+    let mut out = format!("// {}\n\n{}\n", HEADER, open);
+    for (index, (collection, entries)) in collections.iter().enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!("    {} {} {{\n", nested, pascal_case(collection)));
+        let mut names = Names::new(collection);
+        for entry in entries {
+            let mut constants = vec![(naming.apply(&entry.key), &entry.value)];
+            if let Some(dark) = &entry.dark {
+                constants.push((joined(naming, &[&entry.key, "dark"]), dark));
+            }
+            for (name, value) in constants {
+                let name = identifier(&name, style);
+                names.claim(&name, &entry.key)?;
+                let value = match style {
+                    NestedStyle::Swift => quoted(value, false),
+                    NestedStyle::Kotlin => quoted(value, true),
+                };
+                out.push_str(&format!("        {} {} = {}\n", constant, name, value));
+            }
+        }
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+/// Names handed out within one collection, so two tokens that come out the
+/// same after renaming (`0.5` and `0-5` → `0_5`) are an error rather than a
+/// silent duplicate.
+struct Names<'a> {
+    collection: &'a str,
+    claimed: HashMap<String, String>,
+}
+
+impl<'a> Names<'a> {
+    fn new(collection: &'a str) -> Self {
+        Names {
+            collection,
+            claimed: HashMap::new(),
+        }
+    }
+
+    fn claim(&mut self, name: &str, key: &str) -> Result<(), String> {
+        match self.claimed.get(name) {
+            Some(other) if other != key => Err(format!(
+                "{}: tokens '{}' and '{}' both export as '{}'; pick another --naming",
+                self.collection, other, key, name
+            )),
+            _ => {
+                self.claimed.insert(name.to_string(), key.to_string());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// `parts` joined into one name: dash-joined when keeping theme names.
+fn joined(naming: Naming, parts: &[&str]) -> String {
+    match naming {
+        Naming::Keep => parts.join("-"),
+        _ => naming.apply(&parts.join("-")),
+    }
+}
+
+/// Order keys the way people count: `amber-50` before `amber-100`.
+pub(crate) fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(ac), Some(bc)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if ac.is_ascii_digit() && bc.is_ascii_digit() {
+            let end = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let (a_end, b_end) = (end(a), end(b));
+            let (a_num, b_num) = (
+                a[..a_end].trim_start_matches('0'),
+                b[..b_end].trim_start_matches('0'),
+            );
+            let order = a_num
+                .len()
+                .cmp(&b_num.len())
+                .then_with(|| a_num.cmp(b_num))
+                .then_with(|| a_end.cmp(&b_end));
+            if order != Ordering::Equal {
+                return order;
+            }
+            a = &a[a_end..];
+            b = &b[b_end..];
+        } else if ac != bc {
+            return ac.cmp(&bc);
+        } else {
+            a = &a[ac.len_utf8()..];
+            b = &b[bc.len_utf8()..];
+        }
+    }
+}
+
+/// The words of a name; a `/` reads as "of" (`1/2` → `1`, `of`, `2`).
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.' | ' ' | '/') {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            if c == '/' {
+                words.push("of".to_string());
+            }
+            previous = None;
+            continue;
+        }
+        if c.is_uppercase() && previous.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit()) {
+            words.push(std::mem::take(&mut current));
+        }
+        current.extend(c.to_lowercase());
+        previous = Some(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn pascal_case(name: &str) -> String {
+    words(name).iter().map(|word| capitalize(word)).collect()
+}
+
+/// Replace what a CSS or SCSS identifier cannot hold (`0.5` → `0_5`,
+/// `1/2` → `1-of-2`).
+fn css_ident(name: &str) -> String {
+    let mut ident = String::new();
+    for c in name.chars() {
+        match c {
+            '/' => ident.push_str("-of-"),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => ident.push(c),
+            _ => ident.push('_'),
+        }
+    }
+    ident
+}
+
+const SWIFT_KEYWORDS: &[&str] = &[
+    "as",
+    "break",
+    "case",
+    "class",
+    "continue",
+    "default",
+    "do",
+    "else",
+    "enum",
+    "extension",
+    "false",
+    "for",
+    "func",
+    "if",
+    "import",
+    "in",
+    "init",
+    "is",
+    "let",
+    "nil",
+    "private",
+    "public",
+    "repeat",
+    "return",
+    "self",
+    "static",
+    "struct",
+    "switch",
+    "true",
+    "try",
+    "var",
+    "where",
+    "while",
+];
+
+const KOTLIN_KEYWORDS: &[&str] = &[
+    "as",
+    "break",
+    "class",
+    "continue",
+    "do",
+    "else",
+    "false",
+    "for",
+    "fun",
+    "if",
+    "in",
+    "interface",
+    "is",
+    "null",
+    "object",
+    "package",
+    "return",
+    "super",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typealias",
+    "typeof",
+    "val",
+    "var",
+    "when",
+    "while",
+];
+
+/// A Swift or Kotlin identifier: other characters become `_`, a leading
+/// digit gets a `_` prefix and keywords are backquoted.
+fn identifier(name: &str, style: NestedStyle) -> String {
+    let mut ident = String::new();
+    for c in name.chars() {
+        match c {
+            '/' => ident.push_str("_of_"),
+            c if c.is_alphanumeric() || c == '_' => ident.push(c),
+            _ => ident.push('_'),
+        }
+    }
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    let keywords = match style {
+        NestedStyle::Swift => SWIFT_KEYWORDS,
+        NestedStyle::Kotlin => KOTLIN_KEYWORDS,
+    };
+    if keywords.contains(&ident.as_str()) {
+        format!("`{}`", ident)
+    } else {
+        ident
+    }
+}
+
+fn js_string(value: &str) -> String {
+    serde_json::to_string(value).expect("strings serialize")
+}
+
+/// Object keys that are identifiers stay bare.
+fn js_key(key: &str) -> String {
+    let mut chars = key.chars();
+    let is_identifier = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if is_identifier {
+        key.to_string()
+    } else {
+        js_string(key)
+    }
+}
+
+/// A Swift or Kotlin string literal; Kotlin also escapes `$` templates.
+fn quoted(value: &str, kotlin: bool) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '$' if kotlin => out.push_str("\\$"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme() -> Theme {
+        let dir = std::env::temp_dir().join(format!("rcss-export-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("tokens.json"),
+            r##"{
+                "collections": {
+                    "colors": {
+                        "slate": { "500": "#64748b" },
+                        "surface": { "light": "#ffffff", "dark": "#18181b" }
+                    },
+                    "spacing": { "0.5": "0.125rem", "DEFAULT": "1rem" }
+                },
+                "properties": {
+                    "textColor": { "collection": "colors" },
+                    "padding": { "collection": "spacing" }
+                }
+            }"##,
+        )
+        .unwrap();
+        Theme::load_from_dir(&dir.display().to_string()).unwrap()
+    }
+
+    fn render(format: &str, naming: Option<&str>, collections: &[&str]) -> String {
+        let format: ExportFormat = format.parse().unwrap();
+        let options = ExportOptions {
+            format,
+            naming: naming.map_or(Naming::default_for(format), |n| n.parse().unwrap()),
+            collections: collections.iter().map(|c| c.to_string()).collect(),
+        };
+        export(&theme(), &options).unwrap()
+    }
+
+    #[test]
+    fn typescript_objects_have_literal_key_types() {
+        let ts = render("ts", None, &[]);
+        assert!(ts.contains("  colors: {\n    \"slate-500\": \"#64748b\",\n"));
+        assert!(ts.contains("    surface: { light: \"#ffffff\", dark: \"#18181b\" },\n"));
+        assert!(ts.contains("} as const;\n"));
+        assert!(ts.contains("export type SpacingToken = keyof Tokens[\"spacing\"];\n"));
+
+        let js = render("js", Some("snake"), &["spacing"]);
+        assert!(js.contains("  spacing: {\n    \"0_5\": \"0.125rem\",\n    default: \"1rem\",\n"));
+        assert!(!js.contains("colors"));
+    }
+
+    #[test]
+    fn flat_and_native_formats() {
+        let scss = render("scss", None, &["colors"]);
+        assert!(scss.contains("$colors-slate-500: #64748b;\n"));
+        assert!(scss.contains("$colors-surface-dark: #18181b;\n"));
+
+        let css = render("css-vars", None, &[]);
+        assert!(css.contains("    --spacing-0_5: 0.125rem;\n"));
+        assert!(css.contains(
+            "@media (prefers-color-scheme: dark) {\n    :root {\n        --colors-surface: #18181b;\n"
+        ));
+
+        let swift = render("swift", None, &[]);
+        assert!(swift.contains(
+            "    public enum Spacing {\n        public static let _0_5 = \"0.125rem\"\n"
+        ));
+        assert!(swift.contains("        public static let `default` = \"1rem\"\n"));
+        assert!(swift.contains("        public static let surfaceDark = \"#18181b\"\n"));
+
+        let kotlin = render("kotlin", Some("constant"), &["colors"]);
+        assert!(
+            kotlin.contains("    object Colors {\n        const val SLATE_500 = \"#64748b\"\n")
+        );
+    }
+
+    #[test]
+    fn unknown_collections_and_name_clashes_are_errors() {
+        let theme = theme();
+        let options = ExportOptions {
+            format: ExportFormat::Json,
+            naming: Naming::Keep,
+            collections: vec!["colours".to_string()],
+        };
+        assert_eq!(
+            export(&theme, &options).unwrap_err(),
+            "Unknown collection 'colours'"
+        );
+
+        let mut names = Names::new("spacing");
+        names.claim("0_5", "0.5").unwrap();
+        assert_eq!(
+            names.claim("0_5", "0-5").unwrap_err(),
+            "spacing: tokens '0.5' and '0-5' both export as '0_5'; pick another --naming"
+        );
+    }
+}
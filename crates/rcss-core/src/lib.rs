@@ -1,9 +1,26 @@
 pub mod ast;
+#[cfg(feature = "builtin-theme")]
+mod builtin;
+pub mod color;
+pub mod dtcg;
 pub mod emitter;
 pub mod error;
+pub mod export;
 pub mod loader;
+mod math;
 pub mod parser;
+pub mod presets;
 pub mod resolver;
+mod suggest;
+pub mod tailwind;
+pub mod targets;
 pub mod theme;
+pub mod theme_check;
+pub mod theme_scope;
+pub mod token_index;
+pub mod token_refs;
+pub mod token_search;
+pub mod tokens;
+pub mod units;
 
 pub use theme::Theme;
@@ -0,0 +1,759 @@
+use crate::color::{self, Color};
+use std::fmt;
+
+/// A number and its unit: `1.5rem`, `50%`, `2`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Quantity {
+    pub value: f64,
+    pub unit: String,
+}
+
+impl Quantity {
+    /// `css` as a quantity, when it is a plain number or dimension.
+    pub(crate) fn parse(css: &str) -> Option<Quantity> {
+        let css = css.trim();
+        let end = css
+            .char_indices()
+            .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && matches!(c, '-' | '+'))))
+            .map_or(css.len(), |(i, _)| i);
+        let value = css[..end].parse::<f64>().ok()?;
+        let unit = &css[end..];
+        (unit == "%" || unit.chars().all(|c| c.is_ascii_alphabetic())).then(|| Quantity {
+            value,
+            unit: unit.to_ascii_lowercase(),
+        })
+    }
+
+    /// What the unit measures, for error messages.
+    fn kind(&self) -> &'static str {
+        match self.unit.as_str() {
+            "" => "a number",
+            "%" => "a percentage",
+            "s" | "ms" => "a duration",
+            "deg" | "rad" | "grad" | "turn" => "an angle",
+            "fr" => "a flex fraction",
+            "dpi" | "dpcm" | "dppx" | "x" => "a resolution",
+            _ => "a length",
+        }
+    }
+
+    fn is_length(&self) -> bool {
+        matches!(self.kind(), "a length" | "a percentage")
+    }
+}
+
+/// The unit of `rem(24px)`: px, written as rem once the resolver knows the
+/// root font size.
+const PX_AS_REM: &str = "px as rem";
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = color::format_number(self.value, 4);
+        match self.unit.as_str() {
+            PX_AS_REM => write!(f, "rem({}px)", value),
+            unit => write!(f, "{}{}", value, unit),
+        }
+    }
+}
+
+/// How [`fold`] reads and resolves the `@tokens` of a value.
+pub(crate) trait Operands {
+    /// The end of the `@token` starting at `at`, or `None` when the `@`
+    /// does not start a token (`@(...)`).
+    fn token_end(&self, value: &str, at: usize) -> Result<Option<usize>, String>;
+    /// `value[at..end]` as CSS, its tokens resolved.
+    fn resolve(&self, value: &str, at: usize, end: usize) -> Result<String, String>;
+    /// An arithmetic error at `at`.
+    fn error(&self, at: usize, message: String) -> String;
+}
+
+/// Fold the arithmetic on tokens in `value`: `@4 * 2` becomes `2rem`,
+/// `@4 + 10px` becomes `calc(1rem + 10px)` and `max(@2, @4)` becomes
+/// `1rem`. Only expressions holding a token or `rem()` are touched; everything else,
+/// tokens outside expressions included, is left for the resolver. `+` and
+/// `-` need spaces around them, as in `calc()`; `/` divides at the top
+/// level only when `slash_divides` (not in `font: @base / 1.5`).
+pub(crate) fn fold(
+    value: &str,
+    slash_divides: bool,
+    operands: &dyn Operands,
+) -> Result<String, String> {
+    let mut out = String::new();
+    let mut idx = 0;
+    let mut quote = None;
+    while idx < value.len() {
+        let ch = value[idx..].chars().next().unwrap();
+        match quote {
+            Some(q) if ch == q => quote = None,
+            Some(_) => {}
+            None if ch == '"' || ch == '\'' => quote = Some(ch),
+            None => {
+                let starts_segment = value[..idx]
+                    .chars()
+                    .next_back()
+                    .is_none_or(|prev| prev.is_whitespace() || matches!(prev, ',' | '('));
+                if starts_segment
+                    && let Some((css, end)) = fold_at(value, idx, slash_divides, operands)?
+                {
+                    out.push_str(&css);
+                    idx = end;
+                    continue;
+                }
+            }
+        }
+        out.push(ch);
+        idx += ch.len_utf8();
+    }
+    Ok(out)
+}
+
+/// The folded expression starting at `start` and its end, if one does.
+fn fold_at(
+    value: &str,
+    start: usize,
+    slash_divides: bool,
+    operands: &dyn Operands,
+) -> Result<Option<(String, usize)>, String> {
+    let mut parser = Parser {
+        value,
+        pos: start,
+        slash_divides,
+        operands,
+    };
+    let Some(node) = parser.sum(false)? else {
+        return Ok(None);
+    };
+    let ends_segment = value[parser.pos..]
+        .chars()
+        .next()
+        .is_none_or(|next| next.is_whitespace() || matches!(next, ',' | ')' | ';' | '!'));
+    let is_expression = matches!(node, Node::Binary(..) | Node::Call { .. });
+    if !ends_segment || !is_expression || !node.has_token() {
+        return Ok(None);
+    }
+
+    let term = parser.eval(&node)?;
+    let css = match term.value {
+        Value::Number(quantity) => quantity.to_string(),
+        Value::Expression(css, Precedence::Atom) => css,
+        Value::Expression(css, _) => format!("calc({})", css),
+        Value::Other(_) => unreachable!("calls and operators never yield a non-number"),
+    };
+    Ok(Some((css, parser.pos)))
+}
+
+#[derive(Debug)]
+enum Node {
+    Number(usize, usize),
+    Token(usize, usize),
+    /// A function other than `calc()`, `min()`, `max()`, `clamp()` or
+    /// `rem()`, such as `var(--x)`, taken as it is.
+    Opaque(usize, usize),
+    /// `-@token`, from the `-`.
+    Negated(usize, Box<Node>),
+    Binary(char, Box<Node>, Box<Node>),
+    Call {
+        name: String,
+        start: usize,
+        end: usize,
+        args: Vec<Node>,
+    },
+}
+
+impl Node {
+    fn span(&self) -> (usize, usize) {
+        match self {
+            Node::Number(start, end) | Node::Token(start, end) | Node::Opaque(start, end) => {
+                (*start, *end)
+            }
+            Node::Negated(start, inner) => (*start, inner.span().1),
+            Node::Binary(_, left, right) => (left.span().0, right.span().1),
+            Node::Call { start, end, .. } => (*start, *end),
+        }
+    }
+
+    fn has_token(&self) -> bool {
+        match self {
+            Node::Token(..) | Node::Negated(..) => true,
+            Node::Number(..) | Node::Opaque(..) => false,
+            Node::Binary(_, left, right) => left.has_token() || right.has_token(),
+            Node::Call { name, args, .. } => name == "rem" || args.iter().any(Node::has_token),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    Atom,
+    Product,
+    Sum,
+}
+
+enum Value {
+    Number(Quantity),
+    /// What could not be folded, to go inside `calc()`.
+    Expression(String, Precedence),
+    /// A token that is not a number, such as a color.
+    Other(String),
+}
+
+struct Term {
+    value: Value,
+    /// The expression as written, for error messages.
+    source: String,
+}
+
+impl Term {
+    fn describe(&self) -> String {
+        match &self.value {
+            Value::Number(quantity) => format!("{} ({})", quantity.kind(), self.source),
+            Value::Expression(..) => format!("an expression ({})", self.source),
+            Value::Other(css) if Color::parse(css).is_ok() => format!("a color ({})", self.source),
+            Value::Other(css) => format!("'{}' ({})", css, self.source),
+        }
+    }
+
+    /// The term as an operand, parenthesized when it binds looser than
+    /// `max`.
+    fn operand(&self, max: Precedence) -> String {
+        match &self.value {
+            Value::Number(quantity) => quantity.to_string(),
+            Value::Expression(css, precedence) if *precedence > max => format!("({})", css),
+            Value::Expression(css, _) | Value::Other(css) => css.clone(),
+        }
+    }
+}
+
+struct Parser<'a> {
+    value: &'a str,
+    pos: usize,
+    slash_divides: bool,
+    operands: &'a dyn Operands,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.value[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let rest = &self.value[self.pos..];
+        let trimmed = rest.trim_start();
+        self.pos += rest.len() - trimmed.len();
+        rest.len() != trimmed.len()
+    }
+
+    /// `a + b - c`. `None` when no expression starts here.
+    fn sum(&mut self, nested: bool) -> Result<Option<Node>, String> {
+        let Some(mut left) = self.product(nested)? else {
+            return Ok(None);
+        };
+        loop {
+            let save = self.pos;
+            let op = match (self.skip_whitespace(), self.peek()) {
+                (true, Some(op @ ('+' | '-'))) => op,
+                _ => {
+                    self.pos = save;
+                    break;
+                }
+            };
+            self.pos += 1;
+            let right = match self.skip_whitespace() {
+                true => self.product(nested)?,
+                false => None,
+            };
+            match right {
+                Some(right) => left = Node::Binary(op, Box::new(left), Box::new(right)),
+                None => {
+                    self.pos = save;
+                    break;
+                }
+            }
+        }
+        Ok(Some(left))
+    }
+
+    /// `a * b / c`.
+    fn product(&mut self, nested: bool) -> Result<Option<Node>, String> {
+        let Some(mut left) = self.factor()? else {
+            return Ok(None);
+        };
+        loop {
+            let save = self.pos;
+            self.skip_whitespace();
+            let op = match self.peek() {
+                Some('*') => '*',
+                Some('/') if nested || self.slash_divides => '/',
+                _ => {
+                    self.pos = save;
+                    break;
+                }
+            };
+            self.pos += 1;
+            self.skip_whitespace();
+            match self.factor()? {
+                Some(right) => left = Node::Binary(op, Box::new(left), Box::new(right)),
+                None => {
+                    self.pos = save;
+                    break;
+                }
+            }
+        }
+        Ok(Some(left))
+    }
+
+    /// A number, a token, `-@token`, `(...)` or a function call.
+    fn factor(&mut self) -> Result<Option<Node>, String> {
+        let start = self.pos;
+        let rest = &self.value[start..];
+        let mut chars = rest.chars();
+        let (first, second) = (chars.next(), chars.next());
+        match first {
+            Some('(') => {
+                self.pos += 1;
+                self.skip_whitespace();
+                let inner = self.sum(true)?;
+                self.skip_whitespace();
+                if inner.is_none() || self.peek() != Some(')') {
+                    return Ok(None);
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Some('@') => self.token(),
+            Some('-') if second == Some('@') => {
+                self.pos += 1;
+                Ok(self
+                    .token()?
+                    .map(|token| Node::Negated(start, Box::new(token))))
+            }
+            Some(c)
+                if c.is_ascii_digit()
+                    || (matches!(c, '.' | '-' | '+')
+                        && second.is_some_and(|c| c.is_ascii_digit() || c == '.')) =>
+            {
+                let end = rest
+                    .char_indices()
+                    .skip(1)
+                    .find(|&(_, c)| !(c.is_ascii_alphanumeric() || c == '.' || c == '%'))
+                    .map_or(rest.len(), |(i, _)| i);
+                if Quantity::parse(&rest[..end]).is_none() {
+                    return Ok(None);
+                }
+                self.pos += end;
+                Ok(Some(Node::Number(start, self.pos)))
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '-' => self.call(),
+            _ => Ok(None),
+        }
+    }
+
+    fn token(&mut self) -> Result<Option<Node>, String> {
+        let start = self.pos;
+        let Some(end) = self.operands.token_end(self.value, start)? else {
+            return Ok(None);
+        };
+        self.pos = end;
+        Ok(Some(Node::Token(start, end)))
+    }
+
+    /// `min(...)`, `max(...)`, `clamp(...)`, `calc(...)` and `rem(...)` are
+    /// parsed; other functions are kept whole.
+    fn call(&mut self) -> Result<Option<Node>, String> {
+        let start = self.pos;
+        let rest = &self.value[start..];
+        let name_len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(rest.len());
+        if !rest[name_len..].starts_with('(') {
+            return Ok(None);
+        }
+        let name = rest[..name_len].to_ascii_lowercase();
+        if !matches!(name.as_str(), "calc" | "min" | "max" | "clamp" | "rem") {
+            let mut depth = 0;
+            for (offset, c) in rest[name_len..].char_indices() {
+                match c {
+                    '(' => depth += 1,
+                    ')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            self.pos = start + name_len + offset + 1;
+                            return Ok(Some(Node::Opaque(start, self.pos)));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            return Ok(None);
+        }
+
+        self.pos += name_len + 1;
+        let mut args = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let Some(arg) = self.sum(true)? else {
+                return Ok(None);
+            };
+            args.push(arg);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(')') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(Node::Call {
+            name,
+            start,
+            end: self.pos,
+            args,
+        }))
+    }
+
+    fn eval(&self, node: &Node) -> Result<Term, String> {
+        let (start, end) = node.span();
+        let source = self.value[start..end].to_string();
+        let value = match node {
+            Node::Number(..) => Value::Number(Quantity::parse(&source).expect("parsed number")),
+            Node::Token(..) | Node::Opaque(..) => {
+                let css = self.operands.resolve(self.value, start, end)?;
+                let css = css.trim();
+                let lower = css.to_ascii_lowercase();
+                match Quantity::parse(css) {
+                    Some(quantity) => Value::Number(quantity),
+                    None if ["calc(", "var(", "min(", "max(", "clamp(", "env("]
+                        .iter()
+                        .any(|f| lower.starts_with(f)) =>
+                    {
+                        Value::Expression(css.to_string(), Precedence::Atom)
+                    }
+                    None => Value::Other(css.to_string()),
+                }
+            }
+            Node::Negated(_, inner) => {
+                let term = self.eval(inner)?;
+                match term.value {
+                    Value::Number(quantity) => Value::Number(Quantity {
+                        value: if quantity.value == 0.0 {
+                            0.0
+                        } else {
+                            -quantity.value
+                        },
+                        unit: quantity.unit,
+                    }),
+                    Value::Expression(..) => Value::Expression(
+                        format!("-1 * {}", term.operand(Precedence::Atom)),
+                        Precedence::Product,
+                    ),
+                    Value::Other(css) => {
+                        return Err(self.operands.error(
+                            start,
+                            format!(
+                                "-{} cannot be negated: '{}' is not a number or length",
+                                term.source, css
+                            ),
+                        ));
+                    }
+                }
+            }
+            Node::Binary(op, left, right) => {
+                let (left, right) = (self.eval(left)?, self.eval(right)?);
+                self.binary(*op, left, right, start)?
+            }
+            Node::Call { name, args, .. } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call_value(name, args, start)?
+            }
+        };
+        Ok(Term { value, source })
+    }
+
+    fn binary(&self, op: char, left: Term, right: Term, at: usize) -> Result<Value, String> {
+        let fail = || {
+            let (left, right) = (left.describe(), right.describe());
+            let message = match op {
+                '+' => format!("cannot add {} to {}", right, left),
+                '-' => format!("cannot subtract {} from {}", right, left),
+                '*' => format!("cannot multiply {} by {}", left, right),
+                _ => format!("cannot divide {} by {}", left, right),
+            };
+            Err(self.operands.error(at, message))
+        };
+
+        match (&left.value, &right.value) {
+            (Value::Other(_), _) | (_, Value::Other(_)) => return fail(),
+            (Value::Number(a), Value::Number(b)) => {
+                let folded = match op {
+                    '+' | '-' if a.unit == b.unit => {
+                        let value = if op == '+' {
+                            a.value + b.value
+                        } else {
+                            a.value - b.value
+                        };
+                        Some(Quantity {
+                            value,
+                            unit: a.unit.clone(),
+                        })
+                    }
+                    '+' | '-' if (a.is_length() && b.is_length()) || a.kind() == b.kind() => None,
+                    '+' | '-' => return fail(),
+                    '*' if a.unit.is_empty() || b.unit.is_empty() => Some(Quantity {
+                        value: a.value * b.value,
+                        unit: format!("{}{}", a.unit, b.unit),
+                    }),
+                    '*' => return fail(),
+                    _ if b.value == 0.0 => {
+                        return Err(self.operands.error(
+                            at,
+                            format!("division by zero in {} / {}", left.source, right.source),
+                        ));
+                    }
+                    _ if b.unit.is_empty() => Some(Quantity {
+                        value: a.value / b.value,
+                        unit: a.unit.clone(),
+                    }),
+                    _ if a.unit == b.unit => Some(Quantity {
+                        value: a.value / b.value,
+                        unit: String::new(),
+                    }),
+                    _ => return fail(),
+                };
+                if let Some(quantity) = folded {
+                    return Ok(Value::Number(quantity));
+                }
+            }
+            (_, Value::Number(b)) if op == '/' && b.value == 0.0 => {
+                return Err(self.operands.error(
+                    at,
+                    format!("division by zero in {} / {}", left.source, right.source),
+                ));
+            }
+            _ => {}
+        }
+
+        let (css, precedence) = match op {
+            '+' | '-' => {
+                let right_max = if op == '-' {
+                    Precedence::Product
+                } else {
+                    Precedence::Sum
+                };
+                (
+                    format!(
+                        "{} {} {}",
+                        left.operand(Precedence::Sum),
+                        op,
+                        right.operand(right_max)
+                    ),
+                    Precedence::Sum,
+                )
+            }
+            '*' => (
+                format!(
+                    "{} * {}",
+                    left.operand(Precedence::Product),
+                    right.operand(Precedence::Product)
+                ),
+                Precedence::Product,
+            ),
+            _ => (
+                format!(
+                    "{} / {}",
+                    left.operand(Precedence::Product),
+                    right.operand(Precedence::Atom)
+                ),
+                Precedence::Product,
+            ),
+        };
+        Ok(Value::Expression(css, precedence))
+    }
+
+    /// `calc()`, `min()`, `max()` and `clamp()`, folded when every argument
+    /// is a number in the same unit.
+    fn call_value(&self, name: &str, mut args: Vec<Term>, at: usize) -> Result<Value, String> {
+        if let Some(other) = args.iter().find(|arg| matches!(arg.value, Value::Other(_))) {
+            return Err(self.operands.error(
+                at,
+                format!(
+                    "{}() takes numbers and lengths, not {}",
+                    name,
+                    other.describe()
+                ),
+            ));
+        }
+        match (name, args.len()) {
+            ("calc", 1) => return Ok(args.remove(0).value),
+            ("calc", _) => {
+                return Err(self
+                    .operands
+                    .error(at, "calc() takes one expression".to_string()));
+            }
+            ("rem", 1) => {
+                return match &args[0].value {
+                    Value::Number(q) if q.unit == "px" => Ok(Value::Number(Quantity {
+                        value: q.value,
+                        unit: PX_AS_REM.to_string(),
+                    })),
+                    Value::Number(q)
+                        if q.unit == "rem" || q.unit == PX_AS_REM || q.value == 0.0 =>
+                    {
+                        Ok(args.remove(0).value)
+                    }
+                    _ => Err(self.operands.error(
+                        at,
+                        format!("rem() takes a px length, not {}", args[0].describe()),
+                    )),
+                };
+            }
+            ("rem", _) => {
+                return Err(self
+                    .operands
+                    .error(at, "rem() takes one px length".to_string()));
+            }
+            ("clamp", 3) => {}
+            ("clamp", _) => {
+                return Err(self.operands.error(
+                    at,
+                    "clamp() takes a minimum, a preferred value and a maximum".to_string(),
+                ));
+            }
+            _ => {}
+        }
+
+        let numbers: Vec<&Quantity> = args
+            .iter()
+            .filter_map(|arg| match &arg.value {
+                Value::Number(quantity) => Some(quantity),
+                _ => None,
+            })
+            .collect();
+        if numbers.len() == args.len() && numbers.iter().all(|q| q.unit == numbers[0].unit) {
+            let values: Vec<f64> = numbers.iter().map(|q| q.value).collect();
+            let value = match name {
+                "min" => values.iter().copied().fold(f64::INFINITY, f64::min),
+                "max" => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                _ => values[1].min(values[2]).max(values[0]),
+            };
+            return Ok(Value::Number(Quantity {
+                value,
+                unit: numbers[0].unit.clone(),
+            }));
+        }
+
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| arg.operand(Precedence::Sum))
+            .collect();
+        Ok(Value::Expression(
+            format!("{}({})", name, args.join(", ")),
+            Precedence::Atom,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `@name` tokens from a fixed table, without a theme.
+    struct Table;
+
+    impl Operands for Table {
+        fn token_end(&self, value: &str, at: usize) -> Result<Option<usize>, String> {
+            let len = value[at + 1..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+                .unwrap_or(value.len() - at - 1);
+            Ok((len > 0).then_some(at + 1 + len))
+        }
+
+        fn resolve(&self, value: &str, at: usize, end: usize) -> Result<String, String> {
+            Ok(match &value[at..end] {
+                "@4" => "1rem",
+                "@2" => "0.5rem",
+                "@lg" => "1.125rem",
+                "@px" => "1px",
+                "@blue" => "#3b82f6",
+                "@x" => "var(--x)",
+                other => other,
+            }
+            .to_string())
+        }
+
+        fn error(&self, at: usize, message: String) -> String {
+            format!("{}: {}", at, message)
+        }
+    }
+
+    fn fold_value(value: &str) -> Result<String, String> {
+        fold(value, true, &Table)
+    }
+
+    #[test]
+    fn folds_matching_units_and_falls_back_to_calc() {
+        assert_eq!(fold_value("@4 * 2").unwrap(), "2rem");
+        assert_eq!(fold_value("@4 + @2").unwrap(), "1.5rem");
+        assert_eq!(fold_value("@lg / 2").unwrap(), "0.5625rem");
+        assert_eq!(fold_value("(@4 + @2) * 2 @2").unwrap(), "3rem @2");
+        assert_eq!(fold_value("@4 - -@2").unwrap(), "1.5rem");
+        assert_eq!(fold_value("@4 / @2").unwrap(), "2");
+        assert_eq!(fold_value("max(@2, @4)").unwrap(), "1rem");
+        assert_eq!(fold_value("clamp(@2, 5rem, @4)").unwrap(), "1rem");
+        assert_eq!(fold_value("calc(@4 * 3)").unwrap(), "3rem");
+
+        assert_eq!(fold_value("@4 + @px").unwrap(), "calc(1rem + 1px)");
+        assert_eq!(fold_value("max(@2, 10px)").unwrap(), "max(0.5rem, 10px)");
+        assert_eq!(fold_value("calc(100% - @4)").unwrap(), "calc(100% - 1rem)");
+        assert_eq!(
+            fold_value("(@4 + @px) * 2").unwrap(),
+            "calc((1rem + 1px) * 2)"
+        );
+        assert_eq!(
+            fold_value("@4 - (@2 + @px)").unwrap(),
+            "calc(1rem - (0.5rem + 1px))"
+        );
+        assert_eq!(fold_value("rem(24px) * 2").unwrap(), "rem(48px)");
+        assert_eq!(fold_value("@x * 2").unwrap(), "calc(var(--x) * 2)");
+        assert_eq!(fold_value("-@x * 2").unwrap(), "calc(-1 * var(--x) * 2)");
+
+        // Not expressions: left for the resolver.
+        assert_eq!(fold_value("@4 @2").unwrap(), "@4 @2");
+        assert_eq!(fold_value("-@4").unwrap(), "-@4");
+        assert_eq!(fold_value("calc(100% - 1px)").unwrap(), "calc(100% - 1px)");
+        assert_eq!(fold_value("\"@4 * 2\"").unwrap(), "\"@4 * 2\"");
+        assert_eq!(fold(" @lg / 1.5", false, &Table).unwrap(), " @lg / 1.5");
+    }
+
+    #[test]
+    fn rejects_mismatched_operands() {
+        assert_eq!(
+            fold_value("@4 + @blue").unwrap_err(),
+            "0: cannot add a color (@blue) to a length (@4)"
+        );
+        assert_eq!(
+            fold_value("@4 * @2").unwrap_err(),
+            "0: cannot multiply a length (@4) by a length (@2)"
+        );
+        assert_eq!(
+            fold_value("@4 + 2").unwrap_err(),
+            "0: cannot add a number (2) to a length (@4)"
+        );
+        assert_eq!(
+            fold_value("@4 / @px").unwrap_err(),
+            "0: cannot divide a length (@4) by a length (@px)"
+        );
+        assert_eq!(
+            fold_value("@4 / 0").unwrap_err(),
+            "0: division by zero in @4 / 0"
+        );
+        assert_eq!(
+            fold_value("max(@2, @blue)").unwrap_err(),
+            "0: max() takes numbers and lengths, not a color (@blue)"
+        );
+    }
+}
@@ -1,64 +1,40 @@
-use crate::ast::{Declaration, MediaBlock, Rule, Stylesheet};
+use crate::ast::{Declaration, MediaBlock, PresetUse, Rule, Stylesheet};
 use crate::error::Span;
+use crate::suggest;
 use std::collections::HashMap;
 
-enum PresetScope {
-    Root,
-    Dark,
-    Media {
-        query: &'static str,
-        selector: &'static str,
-    },
-}
-
-struct MediaPresetSpec {
-    name: String,
-    query: &'static str,
-    selector: &'static str,
-}
+type RuleBody = (Vec<Declaration>, Vec<MediaBlock>, Vec<Rule>);
 
 pub fn parse(input: &str) -> Result<Stylesheet, String> {
     let mut rules = Vec::new();
 
-    // Extract preset directives and strip them from the input
-    let mut base_presets: Vec<String> = Vec::new();
-    let mut dark_presets: Vec<String> = Vec::new();
-    let mut media_presets: Vec<MediaPresetSpec> = Vec::new();
+    // Extract preset directives and strip them from the input; the resolver
+    // expands them from the theme's presets.
+    let mut presets: Vec<PresetUse> = Vec::new();
     let mut variables: HashMap<String, String> = HashMap::new();
     let mut cleaned = String::new();
     let mut blocks: HashMap<String, Vec<Declaration>> = HashMap::new();
-    let mut lines = input.lines().peekable();
-    while let Some(line) = lines.next() {
+    let mut lines = input.lines().enumerate().peekable();
+    while let Some((line_index, line)) = lines.next() {
         let trimmed = line.trim();
-        if trimmed.starts_with('%') {
-            let content = trimmed.trim_start_matches('%').trim();
-            if !content.is_empty() {
-                let mut dark_flag = false;
-                for token in content.split_whitespace() {
-                    if token == "dark" {
-                        dark_flag = true;
-                        continue;
-                    }
-
-                    let scope = if dark_flag {
-                        dark_flag = false;
-                        PresetScope::Dark
-                    } else {
-                        preset_scope_from_name(token)
-                    };
-
-                    match scope {
-                        PresetScope::Root => base_presets.push(token.to_string()),
-                        PresetScope::Dark => dark_presets.push(token.to_string()),
-                        PresetScope::Media { query, selector } => {
-                            media_presets.push(MediaPresetSpec {
-                                name: token.to_string(),
-                                query,
-                                selector,
-                            })
-                        }
-                    }
+        if let Some(content) = trimmed.strip_prefix('%') {
+            let mut dark_flag = false;
+            let indent = line.len() - line.trim_start().len() + 1;
+            for token in content.split_whitespace() {
+                // `token` is a slice of `content`, so its address gives the column.
+                let column = indent + (token.as_ptr() as usize - content.as_ptr() as usize) + 1;
+                if token == "dark" {
+                    dark_flag = true;
+                    continue;
                 }
+                presets.push(PresetUse {
+                    name: token.to_string(),
+                    dark: std::mem::take(&mut dark_flag),
+                    span: Span {
+                        line: line_index + 1,
+                        column,
+                    },
+                });
             }
             continue;
         }
@@ -76,7 +52,7 @@ pub fn parse(input: &str) -> Result<Stylesheet, String> {
                 .trim()
                 .to_string();
             let mut body = String::new();
-            while let Some(inner) = lines.next() {
+            for (_, inner) in lines.by_ref() {
                 let inner_trim = inner.trim();
                 if inner_trim == "}" {
                     break;
@@ -136,55 +112,39 @@ pub fn parse(input: &str) -> Result<Stylesheet, String> {
         let body = cleaned[body_start..body_end].to_string();
 
         let selector_terms = split_selector_terms(&selector);
-        let (declarations, media, mut nested_rules) =
-            parse_rule_body(&body, &selector, &selector_terms, &blocks)?;
+        let (declarations, media, children) = parse_rule_body(&body, &selector_terms, &blocks)?;
 
         rules.push(Rule {
             selector,
             declarations,
             media,
+            children,
+            nested_header: None,
         });
-        rules.append(&mut nested_rules);
     }
 
-    let mut base_list = base_presets;
-    if base_list.is_empty() {
-        base_list.push("base-16".to_string());
-    }
-    let mut insert_at = 0;
-    if let Some(root_rule) = build_preset_rule(&base_list) {
-        rules.insert(insert_at, root_rule);
-        insert_at += 1;
-    }
-    if let Some(dark_rule) = build_dark_preset_rule(&dark_presets) {
-        rules.insert(insert_at, dark_rule);
-        insert_at += 1;
-    }
-    for media_rule in build_media_preset_rules(&media_presets) {
-        rules.insert(insert_at, media_rule);
-        insert_at += 1;
-    }
-
-    Ok(Stylesheet { rules, variables })
+    Ok(Stylesheet {
+        rules,
+        variables,
+        presets,
+    })
 }
 
 fn parse_rule_body(
     body: &str,
-    selector: &str,
     selector_terms: &[String],
     blocks: &HashMap<String, Vec<Declaration>>,
-) -> Result<(Vec<Declaration>, Vec<MediaBlock>, Vec<Rule>), String> {
+) -> Result<RuleBody, String> {
     let normalized_body = normalize_braces(body);
     let mut reader = LineReader::new(&normalized_body);
-    parse_rule_body_from_reader(selector, selector_terms, &mut reader, blocks)
+    parse_rule_body_from_reader(selector_terms, &mut reader, blocks)
 }
 
 fn parse_rule_body_from_reader<'a>(
-    selector: &str,
     selector_terms: &[String],
     reader: &mut LineReader<'a>,
     blocks: &HashMap<String, Vec<Declaration>>,
-) -> Result<(Vec<Declaration>, Vec<MediaBlock>, Vec<Rule>), String> {
+) -> Result<RuleBody, String> {
     let mut declarations = Vec::new();
     let mut media = Vec::new();
     let mut nested_rules = Vec::new();
@@ -215,7 +175,7 @@ fn parse_rule_body_from_reader<'a>(
             if fragment.ends_with('{') {
                 let header = fragment.trim_end_matches('{').trim();
                 if is_media_header(header) {
-                    let query = header.to_string();
+                    let query = header.trim_start_matches("@media").trim().to_string();
                     let mut inner_decls = Vec::new();
                     while let Some((_inner_raw, _inner_line)) = reader.next_line() {
                         let inner = _inner_raw.trim();
@@ -238,6 +198,7 @@ fn parse_rule_body_from_reader<'a>(
                                 property,
                                 value,
                                 span: Span::dummy(),
+                                themed: false,
                             });
                         } else {
                             return Err(format!("Invalid declaration line: '{}'", inner));
@@ -250,23 +211,17 @@ fn parse_rule_body_from_reader<'a>(
                     });
                 } else if is_property_block_header(header) {
                     let (block_decls, mut nested_from_block) =
-                        parse_property_block(header, reader, selector, selector_terms, blocks)?;
+                        parse_property_block(header, reader, selector_terms, blocks)?;
                     declarations.extend(block_decls);
                     nested_rules.append(&mut nested_from_block);
                 } else {
                     let block_body = collect_block_body(reader)?;
-                    let nested_selectors = combine_selectors(selector_terms, header);
-                    for nested_selector in nested_selectors {
-                        let nested_terms = split_selector_terms(&nested_selector);
-                        let (inner_decls, inner_media, mut inner_nested) =
-                            parse_rule_body(&block_body, &nested_selector, &nested_terms, blocks)?;
-                        nested_rules.push(Rule {
-                            selector: nested_selector.clone(),
-                            declarations: inner_decls,
-                            media: inner_media,
-                        });
-                        nested_rules.append(&mut inner_nested);
-                    }
+                    nested_rules.push(parse_nested_rule(
+                        header,
+                        selector_terms,
+                        &block_body,
+                        blocks,
+                    )?);
                 }
                 continue;
             }
@@ -288,16 +243,23 @@ fn parse_rule_body_from_reader<'a>(
                 let value = value_trim.to_string();
                 if property_trim == "apply" && value.starts_with('$') {
                     let key = value.trim_start_matches('$').trim();
-                    if let Some(block_decls) = blocks.get(key) {
-                        declarations.extend(block_decls.iter().cloned());
-                        continue;
-                    }
+                    let block_decls = blocks.get(key).ok_or_else(|| {
+                        format!(
+                            "{}: unknown mixin '${}'{}",
+                            span,
+                            key,
+                            suggest::did_you_mean(key, blocks.keys().map(String::as_str), "$")
+                        )
+                    })?;
+                    declarations.extend(block_decls.iter().cloned());
+                    continue;
                 }
 
                 declarations.push(Declaration {
                     property: property_trim.to_string(),
                     value,
                     span,
+                    themed: false,
                 });
             } else {
                 return Err(format!("Invalid declaration line: '{}'", fragment));
@@ -310,13 +272,15 @@ fn parse_rule_body_from_reader<'a>(
 
 fn is_media_header(header: &str) -> bool {
     let trimmed = header.trim();
-    trimmed.starts_with("screen(") || trimmed == "dark" || trimmed == "light"
+    trimmed.starts_with("screen(")
+        || trimmed.starts_with("@media ")
+        || trimmed == "dark"
+        || trimmed == "light"
 }
 
 fn parse_property_block<'a>(
     prefix: &str,
     reader: &mut LineReader<'a>,
-    parent_selector: &str,
     parent_terms: &[String],
     blocks: &HashMap<String, Vec<Declaration>>,
 ) -> Result<(Vec<Declaration>, Vec<Rule>), String> {
@@ -350,29 +314,18 @@ fn parse_property_block<'a>(
                 let header = fragment.trim_end_matches('{').trim();
                 if is_selector_header(header) {
                     let block_body = collect_block_body(reader)?;
-                    let nested_selectors = combine_selectors(parent_terms, header);
-                    for nested_selector in nested_selectors {
-                        let nested_terms = split_selector_terms(&nested_selector);
-                        let (inner_decls, inner_media, mut inner_nested) =
-                            parse_rule_body(&block_body, &nested_selector, &nested_terms, blocks)?;
-                        nested_rules.push(Rule {
-                            selector: nested_selector.clone(),
-                            declarations: inner_decls,
-                            media: inner_media,
-                        });
-                        nested_rules.append(&mut inner_nested);
-                    }
+                    nested_rules.push(parse_nested_rule(
+                        header,
+                        parent_terms,
+                        &block_body,
+                        blocks,
+                    )?);
                     continue;
                 }
 
                 let nested_prefix = format!("{}.{}", prefix, header);
-                let (nested_block, mut nested_from_block) = parse_property_block(
-                    &nested_prefix,
-                    reader,
-                    parent_selector,
-                    parent_terms,
-                    blocks,
-                )?;
+                let (nested_block, mut nested_from_block) =
+                    parse_property_block(&nested_prefix, reader, parent_terms, blocks)?;
                 decls.extend(nested_block);
                 nested_rules.append(&mut nested_from_block);
                 continue;
@@ -386,6 +339,7 @@ fn parse_property_block<'a>(
                     property,
                     value,
                     span: Span::dummy(),
+                    themed: false,
                 });
             } else {
                 return Err(format!("Invalid declaration line: '{}'", fragment));
@@ -396,6 +350,26 @@ fn parse_property_block<'a>(
     Ok((decls, nested_rules))
 }
 
+/// Parse a nested selector block into a child rule. The child keeps the header
+/// as written so native nesting can re-emit it, while `selector` holds the
+/// fully combined selector list used when flattening.
+fn parse_nested_rule(
+    header: &str,
+    parent_terms: &[String],
+    block_body: &str,
+    blocks: &HashMap<String, Vec<Declaration>>,
+) -> Result<Rule, String> {
+    let nested_terms = combine_selectors(parent_terms, header);
+    let (declarations, media, children) = parse_rule_body(block_body, &nested_terms, blocks)?;
+    Ok(Rule {
+        selector: nested_terms.join(", "),
+        declarations,
+        media,
+        children,
+        nested_header: Some(header.to_string()),
+    })
+}
+
 fn collect_block_body<'a>(reader: &mut LineReader<'a>) -> Result<String, String> {
     let mut depth = 1;
     let mut body = String::new();
@@ -540,6 +514,7 @@ fn parse_declarations(body: &str) -> Result<Vec<Declaration>, String> {
                 property,
                 value,
                 span: Span::dummy(),
+                themed: false,
             });
         } else {
             return Err(format!("Invalid declaration line: '{}'", line));
@@ -547,183 +522,3 @@ fn parse_declarations(body: &str) -> Result<Vec<Declaration>, String> {
     }
     Ok(decls)
 }
-
-fn build_preset_rule(presets: &[String]) -> Option<Rule> {
-    if presets.iter().any(|p| p == "no-base") {
-        return None;
-    }
-
-    let mut declarations: Vec<Declaration> = Vec::new();
-    for preset in presets {
-        merge_declarations(&mut declarations, preset_declaration_entries(preset));
-    }
-
-    if declarations.is_empty() {
-        return None;
-    }
-
-    Some(Rule {
-        selector: ":root".to_string(),
-        declarations,
-        media: Vec::new(),
-    })
-}
-
-fn build_dark_preset_rule(presets: &[String]) -> Option<Rule> {
-    if presets.is_empty() {
-        return None;
-    }
-
-    let mut declarations: Vec<Declaration> = Vec::new();
-    for preset in presets {
-        merge_declarations(&mut declarations, preset_declaration_entries(preset));
-    }
-
-    if declarations.is_empty() {
-        return None;
-    }
-
-    Some(Rule {
-        selector: ":root".to_string(),
-        declarations: Vec::new(),
-        media: vec![MediaBlock {
-            query: "(prefers-color-scheme: dark)".to_string(),
-            declarations,
-        }],
-    })
-}
-
-fn build_media_preset_rules(media_presets: &[MediaPresetSpec]) -> Vec<Rule> {
-    let mut rules = Vec::new();
-
-    for preset in media_presets {
-        let entries = preset_declaration_entries(&preset.name);
-        if entries.is_empty() {
-            continue;
-        }
-
-        let mut declarations: Vec<Declaration> = Vec::new();
-        merge_declarations(&mut declarations, entries);
-
-        rules.push(Rule {
-            selector: preset.selector.to_string(),
-            declarations: Vec::new(),
-            media: vec![MediaBlock {
-                query: preset.query.to_string(),
-                declarations,
-            }],
-        });
-    }
-
-    rules
-}
-
-fn merge_declarations(target: &mut Vec<Declaration>, entries: Vec<(String, String)>) {
-    for (prop, val) in entries {
-        if let Some(existing) = target.iter_mut().find(|d| d.property == prop) {
-            existing.value = val;
-        } else {
-            target.push(Declaration {
-                property: prop,
-                value: val,
-                span: Span::dummy(),
-            });
-        }
-    }
-}
-
-fn preset_declaration_entries(name: &str) -> Vec<(String, String)> {
-    match name {
-        "base-14" => preset_pairs(&[("font-size", "14px")]),
-        "base-16" => preset_pairs(&[
-            ("font-size", "16px"),
-            ("line-height", "1.5"),
-            ("font-family", "sans-serif"),
-        ]),
-        "base-18" => preset_pairs(&[
-            ("font-size", "18px"),
-            ("line-height", "1.55"),
-            ("font-family", "sans-serif"),
-        ]),
-        "spacious" => preset_pairs(&[
-            ("font-size", "16px"),
-            ("line-height", "1.7"),
-            ("letter-spacing", "0.01em"),
-        ]),
-        "reading" => preset_pairs(&[
-            ("line-height", "1.75"),
-            ("max-width", "65ch"),
-            ("font-weight", "400"),
-        ]),
-        "compact" => preset_pairs(&[("line-height", "1.4"), ("letter-spacing", "0")]),
-        "system" => preset_pairs(&[(
-            "font-family",
-            "-apple-system, BlinkMacSystemFont, \"Segoe UI\", Roboto, Helvetica, Arial, sans-serif",
-        )]),
-        "fluid-type" => preset_pairs(&[("font-size", "clamp(14px, 2.2vw, 18px)")]),
-        "light-ui" => preset_pairs(&[
-            ("--background", "#ffffff"),
-            ("--foreground", "#111111"),
-            ("--muted", "#f3f3f3"),
-            ("--border", "#e5e5e5"),
-        ]),
-        "dark-ui" => preset_pairs(&[
-            ("--background", "#0f0f0f"),
-            ("--foreground", "#fafafa"),
-            ("--muted", "#1b1b1b"),
-            ("--border", "#2a2a2a"),
-        ]),
-        "smooth" => preset_pairs(&[
-            ("--ease", "cubic-bezier(0.4, 0.0, 0.2, 1)"),
-            ("--duration", "150ms"),
-        ]),
-        "snappy" => preset_pairs(&[
-            ("--ease", "cubic-bezier(0.2, 0.0, 0.0, 1)"),
-            ("--duration", "100ms"),
-        ]),
-        "reduced-motion" => preset_pairs(&[
-            ("animation", "none !important"),
-            ("transition", "none !important"),
-        ]),
-        "code" => preset_pairs(&[
-            (
-                "font-family",
-                "ui-monospace, SFMono-Regular, Menlo, Monaco, Consolas, \"Liberation Mono\", \"Courier New\", monospace",
-            ),
-            ("font-size", "14px"),
-            ("line-height", "1.5"),
-        ]),
-        "accessible-lg" => preset_pairs(&[
-            ("font-size", "20px"),
-            ("line-height", "1.7"),
-            ("letter-spacing", "0.01em"),
-        ]),
-        "print" => preset_pairs(&[
-            ("font-size", "12px"),
-            ("line-height", "1.4"),
-            ("color", "#000"),
-            ("background-color", "#fff"),
-        ]),
-        _ => Vec::new(),
-    }
-}
-
-fn preset_pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
-    items
-        .iter()
-        .map(|(p, v)| (p.to_string(), v.to_string()))
-        .collect()
-}
-
-fn preset_scope_from_name(name: &str) -> PresetScope {
-    if name == "reduced-motion" {
-        return PresetScope::Media {
-            query: "(prefers-reduced-motion: reduce)",
-            selector: "*",
-        };
-    }
-    if name.starts_with("dark-") {
-        return PresetScope::Dark;
-    }
-    PresetScope::Root
-}
@@ -0,0 +1,414 @@
+use crate::ast::{Declaration, MediaBlock, PresetUse, Rule};
+use crate::error::Span;
+use crate::suggest;
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+
+/// Applied to `:root` when a stylesheet names no root-scoped preset and does
+/// not opt out with `%no-base`.
+pub const DEFAULT_PRESET: &str = "base-16";
+pub const NO_BASE: &str = "no-base";
+
+/// Where a preset's declarations are emitted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresetScope {
+    /// Merged into a single `:root` rule.
+    Root,
+    /// Merged into `:root` inside `@media (prefers-color-scheme: dark)`.
+    Dark,
+    /// Its own rule for `selector`, optionally wrapped in a media query.
+    Custom {
+        media: Option<String>,
+        selector: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Preset {
+    pub scope: PresetScope,
+    /// Presets whose declarations come first, in order.
+    pub extends: Vec<String>,
+    /// Declarations in file order; values may reference tokens (`@prose`).
+    pub declarations: Vec<(String, String)>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPreset {
+    #[serde(default)]
+    scope: Option<RawScope>,
+    #[serde(default)]
+    extends: RawExtends,
+    #[serde(default, deserialize_with = "ordered_declarations")]
+    declarations: Vec<(String, String)>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawScope {
+    Named(String),
+    Custom {
+        media: Option<String>,
+        selector: Option<String>,
+    },
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(untagged)]
+enum RawExtends {
+    #[default]
+    None,
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Load `presets.json`: an object of preset name → definition.
+pub fn load(path: &str) -> Result<HashMap<String, Preset>, String> {
+    let data = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    parse(&data, path)
+}
+
+fn parse(data: &str, path: &str) -> Result<HashMap<String, Preset>, String> {
+    let presets: HashMap<String, Preset> = parse_layer(data, path)?
+        .into_iter()
+        .filter_map(|(name, preset)| preset.map(|preset| (name, preset)))
+        .collect();
+    let errors = validate(&presets);
+    if !errors.is_empty() {
+        return Err(format!(
+            "Invalid presets in {}:\n  {}",
+            path,
+            errors.join("\n  ")
+        ));
+    }
+    Ok(presets)
+}
+
+/// Parse one `presets.json` of a theme layer. `null` entries (`None`) remove
+/// a preset inherited from a parent theme.
+pub fn parse_layer(data: &str, path: &str) -> Result<Vec<(String, Option<Preset>)>, String> {
+    let raw: HashMap<String, Option<RawPreset>> =
+        serde_json::from_str(data).map_err(|e| format!("Invalid JSON in {}: {}", path, e))?;
+
+    let mut errors = Vec::new();
+    let mut presets = Vec::new();
+    for (name, preset) in raw {
+        let Some(preset) = preset else {
+            presets.push((name, None));
+            continue;
+        };
+        let scope = match preset.scope {
+            None => PresetScope::Root,
+            Some(RawScope::Named(scope)) => match scope.as_str() {
+                "root" => PresetScope::Root,
+                "dark" => PresetScope::Dark,
+                other => {
+                    errors.push(format!(
+                        "{}.scope: unknown scope '{}' (expected \"root\", \"dark\" or {{ \"media\", \"selector\" }})",
+                        name, other
+                    ));
+                    continue;
+                }
+            },
+            Some(RawScope::Custom { media, selector }) => PresetScope::Custom {
+                media,
+                selector: selector.unwrap_or_else(|| ":root".to_string()),
+            },
+        };
+        let extends = match preset.extends {
+            RawExtends::None => Vec::new(),
+            RawExtends::One(name) => vec![name],
+            RawExtends::Many(names) => names,
+        };
+        presets.push((
+            name,
+            Some(Preset {
+                scope,
+                extends,
+                declarations: preset.declarations,
+            }),
+        ));
+    }
+
+    if !errors.is_empty() {
+        errors.sort();
+        return Err(format!(
+            "Invalid presets in {}:\n  {}",
+            path,
+            errors.join("\n  ")
+        ));
+    }
+    Ok(presets)
+}
+
+/// Check every `extends` chain, as `name: problem` messages.
+pub fn validate(presets: &HashMap<String, Preset>) -> Vec<String> {
+    let mut names: Vec<&String> = presets.keys().collect();
+    names.sort();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            flatten(name, presets, &mut Vec::new())
+                .err()
+                .map(|e| format!("{}: {}", name, e))
+        })
+        .collect()
+}
+
+/// Declarations of `name` with everything it extends applied first; later
+/// entries replace earlier ones for the same property.
+fn flatten(
+    name: &str,
+    presets: &HashMap<String, Preset>,
+    stack: &mut Vec<String>,
+) -> Result<Vec<(String, String)>, String> {
+    if stack.iter().any(|seen| seen == name) {
+        stack.push(name.to_string());
+        return Err(format!("extends cycle {}", stack.join(" → ")));
+    }
+    let preset = presets.get(name).ok_or_else(|| {
+        format!(
+            "extends unknown preset '{}'{}",
+            name,
+            suggest::did_you_mean(name, presets.keys().map(String::as_str), "")
+        )
+    })?;
+
+    stack.push(name.to_string());
+    let mut declarations = Vec::new();
+    for parent in &preset.extends {
+        merge(&mut declarations, flatten(parent, presets, stack)?);
+    }
+    stack.pop();
+    merge(&mut declarations, preset.declarations.clone());
+    Ok(declarations)
+}
+
+fn merge(target: &mut Vec<(String, String)>, entries: Vec<(String, String)>) {
+    for (prop, value) in entries {
+        match target.iter_mut().find(|(existing, _)| *existing == prop) {
+            Some(entry) => entry.1 = value,
+            None => target.push((prop, value)),
+        }
+    }
+}
+
+/// Build the rules for the `%preset` directives of a stylesheet. Root and
+/// dark presets each merge into one rule; custom scopes get a rule apiece.
+/// Preset declarations carry the directive's span so token errors point at it.
+pub fn expand(uses: &[PresetUse], presets: &HashMap<String, Preset>) -> Result<Vec<Rule>, String> {
+    let mut root: Vec<Declaration> = Vec::new();
+    let mut dark: Vec<Declaration> = Vec::new();
+    let mut custom: Vec<Rule> = Vec::new();
+    let mut root_named = false;
+
+    for used in uses {
+        if used.name == NO_BASE {
+            root_named = true;
+            continue;
+        }
+        let preset = presets
+            .get(&used.name)
+            .ok_or_else(|| unknown_preset(used, presets))?;
+        let declarations = flatten(&used.name, presets, &mut Vec::new())
+            .map_err(|e| format!("{}: preset '{}' {}", used.span, used.name, e))?;
+
+        let scope = if used.dark {
+            &PresetScope::Dark
+        } else {
+            &preset.scope
+        };
+        match scope {
+            PresetScope::Root => {
+                root_named = true;
+                merge_into(&mut root, declarations, used.span);
+            }
+            PresetScope::Dark => merge_into(&mut dark, declarations, used.span),
+            PresetScope::Custom { media, selector } => {
+                let mut decls = Vec::new();
+                merge_into(&mut decls, declarations, used.span);
+                custom.push(scoped_rule(selector, media.as_deref(), decls));
+            }
+        }
+    }
+
+    if !root_named && let Some(base) = presets.get(DEFAULT_PRESET) {
+        let declarations = flatten(DEFAULT_PRESET, presets, &mut Vec::new())?;
+        if base.scope == PresetScope::Root {
+            merge_into(&mut root, declarations, Span::dummy());
+        }
+    }
+
+    let mut rules = Vec::new();
+    if !root.is_empty() {
+        rules.push(scoped_rule(":root", None, root));
+    }
+    if !dark.is_empty() {
+        rules.push(scoped_rule(
+            ":root",
+            Some("(prefers-color-scheme: dark)"),
+            dark,
+        ));
+    }
+    rules.extend(custom);
+    Ok(rules)
+}
+
+fn unknown_preset(used: &PresetUse, presets: &HashMap<String, Preset>) -> String {
+    let mut known: Vec<&str> = presets.keys().map(String::as_str).collect();
+    known.sort();
+    format!(
+        "{}: unknown preset '{}' (defined presets: {}){}",
+        used.span,
+        used.name,
+        known.join(", "),
+        suggest::did_you_mean(&used.name, known.iter().copied(), "%")
+    )
+}
+
+fn merge_into(target: &mut Vec<Declaration>, entries: Vec<(String, String)>, span: Span) {
+    for (property, value) in entries {
+        match target.iter_mut().find(|d| d.property == property) {
+            Some(existing) => {
+                existing.value = value;
+                existing.span = span;
+            }
+            // Left to the resolver, which marks the values naming a token.
+            None => target.push(Declaration {
+                property,
+                value,
+                span,
+                themed: false,
+            }),
+        }
+    }
+}
+
+fn scoped_rule(selector: &str, media: Option<&str>, declarations: Vec<Declaration>) -> Rule {
+    let (declarations, media) = match media {
+        Some(query) => (
+            Vec::new(),
+            vec![MediaBlock {
+                query: query.to_string(),
+                declarations,
+            }],
+        ),
+        None => (declarations, Vec::new()),
+    };
+    Rule {
+        selector: selector.to_string(),
+        declarations,
+        media,
+        children: Vec::new(),
+        nested_header: None,
+    }
+}
+
+/// Read a declarations object keeping the order it was written in, which
+/// `serde_json::Value` would otherwise sort.
+fn ordered_declarations<'de, D>(deserializer: D) -> Result<Vec<(String, String)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct Ordered;
+
+    impl<'de> Visitor<'de> for Ordered {
+        type Value = Vec<(String, String)>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an object of property → value")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut entries = Vec::new();
+            while let Some((property, value)) = map.next_entry::<String, Value>()? {
+                let value = match value {
+                    Value::String(s) => s,
+                    Value::Number(n) => n.to_string(),
+                    other => {
+                        return Err(serde::de::Error::custom(format!(
+                            "declaration '{}' must be a string or number, got {}",
+                            property, other
+                        )));
+                    }
+                };
+                entries.push((property, value));
+            }
+            Ok(entries)
+        }
+    }
+
+    deserializer.deserialize_map(Ordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    fn from_json(json: &str) -> Result<HashMap<String, Preset>, String> {
+        parse(json, "presets.json")
+    }
+
+    #[test]
+    fn presets_extend_and_scope() {
+        let presets = from_json(
+            r#"{
+                "base-16": { "declarations": { "font-size": "16px", "line-height": "1.5" } },
+                "reading": { "extends": "base-16", "declarations": { "line-height": "1.75", "max-width": "@prose" } },
+                "calm": { "scope": { "media": "(prefers-reduced-motion: reduce)", "selector": "*" },
+                          "declarations": { "transition": "none" } }
+            }"#,
+        )
+        .unwrap();
+        let sheet = parser::parse("%reading  calm\n%dark\tbase-16\n").unwrap();
+        let rules = expand(&sheet.presets, &presets).unwrap();
+
+        let root: Vec<_> = rules[0]
+            .declarations
+            .iter()
+            .map(|d| (d.property.as_str(), d.value.as_str()))
+            .collect();
+        assert_eq!(
+            root,
+            vec![
+                ("font-size", "16px"),
+                ("line-height", "1.75"),
+                ("max-width", "@prose")
+            ]
+        );
+        assert_eq!(rules[1].media[0].query, "(prefers-color-scheme: dark)");
+        assert_eq!(rules[2].selector, "*");
+        assert_eq!(rules[2].media[0].query, "(prefers-reduced-motion: reduce)");
+    }
+
+    #[test]
+    fn unknown_presets_and_cycles_are_errors() {
+        let presets =
+            from_json(r#"{ "base-16": { "declarations": { "font-size": "16px" } } }"#).unwrap();
+        let sheet = parser::parse("\n%base-16\tfancy\n").unwrap();
+        let err = expand(&sheet.presets, &presets).unwrap_err();
+        assert_eq!(
+            err,
+            "2:10: unknown preset 'fancy' (defined presets: base-16)"
+        );
+        let sheet = parser::parse("%base-61\n").unwrap();
+        let err = expand(&sheet.presets, &presets).unwrap_err();
+        assert!(err.ends_with("\n  help: did you mean '%base-16'?"), "{err}");
+
+        let err = from_json(
+            r#"{
+                "a": { "extends": "b" },
+                "b": { "extends": ["a"] },
+                "c": { "extends": "missing" }
+            }"#,
+        )
+        .unwrap_err();
+        assert!(err.contains("a: extends cycle a → b → a"), "{err}");
+        assert!(err.contains("c: extends unknown preset 'missing'"), "{err}");
+    }
+}
@@ -1,126 +1,317 @@
-use crate::ast::{Declaration, Stylesheet};
+use crate::ast::{Declaration, MediaBlock, Rule, Stylesheet};
+use crate::color::{self, Color, ColorSpace};
 use crate::error::Span;
+use crate::math;
+use crate::presets;
+use crate::suggest;
 use crate::theme::{ShorthandDef, Theme};
-use serde_json::Value;
+use crate::tokens::TokenValue;
+use crate::units;
 use std::collections::{HashMap, HashSet};
 
 pub fn resolve(mut stylesheet: Stylesheet, theme: &Theme) -> Result<Stylesheet, String> {
+    let preset_rules = presets::expand(&stylesheet.presets, &theme.presets)?;
+    stylesheet.rules.splice(0..0, preset_rules);
     let variables = stylesheet.variables.clone();
     for rule in &mut stylesheet.rules {
-        let mut new_decls = Vec::new();
-        let mut display_defined = rule.declarations.iter().any(|d| d.property == "display");
-        let mut radius_entries: Vec<(String, String)> = Vec::new();
-
-        let mut grid_block_entries: Vec<GridBlockEntry> = Vec::new();
-        let mut flex_props_used = false;
-        for mut decl in rule.declarations.drain(..) {
-            let decl_span = decl.span;
-            if decl.property == "grid" {
-                let commands = parse_grid_commands(&decl.value)?;
-                let (mut grid_decls, display_added) = build_grid_declarations(
-                    &commands,
-                    theme,
-                    display_defined,
-                    decl_span,
-                    &variables,
-                )?;
-                display_defined |= display_added;
-                new_decls.append(&mut grid_decls);
-                continue;
-            }
+        resolve_rule(rule, theme, &variables)?;
+    }
+    let root_font_size = units::root_font_size(&stylesheet.rules);
+    for rule in &mut stylesheet.rules {
+        replace_rem_functions(rule, root_font_size)?;
+    }
+    Ok(stylesheet)
+}
 
-            if let Some(entry) = extract_grid_block_entry(&decl.property, &decl.value) {
-                grid_block_entries.push(entry);
-                continue;
-            }
+/// `rem(24px)` → `1.5rem`, once the presets have set the root font size.
+fn replace_rem_functions(rule: &mut Rule, root_font_size: f64) -> Result<(), String> {
+    let declarations = rule.declarations.iter_mut().chain(
+        rule.media
+            .iter_mut()
+            .flat_map(|m| m.declarations.iter_mut()),
+    );
+    for decl in declarations {
+        if decl.value.contains("rem(") {
+            decl.value = units::replace_rem_calls(&decl.value, root_font_size)
+                .map_err(|e| span_error(decl.span, format!("RCSS rem() error: {}", e)))?;
+        }
+    }
+    for child in &mut rule.children {
+        replace_rem_functions(child, root_font_size)?;
+    }
+    Ok(())
+}
 
-            if let Some(key) = extract_radius_key(&decl.property) {
-                radius_entries.push((key.to_string(), decl.value));
-                continue;
-            }
+const DARK_QUERY: &str = "(prefers-color-scheme: dark)";
 
-            // Handle shorthands first
-            if let Some(expanded) = expand_shorthand(&decl.property, &decl.value, theme)? {
-                let mut resolved_expanded = Vec::new();
-                for (prop, val, append) in expanded {
-                    let resolved_value = resolve_value(&val, &prop, theme, decl_span, &variables)?;
-                    resolved_expanded.push((prop, resolved_value, append));
-                }
-                merge_declarations(&mut new_decls, resolved_expanded);
-                continue;
-            }
+fn resolve_rule(
+    rule: &mut Rule,
+    theme: &Theme,
+    variables: &HashMap<String, String>,
+) -> Result<(), String> {
+    // Light/dark tokens: resolve the declarations again against the dark
+    // theme; whatever changes goes into a dark block.
+    let dark_declarations = match &theme.dark {
+        Some(dark) if uses_mode_tokens(&rule.declarations, theme) => {
+            let mut shadow = Rule {
+                selector: rule.selector.clone(),
+                declarations: rule.declarations.clone(),
+                media: Vec::new(),
+                children: Vec::new(),
+                nested_header: None,
+            };
+            resolve_rule(&mut shadow, dark, variables)?;
+            Some(shadow.declarations)
+        }
+        _ => None,
+    };
 
-            if let Some(mapped) = map_border_subproperty(&decl.property) {
-                let resolved = resolve_value(&decl.value, mapped, theme, decl_span, &variables)?;
-                new_decls.push(Declaration {
-                    property: mapped.to_string(),
-                    value: resolved,
-                    span: decl_span,
-                });
-                continue;
-            }
+    let mut new_decls = Vec::new();
+    let mut display_defined = rule.declarations.iter().any(|d| d.property == "display");
+    let mut radius_entries: Vec<(String, String)> = Vec::new();
+
+    let mut grid_block_entries: Vec<GridBlockEntry> = Vec::new();
+    let mut flex_props_used = false;
+    let mut font_metrics: Vec<(String, String)> = Vec::new();
+    let mut radius_themed = false;
+    let mut grid_block_themed = false;
+    for mut decl in rule.declarations.drain(..) {
+        let decl_span = decl.span;
+        let themed = decl.themed || uses_theme(&decl.value, variables);
+        if decl.property == "grid" {
+            let commands = parse_grid_commands(&decl.value)?;
+            let (mut grid_decls, display_added) =
+                build_grid_declarations(&commands, theme, display_defined, decl_span, variables)?;
+            display_defined |= display_added;
+            mark_themed(&mut grid_decls, themed);
+            new_decls.append(&mut grid_decls);
+            continue;
+        }
 
-            if let Some(mapped) = map_flex_subproperty(&decl.property) {
-                let resolved = resolve_value(&decl.value, mapped, theme, decl_span, &variables)?;
-                new_decls.push(Declaration {
-                    property: mapped.to_string(),
-                    value: resolved,
-                    span: decl_span,
-                });
-                flex_props_used = true;
-                continue;
+        if let Some(entry) = extract_grid_block_entry(&decl.property, &decl.value) {
+            grid_block_entries.push(entry);
+            grid_block_themed |= themed;
+            continue;
+        }
+
+        if let Some(key) = extract_radius_key(&decl.property) {
+            radius_entries.push((key.to_string(), decl.value));
+            radius_themed |= themed;
+            continue;
+        }
+
+        // Handle shorthands first
+        if let Some(expanded) =
+            expand_shorthand(&decl.property, &decl.value, theme, &mut font_metrics)?
+        {
+            let mut resolved_expanded = Vec::new();
+            for (prop, val, append) in expanded {
+                let resolved_value = resolve_value(&val, &prop, theme, decl_span, variables)?;
+                resolved_expanded.push((prop, resolved_value, append));
             }
+            merge_declarations(&mut new_decls, resolved_expanded);
+            continue;
+        }
+
+        if let Some(mapped) = map_border_subproperty(&decl.property) {
+            let resolved = resolve_value(&decl.value, mapped, theme, decl_span, variables)?;
+            new_decls.push(Declaration {
+                property: mapped.to_string(),
+                value: resolved,
+                span: decl_span,
+                themed,
+            });
+            continue;
+        }
+
+        if let Some(mapped) = map_flex_subproperty(&decl.property) {
+            let resolved = resolve_value(&decl.value, mapped, theme, decl_span, variables)?;
+            new_decls.push(Declaration {
+                property: mapped.to_string(),
+                value: resolved,
+                span: decl_span,
+                themed,
+            });
+            flex_props_used = true;
+            continue;
+        }
 
-            decl.value = resolve_value(&decl.value, &decl.property, theme, decl_span, &variables)?;
-            new_decls.push(decl);
+        if decl.property == "font-size"
+            && let Some(token) = decl.value.trim().strip_prefix('@')
+        {
+            font_metrics.extend(font_size_metrics("fontSize", token, theme));
         }
 
-        if !radius_entries.is_empty() {
-            let mut expanded = expand_radius_entries(&radius_entries, theme, &variables)?;
-            new_decls.append(&mut expanded);
+        decl.value = resolve_value(&decl.value, &decl.property, theme, decl_span, variables)?;
+        decl.themed = themed;
+        new_decls.push(decl);
+    }
+
+    // Line height and friends paired with a font size token, unless the rule
+    // sets them itself.
+    let mut insert_at = new_decls
+        .iter()
+        .rposition(|d| d.property == "font-size")
+        .map_or(new_decls.len(), |i| i + 1);
+    for (property, value) in font_metrics {
+        if new_decls.iter().any(|d| d.property == property) {
+            continue;
         }
+        new_decls.insert(
+            insert_at,
+            Declaration {
+                property,
+                value,
+                span: Span::dummy(),
+                themed: true,
+            },
+        );
+        insert_at += 1;
+    }
+
+    if !radius_entries.is_empty() {
+        let mut expanded = expand_radius_entries(&radius_entries, theme, variables)?;
+        mark_themed(&mut expanded, radius_themed);
+        new_decls.append(&mut expanded);
+    }
+
+    if !grid_block_entries.is_empty() {
+        let commands = build_grid_commands_from_block(&grid_block_entries)?;
+        let (mut grid_decls, display_added) =
+            build_grid_declarations(&commands, theme, display_defined, Span::dummy(), variables)?;
+        display_defined |= display_added;
+        mark_themed(&mut grid_decls, grid_block_themed);
+        new_decls.append(&mut grid_decls);
+    }
 
-        if !grid_block_entries.is_empty() {
-            let commands = build_grid_commands_from_block(&grid_block_entries)?;
-            let (mut grid_decls, display_added) = build_grid_declarations(
-                &commands,
-                theme,
-                display_defined,
+    if flex_props_used && !display_defined {
+        new_decls.insert(
+            0,
+            Declaration {
+                property: "display".to_string(),
+                value: "flex".to_string(),
+                span: Span::dummy(),
+                themed: false,
+            },
+        );
+    }
+
+    rule.declarations = new_decls;
+
+    // Resolve media blocks; dark blocks use dark token values, and other
+    // blocks get a combined `... and (prefers-color-scheme: dark)` block for
+    // their light/dark tokens.
+    let mut dark_media = Vec::new();
+    for media in &mut rule.media {
+        let query_themed = uses_theme(&media.query, variables);
+        media.query = resolve_media_query(&media.query, theme)?;
+        let is_dark = media.query == DARK_QUERY;
+        let media_theme = match &theme.dark {
+            Some(dark) if is_dark => dark,
+            _ => theme,
+        };
+        let written = media.declarations.clone();
+        for decl in &mut media.declarations {
+            decl.themed |= query_themed || uses_theme(&decl.value, variables);
+            decl.value = resolve_value(
+                &decl.value,
+                &decl.property,
+                media_theme,
                 Span::dummy(),
-                &variables,
+                variables,
             )?;
-            display_defined |= display_added;
-            new_decls.append(&mut grid_decls);
         }
-
-        if flex_props_used && !display_defined {
-            new_decls.insert(
-                0,
-                Declaration {
-                    property: "display".to_string(),
-                    value: "flex".to_string(),
-                    span: Span::dummy(),
-                },
-            );
-        }
-
-        rule.declarations = new_decls;
-
-        // Resolve media blocks
-        for media in &mut rule.media {
-            media.query = resolve_media_query(&media.query, theme)?;
-            for decl in &mut media.declarations {
-                decl.value = resolve_value(
-                    &decl.value,
-                    &decl.property,
-                    theme,
-                    Span::dummy(),
-                    &variables,
-                )?;
+        if let Some(dark) = &theme.dark
+            && !is_dark
+            && uses_mode_tokens(&written, theme)
+        {
+            let mut dark_decls = written;
+            for decl in &mut dark_decls {
+                decl.themed = true;
+                decl.value =
+                    resolve_value(&decl.value, &decl.property, dark, Span::dummy(), variables)?;
+            }
+            let changed = changed_declarations(&media.declarations, dark_decls);
+            if !changed.is_empty() {
+                dark_media.push(MediaBlock {
+                    query: format!("{} and {}", media.query, DARK_QUERY),
+                    declarations: changed,
+                });
             }
         }
     }
-    Ok(stylesheet)
+    if let Some(dark_decls) = dark_declarations {
+        let changed = changed_declarations(&rule.declarations, dark_decls);
+        add_dark_declarations(&mut rule.media, changed);
+    }
+    rule.media.extend(dark_media);
+
+    for child in &mut rule.children {
+        resolve_rule(child, theme, variables)?;
+    }
+    Ok(())
+}
+
+/// Whether a value or media query names a theme token, directly or through
+/// a `$variable`.
+fn uses_theme(value: &str, variables: &HashMap<String, String>) -> bool {
+    resolve_variables(value, variables, Span::dummy()).map_or(true, |value| value.contains('@'))
+}
+
+fn mark_themed(declarations: &mut [Declaration], themed: bool) {
+    for decl in declarations {
+        decl.themed |= themed;
+    }
+}
+
+/// Whether declarations may name a light/dark token, directly or through a
+/// `$variable`.
+fn uses_mode_tokens(declarations: &[Declaration], theme: &Theme) -> bool {
+    declarations.iter().any(|decl| {
+        decl.value.contains('$')
+            || (decl.value.contains('@')
+                && theme
+                    .mode_tokens
+                    .iter()
+                    .any(|key| decl.value.contains(key.as_str())))
+    })
+}
+
+/// The dark resolution of each declaration whose value differs from the
+/// light one. Both lists come from resolving the same input, so they line up.
+fn changed_declarations(light: &[Declaration], dark: Vec<Declaration>) -> Vec<Declaration> {
+    dark.into_iter()
+        .zip(light)
+        .filter(|(dark, light)| dark.property == light.property && dark.value != light.value)
+        .map(|(dark, _)| dark)
+        .collect()
+}
+
+/// Put generated dark declarations ahead of a hand-written dark block, which
+/// wins for any property it sets.
+fn add_dark_declarations(media: &mut Vec<MediaBlock>, declarations: Vec<Declaration>) {
+    if declarations.is_empty() {
+        return;
+    }
+    match media.iter_mut().find(|block| block.query == DARK_QUERY) {
+        Some(block) => {
+            let mut merged: Vec<Declaration> = declarations
+                .into_iter()
+                .filter(|decl| {
+                    !block
+                        .declarations
+                        .iter()
+                        .any(|written| written.property == decl.property)
+                })
+                .collect();
+            merged.append(&mut block.declarations);
+            block.declarations = merged;
+        }
+        None => media.push(MediaBlock {
+            query: DARK_QUERY.to_string(),
+            declarations,
+        }),
+    }
 }
 
 fn resolve_media_query(query: &str, theme: &Theme) -> Result<String, String> {
@@ -131,12 +322,12 @@ fn resolve_media_query(query: &str, theme: &Theme) -> Result<String, String> {
     {
         let token = inner.trim().trim_start_matches('@');
         let width = resolve_from_collection("screens", token, theme)?;
-        let width_str = value_to_css(&width, "screens", token)?;
+        let width_str = value_to_css(width, "screens", token)?;
         return Ok(format!("(min-width: {})", width_str));
     }
 
     if trimmed == "dark" {
-        return Ok("(prefers-color-scheme: dark)".to_string());
+        return Ok(DARK_QUERY.to_string());
     }
 
     if trimmed == "light" {
@@ -155,8 +346,69 @@ fn resolve_value(
     variables: &HashMap<String, String>,
 ) -> Result<String, String> {
     let interpolated = resolve_variables(value, variables, span)?;
-    let resolved = resolve_interpolations(&interpolated, property, theme, span)?;
-    Ok(apply_color_functions(&resolved))
+    let operands = TokenOperands {
+        property,
+        theme,
+        span,
+    };
+    let folded = math::fold(&interpolated, slash_divides(property), &operands)?;
+    let resolved = resolve_interpolations(&folded, property, theme, span)?;
+    apply_color_functions(&resolved, span, value)
+}
+
+/// Tokens of a declaration value, for the arithmetic in [`math::fold`].
+struct TokenOperands<'a> {
+    property: &'a str,
+    theme: &'a Theme,
+    span: Span,
+}
+
+impl math::Operands for TokenOperands<'_> {
+    fn token_end(&self, value: &str, at: usize) -> Result<Option<usize>, String> {
+        let (token, end) = lex_token(value, at + 1, self.theme).map_err(|e| {
+            span_error(
+                self.span.with_offset(at),
+                format!("RCSS token error: {}", e),
+            )
+        })?;
+        Ok((!token.is_empty()).then_some(end))
+    }
+
+    fn resolve(&self, value: &str, at: usize, end: usize) -> Result<String, String> {
+        resolve_interpolations(
+            &value[at..end],
+            self.property,
+            self.theme,
+            self.span.with_offset(at),
+        )
+    }
+
+    fn error(&self, at: usize, message: String) -> String {
+        span_error(
+            self.span.with_offset(at),
+            format!("RCSS arithmetic error: {}", message),
+        )
+    }
+}
+
+/// Whether a top-level `/` in the property's value is a division rather
+/// than a separator (`font: 1rem / 1.5`, `grid-area: 1 / 3`).
+fn slash_divides(property: &str) -> bool {
+    !matches!(
+        property,
+        "font"
+            | "grid"
+            | "grid-area"
+            | "grid-row"
+            | "grid-column"
+            | "grid-template"
+            | "aspect-ratio"
+            | "border-radius"
+            | "border-image"
+            | "background"
+            | "mask"
+            | "mask-border"
+    )
 }
 
 fn resolve_variables(
@@ -206,7 +458,11 @@ fn resolve_variables_inner(
                 let replacement = vars.get(&name).ok_or_else(|| {
                     span_error(
                         span.with_offset(idx),
-                        format!("RCSS variable error: unknown variable '${}'", name),
+                        format!(
+                            "RCSS variable error: unknown variable '${}'{}",
+                            name,
+                            suggest::did_you_mean(&name, vars.keys().map(String::as_str), "$")
+                        ),
                     )
                 })?;
                 let resolved_replacement =
@@ -300,26 +556,25 @@ fn resolve_interpolations(
         }
 
         if ch == '@' && !depth_double && !depth_single {
-            if idx + ch_len <= value.len() {
-                if let Some(next_ch) = value[idx + ch_len..].chars().next() {
-                    if next_ch == '(' {
-                        let start = idx + ch_len;
-                        match consume_parenthesized(value, start) {
-                            Ok((inner, consumed)) => {
-                                let inner_span = span.with_offset(start + 1);
-                                let resolved_inner =
-                                    resolve_interpolations(&inner, property, theme, inner_span)?;
-                                out.push_str(&format!("url(\"{}\")", resolved_inner));
-                                idx = start + consumed;
-                                continue;
-                            }
-                            Err(message) => {
-                                return Err(span_error(
-                                    span.with_offset(idx),
-                                    format!("RCSS URL error: {}", message),
-                                ));
-                            }
-                        }
+            if idx + ch_len <= value.len()
+                && let Some(next_ch) = value[idx + ch_len..].chars().next()
+                && next_ch == '('
+            {
+                let start = idx + ch_len;
+                match consume_parenthesized(value, start) {
+                    Ok((inner, consumed)) => {
+                        let inner_span = span.with_offset(start + 1);
+                        let resolved_inner =
+                            resolve_interpolations(&inner, property, theme, inner_span)?;
+                        out.push_str(&format!("url(\"{}\")", resolved_inner));
+                        idx = start + consumed;
+                        continue;
+                    }
+                    Err(message) => {
+                        return Err(span_error(
+                            span.with_offset(idx),
+                            format!("RCSS URL error: {}", message),
+                        ));
                     }
                 }
             }
@@ -332,36 +587,40 @@ fn resolve_interpolations(
                 ));
             }
 
-            let mut token = String::new();
-            let mut cursor = idx + ch_len;
-            while cursor < value.len() {
-                let next = value[cursor..].chars().next().unwrap();
-                if next == '.' {
-                    let after = value[cursor + next.len_utf8()..].chars().next();
-                    if after.map(|c| c.is_ascii_digit()).unwrap_or(false) {
-                        token.push(next);
-                        cursor += next.len_utf8();
-                        continue;
-                    }
-                    break;
-                }
-                if is_token_char(next) {
-                    token.push(next);
-                    cursor += next.len_utf8();
-                } else {
-                    break;
-                }
-            }
-
+            let (token, cursor) = lex_token(value, idx + ch_len, theme).map_err(|e| {
+                span_error(span.with_offset(idx), format!("RCSS token error: {}", e))
+            })?;
             if token.is_empty() {
                 out.push(ch);
                 idx += ch_len;
                 continue;
             }
 
-            let resolved = resolve_token(property, &token, theme).map_err(|e| {
-                span_error(span.with_offset(idx), format!("RCSS token error: {}", e))
-            })?;
+            // `-@4` negates the token when the `-` starts a value (after `(`,
+            // `,` or whitespace), as CSS reads `-1rem`: `calc(1rem -@4)` is
+            // `1rem` followed by `-1rem`, not a subtraction, which takes a
+            // space on both sides (`calc(1rem - @4)`). A `-` glued to the
+            // value before it (`1rem-@4`) is left as written.
+            let negated = prev == Some('-')
+                && matches!(
+                    prev_char(value, idx - 1),
+                    None | Some('(' | ',' | ' ' | '\t' | '\n')
+                )
+                && out.ends_with('-');
+            let resolved = resolve_token(property, &token, theme)
+                .and_then(|css| {
+                    if negated {
+                        negate(&css, &token)
+                    } else {
+                        Ok(css)
+                    }
+                })
+                .map_err(|e| {
+                    span_error(span.with_offset(idx), format!("RCSS token error: {}", e))
+                })?;
+            if negated {
+                out.pop();
+            }
             out.push_str(&resolved);
             idx = cursor;
             continue;
@@ -392,11 +651,120 @@ fn consume_parenthesized(value: &str, start: usize) -> Result<(String, usize), S
     Err("Unterminated @(...) expression".to_string())
 }
 
+/// The token after an `@` at `start` and the index just past it: a theme
+/// key (`blue-500`, `0.5`, `radius.lg`) or an arbitrary value (`[13px]`),
+/// either followed by an optional `/opacity` or `/[0.37]`.
+fn lex_token(value: &str, start: usize, theme: &Theme) -> Result<(String, usize), String> {
+    let mut token = String::new();
+    let mut cursor = start;
+    if value[cursor..].starts_with('[') {
+        let consumed = bracketed_len(value, cursor)?;
+        token.push_str(&value[cursor..cursor + consumed]);
+        cursor += consumed;
+        if !value[cursor..].starts_with('/') {
+            return Ok((token, cursor));
+        }
+        token.push('/');
+        cursor += 1;
+    }
+
+    while cursor < value.len() {
+        let next = value[cursor..].chars().next().unwrap();
+        if next == '.' {
+            // `@0.5`, or a qualifier such as `@radius.lg`; a dot after
+            // anything else ends the token (`@(img/@blue-500.svg)`).
+            let after = value[cursor + next.len_utf8()..].chars().next();
+            let qualified = after.is_some_and(|c| c.is_ascii_alphabetic())
+                && !token.contains('.')
+                && qualifier(&token, theme).is_some();
+            if after.is_some_and(|c| c.is_ascii_digit()) || qualified {
+                token.push(next);
+                cursor += next.len_utf8();
+                continue;
+            }
+            break;
+        }
+        if next == '[' && token.ends_with('/') {
+            let consumed = bracketed_len(value, cursor)?;
+            token.push_str(&value[cursor..cursor + consumed]);
+            cursor += consumed;
+            break;
+        }
+        if is_token_char(next) {
+            token.push(next);
+            cursor += next.len_utf8();
+        } else {
+            break;
+        }
+    }
+    Ok((token, cursor))
+}
+
+/// Length of the `[...]` group opening at `start`, brackets nested.
+fn bracketed_len(value: &str, start: usize) -> Result<usize, String> {
+    let mut depth = 0;
+    for (offset, ch) in value[start..].char_indices() {
+        match ch {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(offset + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err("unterminated @[...] value".to_string())
+}
+
+/// `-@token`: numbers and dimensions flip their sign, expressions are
+/// wrapped as `calc(-1 * ...)`, and anything else is an error.
+fn negate(css: &str, token: &str) -> Result<String, String> {
+    let css = css.trim();
+    let is_number = |s: &str| {
+        let end = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        end > 0
+            && s[..end].parse::<f64>().is_ok()
+            && s[end..]
+                .chars()
+                .all(|c| c.is_ascii_alphabetic() || c == '%')
+    };
+    if let Some(positive) = css.strip_prefix('-')
+        && is_number(positive)
+    {
+        return Ok(positive.to_string());
+    }
+    if is_number(css) {
+        let zero = css
+            .trim_end_matches(|c: char| c.is_ascii_alphabetic() || c == '%')
+            .parse::<f64>()
+            .is_ok_and(|n| n == 0.0);
+        return Ok(if zero {
+            css.to_string()
+        } else {
+            format!("-{}", css)
+        });
+    }
+    if ["calc(", "var(", "min(", "max(", "clamp(", "env("]
+        .iter()
+        .any(|f| css.starts_with(f))
+    {
+        return Ok(format!("calc(-1 * {})", css));
+    }
+    Err(format!(
+        "-@{} cannot be negated: '{}' is not a number or length",
+        token, css
+    ))
+}
+
 fn prev_char(value: &str, idx: usize) -> Option<char> {
     if idx == 0 {
         return None;
     }
-    value[..idx].chars().rev().next()
+    value[..idx].chars().next_back()
 }
 
 fn is_token_boundary(prev: Option<char>) -> bool {
@@ -437,13 +805,22 @@ fn is_token_char(ch: char) -> bool {
     ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' || ch == '/' || ch == '.'
 }
 
-fn apply_color_functions(value: &str) -> String {
+/// Evaluate color helpers in `value`. `source` is the value as written, used
+/// to point errors at the helper call; nested calls pass an empty source and
+/// report at their enclosing call.
+fn apply_color_functions(value: &str, span: Span, source: &str) -> Result<String, String> {
     let mut out = String::new();
     let mut idx = 0;
+    let mut source_cursor = 0;
 
     while idx < value.len() {
         if let Some((name, inner, consumed)) = consume_color_function(value, idx) {
-            let expanded = expand_color_function(name, &inner);
+            let mut call_span = span;
+            if let Some(pos) = source[source_cursor..].find(&format!("{}(", name)) {
+                call_span = span.with_offset(source_cursor + pos);
+                source_cursor += pos + name.len();
+            }
+            let expanded = expand_color_function(name, &inner, call_span)?;
             out.push_str(&expanded);
             idx += consumed;
             continue;
@@ -454,18 +831,47 @@ fn apply_color_functions(value: &str) -> String {
         idx += ch.len_utf8();
     }
 
-    out
+    Ok(out)
 }
 
+/// Compile-time color helpers: name, minimum and maximum argument count, and
+/// usage shown in arity errors.
+const COLOR_FUNCTIONS: [(&str, usize, usize, &str); 12] = [
+    ("mix", 2, 4, "mix(color, color, [weight], [space])"),
+    ("lighten", 2, 3, "lighten(color, amount, [space])"),
+    ("darken", 2, 3, "darken(color, amount, [space])"),
+    ("alpha", 2, 2, "alpha(color, amount)"),
+    ("shade", 2, 3, "shade(color, amount, [space])"),
+    ("tint", 2, 3, "tint(color, amount, [space])"),
+    ("tone", 2, 3, "tone(color, amount, [space])"),
+    ("saturate", 2, 3, "saturate(color, amount, [space])"),
+    ("desaturate", 2, 3, "desaturate(color, amount, [space])"),
+    ("adjust-hue", 2, 3, "adjust-hue(color, degrees, [space])"),
+    ("complement", 1, 2, "complement(color, [space])"),
+    (
+        "contrast-color",
+        1,
+        3,
+        "contrast-color(color, [light], [dark])",
+    ),
+];
+
 fn consume_color_function(value: &str, start: usize) -> Option<(&'static str, String, usize)> {
-    const FUNCTIONS: [&str; 7] = ["mix", "lighten", "darken", "alpha", "shade", "tint", "tone"];
+    // `mix(` inside `color-mix(` is not a helper call.
+    if value[..start]
+        .chars()
+        .next_back()
+        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
 
-    for name in FUNCTIONS {
+    for (name, ..) in COLOR_FUNCTIONS {
         if let Some(bytes) = value.get(start..) {
             if bytes.len() < name.len() + 1 {
                 continue;
             }
-            if !bytes.starts_with(&format!("{}(", name)) {
+            if !bytes.starts_with(name) {
                 continue;
             }
             let open_idx = start + name.len();
@@ -495,52 +901,269 @@ fn consume_color_function(value: &str, start: usize) -> Option<(&'static str, St
     None
 }
 
-fn expand_color_function(name: &str, inner: &str) -> String {
-    let args = split_color_args(inner);
-    let resolved: Vec<String> = args
-        .into_iter()
-        .map(|arg| apply_color_functions(&arg))
-        .collect();
+/// A helper argument that should be a color. Values only known at runtime
+/// (`var()`, `currentColor`, ...) can still be mixed with `color-mix()`.
+enum ColorArg {
+    Static { color: Color, modern: bool },
+    Dynamic(String),
+}
 
-    match name {
-        "mix" if resolved.len() == 3 => {
-            let color1 = resolved[0].trim();
-            let color2 = resolved[1].trim();
-            let percent = resolved[2].trim();
-            format!("color-mix(in srgb, {} {}, {})", color2, percent, color1)
-        }
-        "lighten" if resolved.len() == 2 => {
-            let color = resolved[0].trim();
-            let amount = resolved[1].trim();
-            format!("color-mix(in srgb, white {}, {})", amount, color)
-        }
-        "darken" if resolved.len() == 2 => {
-            let color = resolved[0].trim();
-            let amount = resolved[1].trim();
-            format!("color-mix(in srgb, black {}, {})", amount, color)
+impl ColorArg {
+    fn parse(raw: &str, function: &str, position: usize, span: Span) -> Result<Self, String> {
+        let raw = raw.trim();
+        let lower = raw.to_ascii_lowercase();
+        match Color::parse(raw) {
+            Ok(color) => Ok(ColorArg::Static {
+                color,
+                modern: ["oklch(", "oklab("].iter().any(|p| lower.contains(p)),
+            }),
+            Err(_)
+                if ["var(", "env(", "color-mix("]
+                    .iter()
+                    .any(|p| lower.starts_with(p))
+                    || matches!(lower.as_str(), "currentcolor" | "inherit") =>
+            {
+                Ok(ColorArg::Dynamic(raw.to_string()))
+            }
+            Err(_) => Err(span_error(
+                span,
+                format!(
+                    "{}() expects a color as argument {}, got '{}'",
+                    function, position, raw
+                ),
+            )),
         }
-        "shade" if resolved.len() == 2 => {
-            let color = resolved[0].trim();
-            let amount = resolved[1].trim();
-            format!("color-mix(in srgb, black {}, {})", amount, color)
+    }
+
+    fn css(&self) -> String {
+        match self {
+            ColorArg::Static { color, modern } => format_color(color, *modern),
+            ColorArg::Dynamic(raw) => raw.clone(),
         }
-        "tint" if resolved.len() == 2 => {
-            let color = resolved[0].trim();
-            let amount = resolved[1].trim();
-            format!("color-mix(in srgb, white {}, {})", amount, color)
+    }
+}
+
+/// Keep OKLCH output for OKLCH inputs so wide-gamut colors survive; the
+/// emitter adds sRGB fallbacks for targets that need them.
+fn format_color(color: &Color, modern: bool) -> String {
+    if modern {
+        color.to_oklch_string()
+    } else {
+        color.to_srgb_string()
+    }
+}
+
+fn parse_color_amount(raw: &str, function: &str, span: Span) -> Result<f64, String> {
+    let raw = raw.trim();
+    let value = match raw.strip_suffix('%') {
+        Some(p) => p.trim().parse::<f64>().ok().map(|v| v / 100.0),
+        None => raw.parse::<f64>().ok(),
+    };
+    match value {
+        Some(v) if (0.0..=1.0).contains(&v) => Ok(v),
+        _ => Err(span_error(
+            span,
+            format!(
+                "{}() expects an amount between 0% and 100%, got '{}'",
+                function, raw
+            ),
+        )),
+    }
+}
+
+fn parse_color_space(raw: &str, function: &str, span: Span) -> Result<ColorSpace, String> {
+    let name = raw.trim();
+    let name = name.strip_prefix("in ").unwrap_or(name);
+    ColorSpace::parse(name).ok_or_else(|| {
+        span_error(
+            span,
+            format!(
+                "{}() got unknown color space '{}' (expected srgb, srgb-linear, hsl, oklab or oklch)",
+                function,
+                raw.trim()
+            ),
+        )
+    })
+}
+
+fn parse_degrees(raw: &str, function: &str, span: Span) -> Result<f64, String> {
+    let raw = raw.trim();
+    raw.strip_suffix("deg")
+        .unwrap_or(raw)
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| {
+            span_error(
+                span,
+                format!("{}() expects an angle, got '{}'", function, raw),
+            )
+        })
+}
+
+fn expand_color_function(name: &str, inner: &str, span: Span) -> Result<String, String> {
+    let args = split_color_args(inner)
+        .iter()
+        .map(|arg| apply_color_functions(arg, span, ""))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // One-argument `saturate()` is the CSS filter function.
+    if name == "saturate" && args.len() == 1 {
+        return Ok(format!("saturate({})", args[0]));
+    }
+
+    let (_, min, max, usage) = COLOR_FUNCTIONS
+        .iter()
+        .find(|(candidate, ..)| *candidate == name)
+        .copied()
+        .expect("consume_color_function only yields known helpers");
+    if args.len() < min || args.len() > max {
+        return Err(span_error(
+            span,
+            format!(
+                "{}() got {} argument{}, expected {}",
+                name,
+                args.len(),
+                if args.len() == 1 { "" } else { "s" },
+                usage
+            ),
+        ));
+    }
+
+    if name == "contrast-color" {
+        return expand_contrast_color(&args, span);
+    }
+
+    let color = ColorArg::parse(&args[0], name, 1, span)?;
+    match name {
+        "mix" => {
+            let other = ColorArg::parse(&args[1], name, 2, span)?;
+            let (weight, space) = match args.get(2).map(String::as_str) {
+                Some(raw) if ColorSpace::parse(raw.trim_start_matches("in ")).is_some() => {
+                    (0.5, parse_color_space(raw, name, span)?)
+                }
+                Some(raw) => {
+                    let space = match args.get(3) {
+                        Some(space) => parse_color_space(space, name, span)?,
+                        None => ColorSpace::Srgb,
+                    };
+                    (parse_color_amount(raw, name, span)?, space)
+                }
+                None => (0.5, ColorSpace::Srgb),
+            };
+            Ok(mix_colors(&color, &other, weight, space))
+        }
+        "lighten" | "darken" | "shade" | "tint" | "tone" => {
+            let amount = parse_color_amount(&args[1], name, span)?;
+            let space = match args.get(2) {
+                Some(raw) => parse_color_space(raw, name, span)?,
+                None => ColorSpace::Srgb,
+            };
+            let target = match name {
+                "lighten" | "tint" => "white",
+                "darken" | "shade" => "black",
+                _ => "gray",
+            };
+            let target = ColorArg::parse(target, name, 2, span)?;
+            Ok(mix_colors(&color, &target, amount, space))
+        }
+        "alpha" => {
+            let amount = parse_color_amount(&args[1], name, span)?;
+            Ok(match &color {
+                ColorArg::Static { color, modern } => {
+                    format_color(&color.with_alpha(color.alpha * amount), *modern)
+                }
+                ColorArg::Dynamic(raw) => format!(
+                    "color-mix(in srgb, {} {}, transparent)",
+                    raw,
+                    color::format_number(amount * 100.0, 4) + "%"
+                ),
+            })
+        }
+        _ => {
+            let ColorArg::Static { color, modern } = color else {
+                return Err(span_error(
+                    span,
+                    format!("{}() needs a static color, got '{}'", name, args[0].trim()),
+                ));
+            };
+            let space_arg = if name == "complement" { 1 } else { 2 };
+            let space = match args.get(space_arg) {
+                Some(raw) => parse_color_space(raw, name, span)?,
+                None => ColorSpace::Hsl,
+            };
+            let result = match name {
+                "saturate" => color.saturate(parse_color_amount(&args[1], name, span)?, space),
+                "desaturate" => color.saturate(-parse_color_amount(&args[1], name, span)?, space),
+                "adjust-hue" => color.adjust_hue(parse_degrees(&args[1], name, span)?, space),
+                _ => color.adjust_hue(180.0, space),
+            };
+            Ok(format_color(&result, modern))
         }
-        "tone" if resolved.len() == 2 => {
-            let color = resolved[0].trim();
-            let amount = resolved[1].trim();
-            format!("color-mix(in srgb, gray {}, {})", amount, color)
+    }
+}
+
+/// Mix `amount` of `other` into `base`. Static inputs are computed now;
+/// otherwise the equivalent `color-mix()` is emitted.
+fn mix_colors(base: &ColorArg, other: &ColorArg, amount: f64, space: ColorSpace) -> String {
+    match (base, other) {
+        (
+            ColorArg::Static {
+                color: a,
+                modern: ma,
+            },
+            ColorArg::Static {
+                color: b,
+                modern: mb,
+            },
+        ) => format_color(&a.mix(b, amount, space), *ma || *mb),
+        _ => format!(
+            "color-mix(in {}, {} {}%, {})",
+            space.name(),
+            other.css(),
+            color::format_number(amount * 100.0, 4),
+            base.css()
+        ),
+    }
+}
+
+/// Pick whichever candidate (white and black by default) has the higher
+/// WCAG contrast against the color. Runtime colors keep native
+/// `contrast-color()`.
+fn expand_contrast_color(args: &[String], span: Span) -> Result<String, String> {
+    let name = "contrast-color";
+    let base = match ColorArg::parse(&args[0], name, 1, span)? {
+        ColorArg::Static { color, .. } => color,
+        ColorArg::Dynamic(raw) if args.len() == 1 => return Ok(format!("{}({})", name, raw)),
+        ColorArg::Dynamic(raw) => {
+            return Err(span_error(
+                span,
+                format!("{}() needs a static color, got '{}'", name, raw),
+            ));
         }
-        "alpha" if resolved.len() == 2 => {
-            let color = resolved[0].trim();
-            let amount = resolved[1].trim();
-            format!("color-mix(in srgb, {} {}, transparent)", color, amount)
+    };
+
+    let candidates: Vec<&str> = if args.len() == 1 {
+        vec!["white", "black"]
+    } else {
+        args[1..].iter().map(|arg| arg.trim()).collect()
+    };
+    let mut best: Option<(f64, &str)> = None;
+    for (i, candidate) in candidates.iter().enumerate() {
+        let ColorArg::Static { color, .. } = ColorArg::parse(candidate, name, i + 2, span)? else {
+            return Err(span_error(
+                span,
+                format!(
+                    "{}() needs static candidate colors, got '{}'",
+                    name, candidate
+                ),
+            ));
+        };
+        let ratio = base.contrast_ratio(&color);
+        if best.is_none_or(|(best_ratio, _)| ratio > best_ratio) {
+            best = Some((ratio, candidate));
         }
-        _ => format!("{}({})", name, inner),
     }
+    Ok(best.map(|(_, css)| css.to_string()).unwrap_or_default())
 }
 
 fn split_color_args(input: &str) -> Vec<String> {
@@ -801,7 +1424,7 @@ fn parse_areas(input: &str) -> Result<Vec<String>, String> {
         }
 
         let mut content = String::new();
-        while let Some(next) = chars.next() {
+        for next in chars.by_ref() {
             if next == '"' {
                 break;
             }
@@ -903,6 +1526,7 @@ fn build_grid_declarations(
                 property: "display".to_string(),
                 value: "block".to_string(),
                 span,
+                themed: false,
             });
             display_added = true;
         }
@@ -913,6 +1537,7 @@ fn build_grid_declarations(
                 property: "column-count".to_string(),
                 value: count.to_string(),
                 span,
+                themed: false,
             });
         }
 
@@ -930,6 +1555,7 @@ fn build_grid_declarations(
                 property: "column-gap".to_string(),
                 value: column_gap,
                 span,
+                themed: false,
             });
         }
 
@@ -941,6 +1567,7 @@ fn build_grid_declarations(
             property: "display".to_string(),
             value: "grid".to_string(),
             span,
+            themed: false,
         });
         display_added = true;
     }
@@ -952,6 +1579,7 @@ fn build_grid_declarations(
             property: "grid-template-columns".to_string(),
             value,
             span,
+            themed: false,
         });
     }
 
@@ -961,6 +1589,7 @@ fn build_grid_declarations(
             property: "grid-template-rows".to_string(),
             value: resolved.join(" "),
             span,
+            themed: false,
         });
     }
 
@@ -972,6 +1601,7 @@ fn build_grid_declarations(
                     property: "gap".to_string(),
                     value: resolved,
                     span,
+                    themed: false,
                 });
             }
             2 => {
@@ -981,11 +1611,13 @@ fn build_grid_declarations(
                     property: "row-gap".to_string(),
                     value: row_gap,
                     span,
+                    themed: false,
                 });
                 declarations.push(Declaration {
                     property: "column-gap".to_string(),
                     value: col_gap,
                     span,
+                    themed: false,
                 });
             }
             _ => {}
@@ -999,6 +1631,7 @@ fn build_grid_declarations(
             property: "grid-template-areas".to_string(),
             value,
             span,
+            themed: false,
         });
     }
 
@@ -1029,10 +1662,10 @@ fn resolve_grid_integer(
     if let Ok(num) = raw.parse::<i32>() {
         return Ok(num);
     }
-    if let Some(stripped) = raw.strip_prefix('@') {
-        if let Ok(num) = stripped.trim().parse::<i32>() {
-            return Ok(num);
-        }
+    if let Some(stripped) = raw.strip_prefix('@')
+        && let Ok(num) = stripped.trim().parse::<i32>()
+    {
+        return Ok(num);
     }
     let resolved = resolve_value(raw, "columns", theme, span, variables).map_err(|_| {
         span_error(
@@ -1054,11 +1687,15 @@ fn resolve_grid_integer(
     })
 }
 
+/// A shorthand step rendered as `(property, value, append)`.
+type ExpandedEntry = (String, String, bool);
+
 fn expand_shorthand(
     property: &str,
     value: &str,
     theme: &Theme,
-) -> Result<Option<Vec<(String, String, bool)>>, String> {
+    font_metrics: &mut Vec<(String, String)>,
+) -> Result<Option<Vec<ExpandedEntry>>, String> {
     let property_key = normalize_property(property);
     let def: &ShorthandDef = match theme.shorthands.get(&property_key) {
         Some(s) => s,
@@ -1066,6 +1703,7 @@ fn expand_shorthand(
     };
 
     let assignments = parse_assignments(value, def.order.as_deref(), &property_key)?;
+    check_parts(&assignments, def, property)?;
     let mut resolved: HashMap<String, String> = HashMap::new();
 
     for (name, token) in assignments {
@@ -1077,6 +1715,7 @@ fn expand_shorthand(
 
         let resolved_value = if token.starts_with('@') {
             let cleaned = token.trim_start_matches('@');
+            font_metrics.extend(font_size_metrics(&prop_name, cleaned, theme));
             resolve_token(&prop_name, cleaned, theme)?
         } else {
             token.clone()
@@ -1107,6 +1746,46 @@ fn expand_shorthand(
     Ok(Some(out))
 }
 
+/// Every named part must feed some template; `ring: colr=@red-500` is a
+/// typo, not a part to drop.
+fn check_parts(
+    assignments: &[(String, String)],
+    def: &ShorthandDef,
+    property: &str,
+) -> Result<(), String> {
+    let placeholders: Vec<&str> = def
+        .steps
+        .iter()
+        .flat_map(|step| template_placeholders(&step.template))
+        .collect();
+    for (name, _) in assignments {
+        let spellings = [
+            name.clone(),
+            normalize_property(name),
+            name.replace('_', "-"),
+        ];
+        if name == "token" || spellings.iter().any(|s| placeholders.contains(&s.as_str())) {
+            continue;
+        }
+        return Err(format!(
+            "Unknown part '{}' for shorthand '{}' (expected {}){}",
+            name,
+            property,
+            placeholders.join(", "),
+            suggest::did_you_mean(name, placeholders.iter().copied(), "")
+        ));
+    }
+    Ok(())
+}
+
+/// The `@{name}` placeholders of a shorthand template.
+fn template_placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template
+        .split("@{")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+}
+
 fn parse_assignments(
     value: &str,
     positional: Option<&[String]>,
@@ -1130,18 +1809,19 @@ fn parse_assignments(
             continue;
         }
 
-        if part.ends_with(')') && part.contains('(') {
-            if let Some(idx) = part.find('(') {
-                let key = part[..idx].trim();
-                let val = part[idx + 1..part.len() - 1].trim();
-                if key.is_empty() || val.is_empty() {
-                    return Err(format!("Invalid shorthand part '{}'", part));
-                }
-                let canonical = canonical_key(key, &alias_map);
-                used_keys.insert(canonical.clone());
-                result.push((canonical, val.to_string()));
-                continue;
+        if part.ends_with(')')
+            && part.contains('(')
+            && let Some(idx) = part.find('(')
+        {
+            let key = part[..idx].trim();
+            let val = part[idx + 1..part.len() - 1].trim();
+            if key.is_empty() || val.is_empty() {
+                return Err(format!("Invalid shorthand part '{}'", part));
             }
+            let canonical = canonical_key(key, &alias_map);
+            used_keys.insert(canonical.clone());
+            result.push((canonical, val.to_string()));
+            continue;
         }
 
         if let Some(keys) = positional {
@@ -1268,6 +1948,7 @@ fn apply_template(template: &str, values: &HashMap<String, String>) -> Option<St
     Some(out)
 }
 
+/// Shorthand templates are theme data, so everything they produce is themed.
 fn merge_declarations(
     target: &mut Vec<crate::ast::Declaration>,
     expanded: Vec<(String, String, bool)>,
@@ -1276,6 +1957,7 @@ fn merge_declarations(
         let mut joined = false;
 
         if let Some(existing) = target.iter_mut().rev().find(|d| d.property == prop) {
+            existing.themed = true;
             if append || prop == "box-shadow" {
                 existing.value = format!("{}, {}", existing.value, val);
                 joined = true;
@@ -1292,6 +1974,7 @@ fn merge_declarations(
             property: prop,
             value: val,
             span: Span::dummy(),
+            themed: true,
         });
     }
 }
@@ -1346,6 +2029,7 @@ fn expand_radius_entries(
                 property,
                 value: resolved,
                 span: Span::dummy(),
+                themed: false,
             });
         }
     }
@@ -1365,22 +2049,36 @@ fn resolve_radius_value(
             let trimmed = raw.trim();
             if let Some(stripped) = trimmed.strip_prefix('@') {
                 let resolved = resolve_from_collection("spacing", stripped, theme)?;
-                return value_to_css(&resolved, "spacing", stripped);
+                return value_to_css(resolved, "spacing", stripped);
             }
             Err(original)
         }
     }
 }
 
-fn resolve_color_token(token: &str, theme: &Theme) -> Result<String, String> {
+/// `token`, with an optional `/opacity`, from one collection.
+fn resolve_collection_token(
+    collection: &str,
+    token: &str,
+    theme: &Theme,
+) -> Result<String, String> {
     if let Some((base_token, opacity_token)) = token.split_once('/') {
-        let base_value = resolve_from_collection("colors", base_token, theme)?;
-        let opacity_value = resolve_from_collection("opacity", opacity_token, theme)?;
-        return inject_alpha(base_value, opacity_value);
+        let base_value = collection_css(collection, base_token, theme)?;
+        let opacity_value = collection_css("opacity", opacity_token, theme)?;
+        return color::inject_alpha(&base_value, &opacity_value);
     }
 
-    let value = resolve_from_collection("colors", token, theme)?;
-    value_to_css(&value, "textColor", token)
+    collection_css(collection, token, theme)
+}
+
+/// CSS for a token of a collection, from the index when possible so errors
+/// for missing or non-value tokens come from the typed theme.
+fn collection_css(collection: &str, token: &str, theme: &Theme) -> Result<String, String> {
+    if let Some(css) = theme.index.collection_value(collection, token) {
+        return Ok(css.to_string());
+    }
+    let value = resolve_from_collection(collection, token, theme)?;
+    value_to_css(value, collection, token)
 }
 
 #[derive(Default)]
@@ -1437,7 +2135,7 @@ impl RadiusSpec {
             if let Some(val) = &self.inline_start {
                 return Some(val.clone());
             }
-            return self.inline.as_ref().and_then(|vals| vals.get(0).cloned());
+            return self.inline.as_ref().and_then(|vals| vals.first().cloned());
         }
 
         if let Some(val) = &self.inline_end {
@@ -1448,7 +2146,7 @@ impl RadiusSpec {
             if vals.len() > 1 {
                 vals.get(1).cloned()
             } else {
-                vals.get(0).cloned()
+                vals.first().cloned()
             }
         })
     }
@@ -1458,7 +2156,7 @@ impl RadiusSpec {
             if let Some(val) = &self.block_start {
                 return Some(val.clone());
             }
-            return self.block.as_ref().and_then(|vals| vals.get(0).cloned());
+            return self.block.as_ref().and_then(|vals| vals.first().cloned());
         }
 
         if let Some(val) = &self.block_end {
@@ -1469,7 +2167,7 @@ impl RadiusSpec {
             if vals.len() > 1 {
                 vals.get(1).cloned()
             } else {
-                vals.get(0).cloned()
+                vals.first().cloned()
             }
         })
     }
@@ -1490,153 +2188,595 @@ fn is_block_start(corner: &str) -> bool {
     corner.starts_with("top")
 }
 fn resolve_token(property: &str, token: &str, theme: &Theme) -> Result<String, String> {
-    let lookup_target = if property.starts_with("--") {
-        "color"
-    } else {
-        property
-    };
+    resolve_token_route(property, token, theme).map(|(css, _)| css)
+}
+
+/// Which branch of [`resolve_token`] produced a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Route<'a> {
+    Url,
+    /// An arbitrary value, `@[13px]`.
+    Arbitrary,
+    /// The mapping of a property: an override or its collection.
+    Mapping(&'a str, ChosenBy),
+    /// A collection, looked up directly.
+    Collection(&'a str, ChosenBy),
+}
 
-    let property_key = normalize_property(lookup_target);
+/// What `@qualifier.key` names: a collection, or a property whose mapping
+/// (overrides included) is used.
+enum Qualifier<'a> {
+    Collection(&'a str),
+    Property(&'a str),
+}
+
+/// Split `@spacing.4`, `@border-radius.lg` or `@radius.lg` into its
+/// qualifier and key; `@0.5` and other dotted keys are not qualified.
+fn split_qualifier<'a>(token: &'a str, theme: &'a Theme) -> Option<(Qualifier<'a>, &'a str)> {
+    let (name, key) = token.split_once('.')?;
+    if key.is_empty() {
+        return None;
+    }
+    qualifier(name, theme).map(|qualifier| (qualifier, key))
+}
 
+/// The collection or property `name` refers to. Collections win over
+/// properties of the same name.
+fn qualifier<'a>(name: &'a str, theme: &'a Theme) -> Option<Qualifier<'a>> {
+    if let Some((collection, _)) = theme
+        .collections
+        .get_key_value(name)
+        .or_else(|| theme.collections.get_key_value(&normalize_property(name)))
+    {
+        return Some(Qualifier::Collection(collection));
+    }
+    theme
+        .index
+        .property(name)
+        .map(|_| Qualifier::Property(name))
+}
+
+fn resolve_token_route<'a>(
+    property: &'a str,
+    token: &'a str,
+    theme: &'a Theme,
+) -> Result<(String, Route<'a>), String> {
     // direct url syntax: @(...) -> url("...")
     if token.starts_with('(') && token.ends_with(')') {
         let inner = token.trim_start_matches('(').trim_end_matches(')');
-        return Ok(format!("url(\"{}\")", inner));
+        return Ok((format!("url(\"{}\")", inner), Route::Url));
     }
-    let mapping = property_mapping(&property_key, theme);
 
-    if let Some((base_token, opacity_token)) = token.split_once('/') {
-        if let Some(mapping) = &mapping {
-            if let Ok(base_value) = resolve_from_mapping(base_token, mapping, theme) {
-                let opacity_value = resolve_from_collection("opacity", opacity_token, theme)?;
-                return inject_alpha(base_value, opacity_value);
+    if let (base, Some(opacity)) = split_modifier(token)
+        && let Some(opacity) = opacity.strip_prefix('[').and_then(|o| o.strip_suffix(']'))
+    {
+        let opacity = arbitrary_opacity(opacity)?;
+        let (css, route) = resolve_token_route(property, base, theme)?;
+        return Ok((color::inject_alpha(&css, &opacity)?, route));
+    }
+    if token.starts_with('[') {
+        let (value, opacity) = split_modifier(token);
+        let raw = &value[1..value.len() - 1];
+        let css = arbitrary_value(property, raw, theme)?;
+        let css = match opacity {
+            Some(opacity) => {
+                color::inject_alpha(&css, &collection_css("opacity", opacity, theme)?)?
             }
+            None => css,
+        };
+        return Ok((css, Route::Arbitrary));
+    }
+
+    match split_qualifier(token, theme) {
+        Some((Qualifier::Collection(collection), key)) => {
+            let css = resolve_collection_token(collection, key, theme)?;
+            return Ok((css, Route::Collection(collection, ChosenBy::Qualifier)));
         }
+        Some((Qualifier::Property(name), key)) => {
+            let css = resolve_mapped_token(name, key, theme)?;
+            return Ok((css, Route::Mapping(name, ChosenBy::Qualifier)));
+        }
+        None => {}
+    }
 
-        let base_value = resolve_from_collection("colors", base_token, theme)?;
-        let opacity_value = resolve_from_collection("opacity", opacity_token, theme)?;
-        return inject_alpha(base_value, opacity_value);
+    if property.starts_with("--") {
+        return match infer_custom_route(property, token, theme)? {
+            route @ Route::Mapping(name, _) => {
+                Ok((resolve_mapped_token(name, token, theme)?, route))
+            }
+            route @ Route::Collection(collection, _) => {
+                Ok((resolve_collection_token(collection, token, theme)?, route))
+            }
+            Route::Url | Route::Arbitrary => {
+                unreachable!("custom properties infer a mapping or collection")
+            }
+        };
     }
 
-    if let Some(mapping) = &mapping {
-        if let Ok(value) = resolve_from_mapping(token, mapping, theme) {
-            return value_to_css(&value, &property_key, token);
+    let mapping = match theme.index.property(property) {
+        Some(mapping) => Some(mapping),
+        None => theme.index.property(&normalize_property(property)),
+    };
+    let by_property = Route::Mapping(property, ChosenBy::Property);
+    let colors_fallback = Route::Collection("colors", ChosenBy::ColorsFallback);
+
+    if let Some((base_token, opacity_token)) = token.split_once('/') {
+        if let Some(base_value) = mapping.and_then(|m| m.get(base_token)) {
+            let opacity_value = collection_css("opacity", opacity_token, theme)?;
+            let css = color::inject_alpha(base_value, &opacity_value)?;
+            return Ok((css, by_property));
+        }
+
+        let css = resolve_collection_token("colors", token, theme)?;
+        return Ok((css, colors_fallback));
+    }
+
+    if let Some(mapping) = mapping {
+        if let Some(value) = mapping.get(token) {
+            return Ok((value.to_string(), by_property));
+        }
+        // Tokens missing from the index are either unknown or not usable as
+        // a value; the typed lookup reports which.
+        if let Some(value) = theme
+            .property(&normalize_property(property))
+            .and_then(|m| resolve_from_mapping(token, m, theme).ok())
+        {
+            let css = value_to_css(value, &normalize_property(property), token)?;
+            return Ok((css, by_property));
+        }
+        let known = |collection: &str| {
+            theme
+                .collections
+                .get(collection)
+                .is_some_and(|tokens| tokens.contains_key(token))
+        };
+        if mapping.collection != "colors" && !known(&mapping.collection) && !known("colors") {
+            let keys = [mapping.collection.as_str(), "colors"]
+                .into_iter()
+                .filter_map(|collection| theme.collections.get(collection))
+                .flat_map(|tokens| tokens.keys().map(String::as_str));
+            return Err(format!(
+                "Unknown token '{}' for {} (not in {} or colors){}",
+                token,
+                property,
+                mapping.collection,
+                suggest::did_you_mean(token, keys, "")
+            ));
         }
-        return resolve_color_token(token, theme);
+        let css = resolve_collection_token("colors", token, theme)?;
+        return Ok((css, colors_fallback));
     }
 
-    Err(format!("Unknown property '{}'", property))
+    // `border` is a property the theme does not map, not a typo of `order`.
+    let help = if theme.shorthands.contains_key(property)
+        || suggest::names_a_family(property, theme.index.property_names())
+    {
+        String::new()
+    } else {
+        let candidates = suggestable_properties(theme);
+        suggest::did_you_mean(property, candidates.iter().map(String::as_str), "")
+    };
+    Err(format!("Unknown property '{}'{}", property, help))
 }
 
-fn property_mapping<'a>(
-    property_key: &str,
-    theme: &'a Theme,
-) -> Option<&'a crate::theme::PropertyMapping> {
-    // CSS aliases to theme property keys
-    if property_key == "color" {
-        return theme.properties.get("textColor");
-    }
+/// Aliases that are CSS properties in their own right; the others (`to`,
+/// `size`, `offset-color`) are theme shorthands and never offered as the
+/// fix for a misspelt property.
+const CSS_ALIASES: [&str; 10] = [
+    "background",
+    "color",
+    "border-top-left-radius",
+    "border-top-right-radius",
+    "border-bottom-right-radius",
+    "border-bottom-left-radius",
+    "border-start-start-radius",
+    "border-start-end-radius",
+    "border-end-start-radius",
+    "border-end-end-radius",
+];
+
+/// Names suggested for an unknown property: the mapped properties in
+/// kebab case and the aliases that are real CSS.
+fn suggestable_properties(theme: &Theme) -> Vec<String> {
+    let mapped = theme
+        .properties
+        .keys()
+        .filter(|name| !theme.aliases.contains_key(*name))
+        .map(|name| crate::tokens::kebab_case(name));
+    let aliases = theme
+        .aliases
+        .keys()
+        .map(|name| crate::tokens::kebab_case(name))
+        .filter(|name| CSS_ALIASES.contains(&name.as_str()));
+    mapped.chain(aliases).collect()
+}
 
-    if property_key == "background" {
-        return theme.properties.get("backgroundColor");
+/// Split `token/opacity` at its top-level slash, leaving slashes inside
+/// `[...]` and `(...)` alone (`[calc(100%/3)]`).
+fn split_modifier(token: &str) -> (&str, Option<&str>) {
+    let mut depth = 0i32;
+    for (i, ch) in token.char_indices() {
+        match ch {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth -= 1,
+            '/' if depth == 0 => return (&token[..i], Some(&token[i + 1..])),
+            _ => {}
+        }
     }
+    (token, None)
+}
 
-    if property_key == "from" || property_key == "via" || property_key == "to" {
-        return theme.properties.get("gradientColorStops");
+/// `@[...]` as written, once it fits the property: a color for color
+/// properties, a length where the property's collection holds lengths, and
+/// so on. Custom properties, properties without a mapping and `var()` or
+/// `calc()` expressions are taken as they are.
+fn arbitrary_value(property: &str, raw: &str, theme: &Theme) -> Result<String, String> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Err("empty arbitrary value @[]".to_string());
     }
+    let dynamic = ["var(", "calc(", "min(", "max(", "clamp(", "env("]
+        .iter()
+        .any(|f| raw.starts_with(f));
+    let mapping = match theme.index.property(property) {
+        Some(mapping) => Some(mapping),
+        None => theme.index.property(&normalize_property(property)),
+    };
+    let Some(collection) = mapping
+        .filter(|_| !dynamic && !property.starts_with("--"))
+        .map(|m| m.collection.as_str())
+    else {
+        return Ok(raw.to_string());
+    };
 
-    if property_key == "shadow" {
-        return theme.properties.get("boxShadow");
+    let parsed = crate::tokens::parse_value(collection, &serde_json::Value::String(raw.into()))
+        .map_err(|e| format!("@[{}] does not fit {}: {}", raw, property, e))?;
+    let mut kinds: Vec<&str> = theme
+        .collections
+        .get(collection)
+        .map(|tokens| tokens.values().map(|t| t.value.kind()).collect())
+        .unwrap_or_default();
+    kinds.sort();
+    kinds.dedup();
+    let zero = raw.parse::<f64>().is_ok_and(|n| n == 0.0);
+    if kinds.is_empty()
+        || kinds.contains(&"raw value")
+        || kinds.contains(&parsed.kind())
+        || (zero && kinds.contains(&"length"))
+    {
+        return Ok(raw.to_string());
     }
+    Err(format!(
+        "@[{}] is a {}, but {} takes {} values from {}",
+        raw,
+        parsed.kind(),
+        property,
+        kinds.join(" or "),
+        collection
+    ))
+}
 
-    if property_key == "offsetWidth" {
-        return theme.properties.get("ringOffsetWidth");
+/// `/[0.37]` or `/[37%]`, as an alpha from 0 to 1.
+fn arbitrary_opacity(raw: &str) -> Result<String, String> {
+    let raw = raw.trim();
+    let alpha = match raw.strip_suffix('%') {
+        Some(percent) => percent.parse::<f64>().ok().map(|p| p / 100.0),
+        None => raw.parse::<f64>().ok(),
+    };
+    match alpha {
+        Some(alpha) if (0.0..=1.0).contains(&alpha) => Ok(alpha.to_string()),
+        _ => Err(format!(
+            "arbitrary opacity '[{}]' must be a number from 0 to 1 or a percentage",
+            raw
+        )),
     }
+}
 
-    if property_key == "offsetColor" {
-        return theme.properties.get("ringOffsetColor");
+/// `token`, with an optional `/opacity`, through a property's mapping
+/// (overrides first), without falling back to colors.
+fn resolve_mapped_token(property: &str, token: &str, theme: &Theme) -> Result<String, String> {
+    let (base_token, opacity_token) = match token.split_once('/') {
+        Some((base, opacity)) => (base, Some(opacity)),
+        None => (token, None),
+    };
+    let name = normalize_property(property);
+    let css = match theme
+        .index
+        .property(property)
+        .and_then(|mapping| mapping.get(base_token))
+    {
+        Some(css) => css.to_string(),
+        None => {
+            let mapping = theme
+                .property(&name)
+                .ok_or_else(|| format!("Unknown property '{}'", property))?;
+            value_to_css(
+                resolve_from_mapping(base_token, mapping, theme)?,
+                &name,
+                base_token,
+            )?
+        }
+    };
+    match opacity_token {
+        Some(opacity) => color::inject_alpha(&css, &collection_css("opacity", opacity, theme)?),
+        None => Ok(css),
     }
+}
 
-    if property_key == "family" {
-        return theme.properties.get("fontFamily");
-    }
+/// Where a custom property's token comes from, since `--*` names no
+/// mapping of its own. A name ending in a property (`--radius`,
+/// `--card-padding`) uses that property when it has the token; otherwise
+/// the collection holding the token is used, preferring `colors`, then
+/// `spacing`, when several do.
+fn infer_custom_route<'a>(
+    property: &'a str,
+    token: &'a str,
+    theme: &'a Theme,
+) -> Result<Route<'a>, String> {
+    let base = token.split_once('/').map_or(token, |(base, _)| base);
 
-    if property_key == "size" {
-        return theme.properties.get("fontSize");
+    let name = property.trim_start_matches("--");
+    let suffixes =
+        std::iter::once(name).chain(name.match_indices('-').map(|(i, _)| &name[i + 1..]));
+    for suffix in suffixes {
+        if theme
+            .index
+            .property(suffix)
+            .is_some_and(|mapping| mapping.get(base).is_some())
+        {
+            return Ok(Route::Mapping(suffix, ChosenBy::CustomName));
+        }
     }
 
-    if property_key == "weight" {
-        return theme.properties.get("fontWeight");
+    let mut candidates: Vec<&str> = theme
+        .collections
+        .keys()
+        .map(String::as_str)
+        .filter(|collection| theme.index.collection_value(collection, base).is_some())
+        .collect();
+    candidates.sort();
+    let chosen = ["colors", "spacing"]
+        .into_iter()
+        .find(|preferred| candidates.contains(preferred))
+        .or_else(|| {
+            // Collections that agree on the value are not ambiguous.
+            let first = candidates.first()?;
+            let css = theme.index.collection_value(first, base);
+            candidates
+                .iter()
+                .all(|c| theme.index.collection_value(c, base) == css)
+                .then_some(*first)
+        });
+    match chosen {
+        Some(collection) => Ok(Route::Collection(collection, ChosenBy::TokenShape)),
+        None if candidates.is_empty() => {
+            let keys = theme
+                .collections
+                .values()
+                .flat_map(|tokens| tokens.keys().map(String::as_str));
+            Err(format!(
+                "Unknown token '{}' for {}{}",
+                base,
+                property,
+                suggest::did_you_mean(base, keys, "")
+            ))
+        }
+        None => Err(format!(
+            "Token '@{}' for {} is ambiguous: it is in {}; qualify it, e.g. @{}.{}",
+            base,
+            property,
+            candidates.join(", "),
+            candidates[0],
+            token
+        )),
     }
+}
+
+/// How a `@token` resolves for a property, step by step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// The CSS the token resolves to.
+    pub css: String,
+    /// How the mapping or collection was picked.
+    pub chosen_by: ChosenBy,
+    /// The theme property whose mapping was consulted, camelCase; `None`
+    /// for `@(...)` urls and tokens taken straight from a collection.
+    pub property: Option<String>,
+    /// The property an alias points at (`color` → `textColor`).
+    pub alias_for: Option<String>,
+    /// The collection the property maps to.
+    pub mapping: Option<String>,
+    /// Where the value came from.
+    pub origin: Origin,
+    /// The opacity of `@token/opacity` or `@token/[0.37]`.
+    pub opacity: Option<Opacity>,
+}
+
+/// How [`resolve_token`] picked the mapping or collection for a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChosenBy {
+    /// The declaration's property (also for `@(...)` urls).
+    Property,
+    /// The token's qualifier, as in `@spacing.4` or `@radius.lg`.
+    Qualifier,
+    /// The property a custom property is named after (`--radius`).
+    CustomName,
+    /// The collection holding a custom property's token.
+    TokenShape,
+    /// The `colors` collection, for a token the property's mapping lacks.
+    ColorsFallback,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Opacity {
+    Token(TokenOrigin),
+    Arbitrary(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    Url,
+    /// `@[...]`, as written.
+    Arbitrary(String),
+    /// An entry of the property's `overrides`.
+    Override {
+        property: String,
+        key: String,
+    },
+    Token(TokenOrigin),
+}
 
-    if property_key == "lineHeight" {
-        return theme.properties.get("lineHeight");
+/// A token of a collection and where the theme defined it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenOrigin {
+    pub collection: String,
+    pub key: String,
+    /// The theme file, e.g. `theme/tokens/colors.json`.
+    pub source: Option<String>,
+    /// The `{collection.path}` reference the value was taken from.
+    pub alias_of: Option<String>,
+}
+
+impl TokenOrigin {
+    fn new(collection: &str, key: &str, theme: &Theme) -> Self {
+        let token = theme.collections.get(collection).and_then(|c| c.get(key));
+        TokenOrigin {
+            collection: collection.to_string(),
+            key: key.to_string(),
+            source: token.and_then(|t| t.source.as_deref()).map(str::to_string),
+            alias_of: token.and_then(|t| t.alias_of.clone()),
+        }
     }
+}
 
-    if property_key == "radius" {
-        return theme.properties.get("borderRadius");
+/// Resolve `token` (without its `@`) for `property` exactly as a
+/// declaration would, and say which mapping, override and collection the
+/// value came from.
+pub fn explain_token(property: &str, token: &str, theme: &Theme) -> Result<Explanation, String> {
+    let (css, route) = resolve_token_route(property, token, theme)?;
+    let key = match split_qualifier(token, theme) {
+        Some((_, key)) => key,
+        None => token,
+    };
+    let (key, opacity) = match split_modifier(key) {
+        (base, Some(opacity)) if route != Route::Url => {
+            let opacity = match opacity.strip_prefix('[').and_then(|o| o.strip_suffix(']')) {
+                Some(raw) => Opacity::Arbitrary(arbitrary_opacity(raw)?),
+                None => Opacity::Token(TokenOrigin::new("opacity", opacity, theme)),
+            };
+            (base, Some(opacity))
+        }
+        _ => (key, None),
+    };
+
+    let mut explained = Explanation {
+        css,
+        chosen_by: ChosenBy::Property,
+        property: None,
+        alias_for: None,
+        mapping: None,
+        origin: Origin::Url,
+        opacity,
+    };
+    let (mapped, chosen_by) = match route {
+        Route::Url => return Ok(explained),
+        Route::Arbitrary => {
+            explained.origin = Origin::Arbitrary(key[1..key.len() - 1].trim().to_string());
+            return Ok(explained);
+        }
+        Route::Mapping(name, chosen_by) => (Some(name), chosen_by),
+        Route::Collection(collection, chosen_by) => {
+            explained.origin = Origin::Token(TokenOrigin::new(collection, key, theme));
+            let fallback_from = (chosen_by == ChosenBy::ColorsFallback).then_some(property);
+            (fallback_from, chosen_by)
+        }
+    };
+    explained.chosen_by = chosen_by;
+    let Some(name) = mapped else {
+        return Ok(explained);
+    };
+
+    let name = normalize_property(name);
+    let alias_for = theme.aliases.get(&name).cloned();
+    let target = alias_for.as_deref().unwrap_or(&name).to_string();
+    let mapping = theme.properties.get(&target);
+    if let Route::Mapping(..) = route {
+        explained.origin = match mapping {
+            Some(m) if m.overrides.contains_key(key) => Origin::Override {
+                property: target,
+                key: key.to_string(),
+            },
+            Some(m) => Origin::Token(TokenOrigin::new(&m.collection, key, theme)),
+            None => Origin::Token(TokenOrigin::new("colors", key, theme)),
+        };
     }
+    explained.mapping = mapping.map(|m| m.collection.clone());
+    explained.property = Some(name);
+    explained.alias_for = alias_for;
+    Ok(explained)
+}
 
-    if property_key.starts_with("border")
-        && property_key.ends_with("Radius")
-        && property_key != "borderRadius"
+/// Declarations implied by a `fontSize` token with metrics, e.g. the
+/// `line-height` paired with `@lg`.
+fn font_size_metrics(property_key: &str, token: &str, theme: &Theme) -> Vec<(String, String)> {
+    let (property_key, token) = match split_qualifier(token, theme) {
+        Some((Qualifier::Collection(collection), key)) => {
+            return match theme.token(collection, key) {
+                Ok(value) => metric_declarations(value),
+                Err(_) => Vec::new(),
+            };
+        }
+        Some((Qualifier::Property(name), key)) => (normalize_property(name), key),
+        None => (property_key.to_string(), token),
+    };
+    match theme
+        .property(&property_key)
+        .map(|mapping| resolve_from_mapping(token, mapping, theme))
     {
-        return theme.properties.get("borderRadius");
+        Some(Ok(value)) => metric_declarations(value),
+        _ => Vec::new(),
     }
+}
 
-    if let Some(m) = theme.properties.get(property_key) {
-        return Some(m);
-    }
+fn metric_declarations(value: &TokenValue) -> Vec<(String, String)> {
+    let TokenValue::FontSize {
+        line_height,
+        letter_spacing,
+        font_weight,
+        ..
+    } = value
+    else {
+        return Vec::new();
+    };
 
-    None
+    [
+        ("line-height", line_height),
+        ("letter-spacing", letter_spacing),
+        ("font-weight", font_weight),
+    ]
+    .into_iter()
+    .filter_map(|(property, value)| value.clone().map(|v| (property.to_string(), v)))
+    .collect()
 }
 
-fn resolve_from_mapping(
+fn resolve_from_mapping<'a>(
     token: &str,
-    mapping: &crate::theme::PropertyMapping,
-    theme: &Theme,
-) -> Result<Value, String> {
+    mapping: &'a crate::theme::PropertyMapping,
+    theme: &'a Theme,
+) -> Result<&'a TokenValue, String> {
     if let Some(override_value) = mapping.overrides.get(token) {
-        return Ok(override_value.clone());
+        return Ok(override_value);
     }
 
     resolve_from_collection(&mapping.collection, token, theme)
 }
 
-fn resolve_from_collection(
+fn resolve_from_collection<'a>(
     collection_name: &str,
     token: &str,
-    theme: &Theme,
-) -> Result<Value, String> {
-    let collection = theme
-        .collections
-        .get(collection_name)
-        .ok_or_else(|| format!("Unknown collection '{}'", collection_name))?;
-
-    // direct key match (for flat keys containing dashes)
-    if let Value::Object(map) = collection {
-        if let Some(val) = map.get(token) {
-            return Ok(val.clone());
-        }
-    }
-
-    let mut current = collection;
-    for part in token.split('-') {
-        match current {
-            Value::Object(map) => {
-                current = map
-                    .get(part)
-                    .ok_or_else(|| format!("Unknown token '{}' in {}", token, collection_name))?;
-            }
-            _ => {
-                return Err(format!(
-                    "Token '{}' does not map into collection '{}'",
-                    token, collection_name
-                ));
-            }
-        }
-    }
-    Ok(current.clone())
+    theme: &'a Theme,
+) -> Result<&'a TokenValue, String> {
+    theme.token(collection_name, token)
 }
 
 fn normalize_property(property: &str) -> String {
@@ -1659,89 +2799,15 @@ fn normalize_property(property: &str) -> String {
     result
 }
 
-fn value_to_css(value: &Value, property: &str, token: &str) -> Result<String, String> {
-    if let Some(url) = value
-        .as_str()
-        .and_then(|s| s.strip_prefix("@("))
-        .and_then(|s| s.strip_suffix(')'))
-    {
-        return Ok(format!("url(\"{}\")", url));
-    }
-
-    match value {
-        Value::String(s) => Ok(s.clone()),
-        Value::Number(n) => Ok(n.to_string()),
-        Value::Bool(b) => Ok(b.to_string()),
-        Value::Array(items) => {
-            if let Some(Value::String(s)) = items.get(0) {
-                Ok(s.clone())
-            } else {
-                serde_json::to_string(value).map_err(|e| {
-                    format!(
-                        "Cannot stringify token '@{}' for {}: {}",
-                        token, property, e
-                    )
-                })
-            }
-        }
-        Value::Object(_) => serde_json::to_string(value).map_err(|e| {
-            format!(
-                "Cannot stringify token '@{}' for {}: {}",
-                token, property, e
-            )
-        }),
-        Value::Null => Err(format!("Token '@{}' for {} is null", token, property)),
-    }
-}
-
-fn inject_alpha(base: Value, opacity: Value) -> Result<String, String> {
-    let base_str = match base {
-        Value::String(s) => s,
-        other => {
-            serde_json::to_string(&other).map_err(|e| format!("Cannot stringify color: {}", e))?
-        }
-    };
-
-    let opacity_str = match opacity {
-        Value::String(s) => s,
-        Value::Number(n) => n.to_string(),
-        other => {
-            serde_json::to_string(&other).map_err(|e| format!("Cannot stringify opacity: {}", e))?
-        }
-    };
-
-    if base_str.starts_with("oklch(") || base_str.contains('(') {
-        let base = base_str.trim_end_matches(')');
-        return Ok(format!("{} / {})", base, opacity_str));
-    }
-
-    if base_str.starts_with('#') {
-        return hex_to_rgba(&base_str, &opacity_str);
-    }
-
-    Err(format!("Unsupported color format: {}", base_str))
-}
-
-fn hex_to_rgba(hex: &str, opacity: &str) -> Result<String, String> {
-    let hex = hex.trim_start_matches('#');
-
-    let (r, g, b) = match hex.len() {
-        6 => {
-            let r = u8::from_str_radix(&hex[0..2], 16).unwrap();
-            let g = u8::from_str_radix(&hex[2..4], 16).unwrap();
-            let b = u8::from_str_radix(&hex[4..6], 16).unwrap();
-            (r, g, b)
-        }
-        3 => {
-            let r = u8::from_str_radix(&hex[0..1].repeat(2), 16).unwrap();
-            let g = u8::from_str_radix(&hex[1..2].repeat(2), 16).unwrap();
-            let b = u8::from_str_radix(&hex[2..3].repeat(2), 16).unwrap();
-            (r, g, b)
-        }
-        _ => return Err(format!("Invalid hex color: {}", hex)),
-    };
-
-    Ok(format!("rgba({},{},{},{})", r, g, b, opacity))
+fn value_to_css(value: &TokenValue, property: &str, token: &str) -> Result<String, String> {
+    crate::tokens::value_css(value, property).ok_or_else(|| {
+        format!(
+            "Token '@{}' for {} is a {} definition, not a value",
+            token,
+            property,
+            value.kind()
+        )
+    })
 }
 
 #[cfg(test)]
@@ -1758,6 +2824,18 @@ mod tests {
         emitter::emit_css(&resolved)
     }
 
+    fn render_css_for(input: &str, query: &str) -> String {
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let theme = Theme::load_from_dir(&theme_dir).expect("load theme");
+        let stylesheet = parser::parse(input).expect("parse rc");
+        let resolved = resolve(stylesheet, &theme).expect("resolve");
+        let options = emitter::EmitOptions {
+            targets: query.parse().expect("parse targets"),
+            ..emitter::EmitOptions::default()
+        };
+        emitter::emit_css_with_options(&resolved, &options)
+    }
+
     #[test]
     fn grid_cols_and_gap() {
         let css = render_css(".demo { grid: cols(4) gap(@2); }");
@@ -1789,14 +2867,72 @@ mod tests {
         assert!(css.contains("grid-template-rows: 1rem auto 1fr;"));
     }
 
+    #[test]
+    fn background_size_keywords_resolve() {
+        let css = render_css(".demo { background-size: @cover; }");
+        assert!(css.contains("background-size: cover;"), "{css}");
+    }
+
     #[test]
     fn color_functions() {
+        let css = render_css_for(
+            ".demo { color: mix(#ff0000, #0000ff, 25%); background: lighten(#000000, 20%); border-color: darken(@slate-500, 10%, oklch); outline-color: alpha(var(--brand), 50%); }",
+            "last 2 chrome versions",
+        );
+        assert!(css.contains("color: #bf0040;"));
+        assert!(css.contains("background: #333333;"));
+        assert!(css.contains("border-color: oklch(49.86% 0.0414 257.417);"));
+        assert!(css.contains("outline-color: color-mix(in srgb, var(--brand) 50%, transparent);"));
+    }
+
+    #[test]
+    fn typed_tokens_keep_full_values() {
+        let css = render_css(
+            ".demo { font: @sans @lg; filter: blur(@sm) brightness(@100) contrast(@100) hue-rotate(@0) saturate(@100) drop-shadow(@md); }",
+        );
+        assert!(css.contains("font-family: ui-sans-serif, system-ui, sans-serif,"));
+        assert!(css.contains("font-size: 1.125rem;\n    line-height: 1.75rem;"));
+        assert!(css.contains(
+            "drop-shadow(0 4px 3px rgb(0 0 0 / 0.07)) drop-shadow(0 2px 2px rgb(0 0 0 / 0.06))"
+        ));
+
+        let explicit = render_css(".demo { font-size: @lg; line-height: 1; }");
+        assert!(explicit.contains("line-height: 1;"));
+        assert!(!explicit.contains("1.75rem"));
+    }
+
+    #[test]
+    fn color_adjustments() {
         let css = render_css(
-            ".demo { color: mix(@blue-500, @red-400, 40%); background: lighten(@blue-500, 20%); border-color: darken(@slate-500, 10%); }",
+            ".demo { color: complement(#ff0000); background: contrast-color(#1e293b); fill: adjust-hue(#ff0000, 120deg); stroke: desaturate(#ff0000, 100%); backdrop-filter: saturate(150%); }",
+        );
+        assert!(css.contains("color: #00ffff;"));
+        assert!(css.contains("background: white;"));
+        assert!(css.contains("fill: #00ff00;"));
+        assert!(css.contains("stroke: #808080;"));
+        assert!(css.contains("backdrop-filter: saturate(150%);"));
+    }
+
+    #[test]
+    fn color_function_errors() {
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let theme = Theme::load_from_dir(&theme_dir).expect("load theme");
+        let resolve_err = |input: &str| {
+            let stylesheet = parser::parse(input).expect("parse rc");
+            resolve(stylesheet, &theme).unwrap_err()
+        };
+
+        let err = resolve_err(".demo {\n  color: #fff lighten(#fff);\n}");
+        assert!(err.starts_with("2:15:"), "{}", err);
+        assert!(err.contains("lighten() got 1 argument"));
+        assert!(
+            resolve_err(".demo { color: mix(#fff, 12px); }")
+                .contains("mix() expects a color as argument 2, got '12px'")
+        );
+        assert!(
+            resolve_err(".demo { color: tint(#fff, 20%, cmyk); }")
+                .contains("unknown color space 'cmyk'")
         );
-        assert!(css.contains("color-mix(in srgb"));
-        assert!(css.contains("background: color-mix(in srgb, white 20%"));
-        assert!(css.contains("border-color: color-mix(in srgb, black 10%"));
     }
 
     #[test]
@@ -1887,6 +3023,200 @@ mod tests {
         assert!(err.contains("unknown variable '$missing'"));
     }
 
+    #[test]
+    fn unknown_names_suggest_close_matches() {
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let theme = Theme::load_from_dir(&theme_dir).expect("load theme");
+        let error = |input: &str| match parser::parse(input) {
+            Ok(sheet) => resolve(sheet, &theme).expect_err("expected an error"),
+            Err(e) => e,
+        };
+
+        let err = error(".a { color: @blu-500; }");
+        assert!(
+            err.ends_with("Unknown token 'blu-500' in colors\n  help: did you mean 'blue-500'?"),
+            "{err}"
+        );
+        // Suggestions for a spacing property come from spacing as well as colors.
+        let err = error(".a { padding: @0.55; }");
+        assert!(
+            err.contains("Unknown token '0.55' for padding (not in spacing or colors)"),
+            "{err}"
+        );
+        assert!(err.contains("help: did you mean '0.5'"), "{err}");
+        let err = error(".a { margn: @4; }");
+        assert!(err.ends_with("help: did you mean 'margin'?"), "{err}");
+        let err = error("$small: @2;\n.a { padding: $smal; }");
+        assert!(
+            err.ends_with("unknown variable '$smal'\n  help: did you mean '$small'?"),
+            "{err}"
+        );
+        let err = error(".a { ring: colr=@red-500 width=@2; }");
+        assert!(
+            err.starts_with("Unknown part 'colr' for shorthand 'ring'"),
+            "{err}"
+        );
+        assert!(err.ends_with("help: did you mean 'color'?"), "{err}");
+        let err = error("$card {\n    padding: @4;\n}\n.a {\n    apply: $crad;\n}\n");
+        assert!(
+            err.ends_with("unknown mixin '$crad'\n  help: did you mean '$card'?"),
+            "{err}"
+        );
+        // Nothing close enough: no help line.
+        let err = error(".a { color: @zzz; }");
+        assert!(!err.contains("help:"), "{err}");
+        // A real property the theme does not map is not a typo of `order`.
+        let err = error(".a { border: @2; }");
+        assert!(err.ends_with("Unknown property 'border'"), "{err}");
+        // Nor is `top` a typo of the gradient shorthand `to`.
+        let err = error(".a { top: @4; }");
+        assert!(err.ends_with("Unknown property 'top'"), "{err}");
+        let err = error(".a { colr: @red-500; }");
+        assert!(err.ends_with("help: did you mean 'color'?"), "{err}");
+    }
+
+    #[test]
+    fn qualified_tokens_and_custom_property_inference() {
+        let css = render_css(
+            ".a {\n    padding: @spacing.4;\n    margin: @spacing.0.5 @border-radius.2xl;\n    border-radius: @radius.lg;\n    color: @colors.blue-500/50;\n    background-image: @(img/@blue-500.svg);\n    --gap: @4;\n    --radius: @lg;\n    --card-padding: @2;\n    --ring: @blue-500/50;\n}\n",
+        );
+        assert!(css.contains("padding: 1rem;"), "{css}");
+        assert!(css.contains("margin: 0.125rem 1rem;"), "{css}");
+        assert!(css.contains("border-radius: 0.5rem;"), "{css}");
+        assert!(
+            css.contains("color: oklch(62.3% 0.214 259.815 / 0.5);"),
+            "{css}"
+        );
+        assert!(
+            css.contains("url(\"img/oklch(62.3% 0.214 259.815).svg\")"),
+            "{css}"
+        );
+        // By the token's shape, then by the custom property's name.
+        assert!(css.contains("--gap: 1rem;"), "{css}");
+        assert!(css.contains("--radius: 0.5rem;"), "{css}");
+        assert!(css.contains("--card-padding: 0.5rem;"), "{css}");
+        // The default targets lack OKLCH, so the custom property is sRGB.
+        assert!(css.contains("--ring: rgba(43, 127, 255, 0.5);"), "{css}");
+
+        let qualified = render_css(".a {\n    font-size: @fontSize.lg;\n}\n");
+        assert!(qualified.contains("line-height: 1.75rem;"), "{qualified}");
+
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let theme = Theme::load_from_dir(&theme_dir).expect("load theme");
+        let err = resolve_token("--x", "lg", &theme).unwrap_err();
+        assert!(
+            err.starts_with(
+                "Token '@lg' for --x is ambiguous: it is in backdropBlur, borderRadius,"
+            ),
+            "{err}"
+        );
+        assert!(err.ends_with("qualify it, e.g. @backdropBlur.lg"), "{err}");
+        // A qualifier is not a fallback: spacing has no blue-500.
+        let err = resolve_token("color", "spacing.blue-500", &theme).unwrap_err();
+        assert!(
+            err.starts_with("Unknown token 'blue-500' in spacing"),
+            "{err}"
+        );
+
+        let explained = explain_token("--x", "spacing.4", &theme).unwrap();
+        assert_eq!(explained.chosen_by, ChosenBy::Qualifier);
+        assert!(
+            matches!(&explained.origin, Origin::Token(t) if t.collection == "spacing" && t.key == "4")
+        );
+    }
+
+    #[test]
+    fn arbitrary_values_and_negative_tokens() {
+        let css = render_css(
+            ".a {\n    font-size: @[13px];\n    color: @blue-500/[0.37];\n    background-color: @blue-500/[37%];\n    border-color: @[#ff0000]/50;\n    width: @[calc(100%/3)];\n    margin: -@4 -@[var(--x)];\n    height: calc(100% - @4);\n    --inset: -@[2px];\n}\n",
+        );
+        assert!(css.contains("font-size: 13px;"), "{css}");
+        assert!(
+            css.contains("color: oklch(62.3% 0.214 259.815 / 0.37);"),
+            "{css}"
+        );
+        assert!(
+            css.contains("background-color: oklch(62.3% 0.214 259.815 / 0.37);"),
+            "{css}"
+        );
+        assert!(css.contains("border-color: rgba(255,0,0,0.5);"), "{css}");
+        assert!(css.contains("width: calc(100%/3);"), "{css}");
+        assert!(css.contains("margin: -1rem calc(-1 * var(--x));"), "{css}");
+        assert!(css.contains("height: calc(100% - 1rem);"), "{css}");
+        assert!(css.contains("--inset: -2px;"), "{css}");
+        // A `-` after a space is a sign, even inside `calc()`.
+        let css = render_css(
+            ".a {\n    width: calc(1rem -@4);\n    height: calc(1rem -@[var(--x)]);\n    max-width: calc(1rem-@[var(--x)]);\n}\n",
+        );
+        assert!(css.contains("width: calc(1rem -1rem);"), "{css}");
+        assert!(
+            css.contains("height: calc(1rem calc(-1 * var(--x)));"),
+            "{css}"
+        );
+        assert!(css.contains("max-width: calc(1rem-var(--x));"), "{css}");
+
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let theme = Theme::load_from_dir(&theme_dir).expect("load theme");
+        let error = |input: &str| {
+            let stylesheet = parser::parse(input).expect("parse rc");
+            resolve(stylesheet, &theme).expect_err("expected token error")
+        };
+        let err = error(".a { padding: @[red]; }");
+        assert!(
+            err.contains("@[red] is a color, but padding takes length values from spacing"),
+            "{err}"
+        );
+        let err = error(".a { color: @[13px]; }");
+        assert!(err.contains("@[13px] does not fit color"), "{err}");
+        let err = error(".a { color: -@blue-500; }");
+        assert!(err.contains("-@blue-500 cannot be negated"), "{err}");
+        let err = error(".a { color: @blue-500/[1.5]; }");
+        assert!(err.contains("must be a number from 0 to 1"), "{err}");
+        let err = error(".a { width: @[13px; }");
+        assert!(err.contains("unterminated @[...] value"), "{err}");
+    }
+
+    #[test]
+    fn token_arithmetic_folds_at_compile_time() {
+        let css = render_css(
+            ".a {\n    padding: @4 * 2 @2;\n    margin: @4 + @2 -@1;\n    width: max(@2, 10px);\n    height: calc(@4 / 4);\n    --gap: @4 + 1px;\n}\n",
+        );
+        assert!(css.contains("padding: 2rem 0.5rem;"), "{css}");
+        assert!(css.contains("margin: 1.5rem -0.25rem;"), "{css}");
+        assert!(css.contains("width: max(0.5rem, 10px);"), "{css}");
+        assert!(css.contains("height: 0.25rem;"), "{css}");
+        assert!(css.contains("--gap: calc(1rem + 1px);"), "{css}");
+
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let theme = Theme::load_from_dir(&theme_dir).expect("load theme");
+        let stylesheet = parser::parse(".a { padding: @4 + @blue-500; }").expect("parse rc");
+        let err = resolve(stylesheet, &theme).expect_err("expected arithmetic error");
+        assert!(
+            err.contains("RCSS arithmetic error: cannot add a color (@blue-500) to a length (@4)"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn rem_function_uses_the_root_font_size() {
+        let css =
+            render_css(".a {\n    padding: rem(24px) rem(0);\n    margin: rem(8px) * 2;\n}\n");
+        assert!(css.contains("padding: 1.5rem 0;"), "{css}");
+        assert!(css.contains("margin: 1rem;"), "{css}");
+
+        let css = render_css("%base-18\n.a {\n    width: rem(36px) + @4;\n}\n");
+        assert!(css.contains("width: calc(2rem + 1rem);"), "{css}");
+
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let theme = Theme::load_from_dir(&theme_dir).expect("load theme");
+        let stylesheet = parser::parse(".a { width: rem(2em); }").expect("parse rc");
+        let err = resolve(stylesheet, &theme).expect_err("expected rem error");
+        assert!(
+            err.contains("rem() takes a px length, not a length (2em)"),
+            "{err}"
+        );
+    }
+
     #[test]
     fn nested_selectors_multi_level() {
         let css = render_css(
@@ -1920,4 +3250,92 @@ mod tests {
         assert!(css.contains(".card .inner {"));
         assert!(css.contains(".card:hover {"));
     }
+
+    #[test]
+    fn presets_come_from_the_theme() {
+        let css = render_css("%reading reduced-motion\n%dark-ui\n.a {\n    color: red;\n}\n");
+        assert!(css.contains("max-width: 65ch;"), "{css}");
+        assert!(css.contains("@media (prefers-reduced-motion: reduce)"));
+        assert!(css.contains("@media (prefers-color-scheme: dark)"));
+        // A named root preset replaces the default base.
+        assert!(!css.contains("font-family: sans-serif"));
+
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let theme = Theme::load_from_dir(&theme_dir).expect("load theme");
+        let sheet = parser::parse("%base-16 readng\n").unwrap();
+        let err = resolve(sheet, &theme).unwrap_err();
+        assert!(err.starts_with("1:10: unknown preset 'readng'"), "{err}");
+    }
+
+    #[test]
+    fn mode_tokens_add_dark_blocks() {
+        let css = render_css_for(
+            ".panel {\n    background-color: @surface;\n    padding: @4;\n    screen(@md) {\n        color: @foreground;\n    }\n    dark {\n        background-color: @blue-900;\n    }\n}\n",
+            "chrome 120",
+        );
+        assert!(
+            css.contains(".panel {\n    background-color: #fff;\n    padding: 1rem;\n}"),
+            "{css}"
+        );
+        // The hand-written dark block wins over the generated value.
+        assert!(css.contains("@media (prefers-color-scheme: dark) {\n  .panel {\n    background-color: oklch(37.9% 0.146 265.522);\n  }"), "{css}");
+        assert!(css.contains("@media (min-width: 48rem) and (prefers-color-scheme: dark) {\n  .panel {\n    color: oklch(98.5% 0 0);"), "{css}");
+
+        let css = render_css_for(".card {\n    color: @foreground;\n}\n", "chrome 120");
+        assert!(
+            css.contains(
+                "@media (prefers-color-scheme: dark) {\n  .card {\n    color: oklch(98.5% 0 0);"
+            ),
+            "{css}"
+        );
+    }
+
+    #[test]
+    fn explain_token_names_the_mapping_override_and_collection() {
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let mut theme = Theme::load_from_dir(&theme_dir).expect("load theme");
+
+        let explained = explain_token("color", "blue-500/50", &theme).unwrap();
+        assert_eq!(
+            explained.css,
+            resolve_token("color", "blue-500/50", &theme).unwrap()
+        );
+        assert_eq!(explained.alias_for.as_deref(), Some("textColor"));
+        assert_eq!(explained.mapping.as_deref(), Some("colors"));
+        let Origin::Token(token) = &explained.origin else {
+            panic!("expected a collection token: {:?}", explained.origin);
+        };
+        assert_eq!(
+            (token.collection.as_str(), token.key.as_str()),
+            ("colors", "blue-500")
+        );
+        assert!(token.source.is_some());
+        assert!(matches!(explained.opacity, Some(Opacity::Token(t)) if t.key == "50"));
+
+        // Tokens the property's collection lacks fall back to colors.
+        let explained = explain_token("padding", "red-500", &theme).unwrap();
+        assert_eq!(explained.mapping.as_deref(), Some("spacing"));
+        assert!(matches!(&explained.origin, Origin::Token(t) if t.collection == "colors"));
+
+        theme
+            .properties
+            .get_mut("lineHeight")
+            .unwrap()
+            .overrides
+            .insert("snug".to_string(), TokenValue::Number("1.3".to_string()));
+        theme.rebuild_index();
+        let explained = explain_token("line-height", "snug", &theme).unwrap();
+        assert_eq!(explained.css, "1.3");
+        assert_eq!(
+            explained.origin,
+            Origin::Override {
+                property: "lineHeight".to_string(),
+                key: "snug".to_string()
+            }
+        );
+        assert_eq!(
+            explain_token("nope", "4", &theme).unwrap_err(),
+            "Unknown property 'nope'"
+        );
+    }
 }
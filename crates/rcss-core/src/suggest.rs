@@ -0,0 +1,129 @@
+/// Help text for an unknown name: `\n  help: did you mean 'blue-500'?` when
+/// some candidates are a few edits away, otherwise empty. `sigil` is put
+/// before each suggestion (`$` for variables).
+pub(crate) fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    sigil: &str,
+) -> String {
+    let closest = closest(name, candidates);
+    let Some((last, rest)) = closest.split_last() else {
+        return String::new();
+    };
+    let quoted = |s: &str| format!("'{}{}'", sigil, s);
+    let list = match rest {
+        [] => quoted(last),
+        _ => format!(
+            "{} or {}",
+            rest.iter()
+                .map(|s| quoted(s))
+                .collect::<Vec<_>>()
+                .join(", "),
+            quoted(last)
+        ),
+    };
+    format!("\n  help: did you mean {}?", list)
+}
+
+/// Up to three candidates at the smallest edit distance from `name`,
+/// alphabetically; none when even the closest needs more than a quarter
+/// of the name changed.
+pub(crate) fn closest<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let limit = (name.len() / 4).max(1);
+
+    let mut best = usize::MAX;
+    let mut found: Vec<&str> = Vec::new();
+    for candidate in candidates {
+        let other: Vec<char> = candidate.to_lowercase().chars().collect();
+        if other == name || other.len().abs_diff(name.len()) > limit {
+            continue;
+        }
+        let distance = edit_distance(&name, &other);
+        if distance > limit {
+            continue;
+        }
+        if distance < best {
+            best = distance;
+            found.clear();
+        }
+        if distance == best && !found.contains(&candidate) {
+            found.push(candidate);
+        }
+    }
+    found.sort();
+    found.truncate(3);
+    found
+}
+
+/// Whether `name` is the leading part of a known dashed name (`border` of
+/// `border-color`): a real name the theme just does not map, rather than a
+/// misspelling of a neighbour (`order`).
+pub(crate) fn names_a_family<'a>(name: &str, known: impl IntoIterator<Item = &'a str>) -> bool {
+    known.into_iter().any(|other| {
+        other
+            .strip_prefix(name)
+            .is_some_and(|rest| rest.starts_with('-'))
+    })
+}
+
+/// Edits (insert, delete, substitute, swap two neighbours) turning `a`
+/// into `b`.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let width = b.len() + 1;
+    let mut rows = vec![0; (a.len() + 1) * width];
+    for (i, row) in rows.chunks_mut(width).enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[..width].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[(i - 1) * width + j] + 1)
+                .min(rows[i * width + j - 1] + 1)
+                .min(rows[(i - 1) * width + j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[(i - 2) * width + j - 2] + 1);
+            }
+            rows[i * width + j] = distance;
+        }
+    }
+    rows[a.len() * width + b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_the_nearest_names_only() {
+        let colors = ["blue-500", "blue-600", "slate-500", "red-500"];
+        assert_eq!(closest("blu-500", colors), ["blue-500"]);
+        assert_eq!(closest("bleu-500", colors), ["blue-500"]);
+        assert_eq!(closest("blue-550", colors), ["blue-500"]);
+        assert_eq!(closest("green-500", colors), Vec::<&str>::new());
+
+        assert_eq!(
+            did_you_mean("blue-5O0", colors, ""),
+            "\n  help: did you mean 'blue-500'?"
+        );
+        assert_eq!(
+            did_you_mean("gapp", ["gap", "grid", "gaps"], "$"),
+            "\n  help: did you mean '$gap' or '$gaps'?"
+        );
+        assert_eq!(did_you_mean("teal-500", colors, ""), "");
+    }
+
+    #[test]
+    fn family_names_are_not_typos() {
+        let properties = ["order", "border-color", "border-width", "padding"];
+        assert!(names_a_family("border", properties));
+        assert!(!names_a_family("bord", properties));
+        assert!(!names_a_family("paddin", properties));
+    }
+}
@@ -0,0 +1,264 @@
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+
+/// Scales other Tailwind keys are derived from; a group holding one is named
+/// after it.
+const SHARED_SCALES: [&str; 2] = ["colors", "spacing"];
+
+/// Build `tokens.json` from a Tailwind-resolved theme:
+///
+/// 1. drop identity entries (`"auto": "auto"`) from every property's map;
+/// 2. group properties whose remaining maps are identical into one
+///    collection;
+/// 3. point each property at its collection.
+///
+/// `previous` is the current `tokens.json`, if any. Collections keep the name
+/// they have there while their properties still share one, and its
+/// `aliases` and property `overrides` (which Tailwind does not know about)
+/// are carried over. Identity entries the previous collection kept
+/// (`"cover": "cover"` for `@cover`) are kept too while one of its
+/// properties still has them. Output keys are sorted, so the result only
+/// depends on the inputs.
+pub fn generate(source: &Value, previous: Option<&Value>) -> Result<Value, String> {
+    let source = source
+        .as_object()
+        .ok_or("expected the resolved Tailwind theme to be an object of properties")?;
+
+    // Stripped map (as canonical JSON text) → properties sharing it.
+    let mut groups: BTreeMap<String, (Value, Vec<&str>)> = BTreeMap::new();
+    for (property, value) in source {
+        let stripped = strip_identity(value);
+        groups
+            .entry(stripped.to_string())
+            .or_insert_with(|| (stripped, Vec::new()))
+            .1
+            .push(property);
+    }
+
+    let previous_properties = previous
+        .and_then(|p| p.get("properties"))
+        .and_then(Value::as_object);
+    let previous_collections = previous
+        .and_then(|p| p.get("collections"))
+        .and_then(Value::as_object);
+    let mut collections = Map::new();
+    let mut properties = Map::new();
+    for (mut tokens, members) in groups.into_values() {
+        let name = previous_name(&members, previous_properties, &collections)
+            .unwrap_or_else(|| collection_name(&members).to_string());
+        if let (Some(kept), Some(tokens)) = (
+            previous_collections
+                .and_then(|c| c.get(&name))
+                .and_then(Value::as_object),
+            tokens.as_object_mut(),
+        ) {
+            for (key, value) in kept {
+                let identity = value.as_str() == Some(key.as_str());
+                let in_source = members
+                    .iter()
+                    .any(|member| source[*member].get(key) == Some(value));
+                if identity && in_source && !tokens.contains_key(key) {
+                    tokens.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        for property in &members {
+            let mut mapping = json!({ "collection": name });
+            if let Some(overrides) = previous_properties
+                .and_then(|p| p.get(*property))
+                .and_then(|m| m.get("overrides"))
+                .filter(|o| o.as_object().is_some_and(|o| !o.is_empty()))
+            {
+                mapping["overrides"] = overrides.clone();
+            }
+            properties.insert(property.to_string(), mapping);
+        }
+        collections.insert(name, tokens);
+    }
+
+    let aliases = previous
+        .and_then(|p| p.get("aliases"))
+        .cloned()
+        .unwrap_or_else(|| json!({}));
+    Ok(json!({
+        "collections": collections,
+        "properties": properties,
+        "aliases": aliases,
+    }))
+}
+
+/// `tokens.json` text as [`generate`] writes it.
+pub fn to_json(tokens: &Value) -> String {
+    let mut text = serde_json::to_string_pretty(tokens).expect("JSON values serialize");
+    text.push('\n');
+    text
+}
+
+/// Top-level entries (`collections.spacing`, `properties.scale`) that differ
+/// between a committed `tokens.json` and a freshly generated one.
+pub fn stale_entries(committed: &Value, generated: &Value) -> Vec<String> {
+    let mut stale = Vec::new();
+    for section in ["collections", "properties", "aliases"] {
+        let empty = Map::new();
+        let old = committed
+            .get(section)
+            .and_then(Value::as_object)
+            .unwrap_or(&empty);
+        let new = generated
+            .get(section)
+            .and_then(Value::as_object)
+            .unwrap_or(&empty);
+        let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            if old.get(key) != new.get(key) {
+                stale.push(format!("{}.{}", section, key));
+            }
+        }
+    }
+    stale
+}
+
+fn strip_identity(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(key, value)| value.as_str() != Some(key.as_str()))
+                .map(|(key, value)| (key.clone(), strip_identity(value)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// The collection all `members` pointed at in the previous `tokens.json`,
+/// unless it is taken or names a property of another group.
+fn previous_name(
+    members: &[&str],
+    previous: Option<&Map<String, Value>>,
+    taken: &Map<String, Value>,
+) -> Option<String> {
+    let previous = previous?;
+    let mut names = members.iter().map(|member| {
+        previous
+            .get(*member)
+            .and_then(|mapping| mapping.get("collection"))
+            .and_then(Value::as_str)
+    });
+    let first = names.next()??;
+    let shared = names.all(|name| name == Some(first));
+    let foreign = previous.contains_key(first) && !members.contains(&first);
+    (shared && !foreign && !taken.contains_key(first)).then(|| first.to_string())
+}
+
+/// A readable name for a new group: a shared scale it contains (`colors`),
+/// else the member the most others end with (`opacity` for
+/// `backgroundOpacity`), else the shortest name, alphabetically first.
+fn collection_name<'a>(members: &[&'a str]) -> &'a str {
+    if let Some(scale) = members.iter().find(|m| SHARED_SCALES.contains(m)) {
+        return scale;
+    }
+    let derived = |name: &str| {
+        members
+            .iter()
+            .filter(|other| {
+                other.len() > name.len() && other.to_lowercase().ends_with(&name.to_lowercase())
+            })
+            .count()
+    };
+    members
+        .iter()
+        .copied()
+        .min_by_key(|name| (std::cmp::Reverse(derived(name)), name.len(), *name))
+        .expect("groups have members")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_maps_share_a_named_collection() {
+        let source = json!({
+            "opacity": { "0": "0", "50": "0.5" },
+            "backgroundOpacity": { "0": "0", "50": "0.5" },
+            "cursor": { "auto": "auto", "pointer": "pointer" },
+            "zIndex": { "10": "10" },
+            "textColor": { "red": "#f00", "current": "currentcolor" },
+            "colors": { "red": "#f00", "current": "currentcolor" },
+            "size": { "full": "100%" }
+        });
+        let tokens = generate(&source, None).unwrap();
+        assert_eq!(
+            tokens["collections"],
+            json!({
+                "opacity": { "50": "0.5" },
+                "colors": { "red": "#f00", "current": "currentcolor" },
+                "cursor": {},
+                "size": { "full": "100%" }
+            })
+        );
+        assert_eq!(
+            tokens["properties"]["backgroundOpacity"]["collection"],
+            "opacity"
+        );
+        assert_eq!(tokens["properties"]["textColor"]["collection"], "colors");
+        assert_eq!(tokens["properties"]["zIndex"]["collection"], "cursor");
+
+        // Names, aliases and overrides survive regeneration.
+        let previous = json!({
+            "properties": {
+                "cursor": { "collection": "data" },
+                "zIndex": { "collection": "data" },
+                "size": { "collection": "size", "overrides": { "half": "50%" } }
+            },
+            "aliases": { "z": "zIndex" }
+        });
+        let tokens = generate(&source, Some(&previous)).unwrap();
+        assert_eq!(tokens["properties"]["zIndex"]["collection"], "data");
+        assert_eq!(tokens["collections"]["data"], json!({}));
+        assert_eq!(
+            tokens["properties"]["size"]["overrides"],
+            json!({ "half": "50%" })
+        );
+        assert_eq!(tokens["aliases"], json!({ "z": "zIndex" }));
+        assert_eq!(
+            stale_entries(&previous, &tokens),
+            vec![
+                "collections.colors",
+                "collections.data",
+                "collections.opacity",
+                "collections.size",
+                "properties.backgroundOpacity",
+                "properties.colors",
+                "properties.opacity",
+                "properties.textColor",
+            ]
+        );
+    }
+
+    #[test]
+    fn identity_entries_the_previous_file_kept_survive() {
+        let source = json!({
+            "backgroundSize": { "auto": "auto", "cover": "cover", "contain": "contain" },
+            "zIndex": { "auto": "auto" }
+        });
+        let previous = json!({
+            "collections": { "data": { "cover": "cover", "contain": "contain", "fill": "fill" } },
+            "properties": {
+                "backgroundSize": { "collection": "data" },
+                "zIndex": { "collection": "data" }
+            }
+        });
+        let tokens = generate(&source, Some(&previous)).unwrap();
+        assert_eq!(
+            tokens["collections"],
+            json!({ "data": { "cover": "cover", "contain": "contain" } })
+        );
+        assert_eq!(
+            generate(&source, None).unwrap()["collections"],
+            json!({ "zIndex": {} })
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Browserslist-style target set resolved against the bundled compatibility
+/// table in `data/compat.json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Targets {
+    query: String,
+    browsers: BTreeSet<(String, Version)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Version(u32, u32);
+
+#[derive(Debug, Deserialize)]
+struct CompatData {
+    browsers: HashMap<String, BrowserData>,
+    #[serde(default)]
+    esr: HashMap<String, Vec<String>>,
+    features: HashMap<String, Vec<PrefixSupport>>,
+    /// Last version of each browser lacking an unprefixed feature.
+    #[serde(default)]
+    unsupported: HashMap<String, HashMap<String, String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BrowserData {
+    versions: Vec<(String, f64)>,
+    #[serde(default)]
+    dead: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct PrefixSupport {
+    prefix: String,
+    /// Last version of each browser that still needs the prefix.
+    browsers: HashMap<String, String>,
+}
+
+const DEFAULTS_QUERY: &str = "> 0.5%, last 2 versions, firefox esr, not dead";
+
+fn compat_data() -> &'static CompatData {
+    static DATA: OnceLock<CompatData> = OnceLock::new();
+    DATA.get_or_init(|| {
+        serde_json::from_str(include_str!("../data/compat.json"))
+            .expect("bundled compat.json is valid")
+    })
+}
+
+impl Targets {
+    /// Resolve a comma-separated browserslist query such as
+    /// `last 2 versions, > 0.5%, not dead`.
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let browsers = evaluate(query, compat_data())?;
+        if browsers.is_empty() {
+            return Err(format!("Browser query '{}' matched no browsers", query));
+        }
+        Ok(Targets {
+            query: query.trim().to_string(),
+            browsers,
+        })
+    }
+
+    /// The browserslist `defaults` query.
+    pub fn defaults() -> Self {
+        Self::parse(DEFAULTS_QUERY).expect("defaults query resolves")
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Browsers selected by the query as `(name, version)` pairs.
+    pub fn browsers(&self) -> Vec<(String, String)> {
+        self.browsers
+            .iter()
+            .map(|(name, version)| (name.clone(), version.to_string()))
+            .collect()
+    }
+
+    /// Whether any targeted browser still needs `prefix` for `feature`.
+    pub fn needs_prefix(&self, feature: &str, prefix: &str) -> bool {
+        let Some(entries) = compat_data().features.get(feature) else {
+            return false;
+        };
+        let Some(support) = entries.iter().find(|entry| entry.prefix == prefix) else {
+            return false;
+        };
+
+        self.any_at_or_below(&support.browsers)
+    }
+
+    /// Whether every targeted browser supports `feature` (e.g. `css-oklch`).
+    /// Features missing from the compat table are assumed supported.
+    pub fn supports(&self, feature: &str) -> bool {
+        match compat_data().unsupported.get(feature) {
+            Some(bounds) => !self.any_at_or_below(bounds),
+            None => true,
+        }
+    }
+
+    fn any_at_or_below(&self, bounds: &HashMap<String, String>) -> bool {
+        self.browsers.iter().any(
+            |(name, version)| match bounds.get(name).map(String::as_str) {
+                Some("all") => true,
+                Some(bound) => Version::parse(bound)
+                    .map(|bound| *version <= bound)
+                    .unwrap_or(false),
+                None => false,
+            },
+        )
+    }
+}
+
+impl Default for Targets {
+    fn default() -> Self {
+        Self::defaults()
+    }
+}
+
+impl FromStr for Targets {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Targets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.query)
+    }
+}
+
+impl Version {
+    fn parse(raw: &str) -> Result<Self, String> {
+        let mut parts = raw.trim().split('.');
+        let major = parts
+            .next()
+            .and_then(|p| p.parse().ok())
+            .ok_or_else(|| format!("Invalid browser version '{}'", raw))?;
+        let minor = match parts.next() {
+            Some(p) => p
+                .parse()
+                .map_err(|_| format!("Invalid browser version '{}'", raw))?,
+            None => 0,
+        };
+        Ok(Version(major, minor))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.1 == 0 {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "{}.{}", self.0, self.1)
+        }
+    }
+}
+
+type Selection = BTreeSet<(String, Version)>;
+
+fn evaluate(query: &str, data: &CompatData) -> Result<Selection, String> {
+    let mut selected = Selection::new();
+
+    for term in split_terms(query) {
+        let lower = term.to_ascii_lowercase();
+        if let Some(negated) = lower.strip_prefix("not ") {
+            let removed = evaluate_and(negated.trim(), data)?;
+            selected.retain(|entry| !removed.contains(entry));
+        } else {
+            selected.extend(evaluate_and(&lower, data)?);
+        }
+    }
+
+    Ok(selected)
+}
+
+fn split_terms(query: &str) -> Vec<String> {
+    query
+        .split(',')
+        .flat_map(|part| part.split(" or "))
+        .map(str::trim)
+        .filter(|term| !term.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn evaluate_and(term: &str, data: &CompatData) -> Result<Selection, String> {
+    let mut parts = term.split(" and ");
+    let first = parts.next().unwrap_or_default();
+    let mut result = evaluate_atom(first.trim(), data)?;
+    for part in parts {
+        let other = evaluate_atom(part.trim(), data)?;
+        result.retain(|entry| other.contains(entry));
+    }
+    Ok(result)
+}
+
+fn evaluate_atom(atom: &str, data: &CompatData) -> Result<Selection, String> {
+    let words: Vec<&str> = atom.split_whitespace().collect();
+
+    match words.as_slice() {
+        ["defaults"] => evaluate(DEFAULTS_QUERY, data),
+        ["dead"] => Ok(select(data, |browser, _| browser.dead)),
+        ["firefox" | "ff", "esr"] => {
+            let mut out = Selection::new();
+            for version in data.esr.get("firefox").into_iter().flatten() {
+                out.insert(("firefox".to_string(), Version::parse(version)?));
+            }
+            Ok(out)
+        }
+        ["last", count, "versions" | "version"] => {
+            let count = parse_count(count, atom)?;
+            let mut out = Selection::new();
+            for (name, browser) in &data.browsers {
+                out.extend(last_versions(name, browser, count)?);
+            }
+            Ok(out)
+        }
+        ["last", count, browser, "versions" | "version"] => {
+            let count = parse_count(count, atom)?;
+            let name = canonical_browser(browser, data)?;
+            last_versions(name, &data.browsers[name], count)
+        }
+        [op @ (">" | ">=" | "<" | "<="), share] if share.ends_with('%') => {
+            let limit: f64 = share
+                .trim_end_matches('%')
+                .parse()
+                .map_err(|_| format!("Invalid usage share in browser query '{}'", atom))?;
+            let op = *op;
+            Ok(select(data, |_, usage| compare(usage, op, limit)))
+        }
+        [browser, op @ (">" | ">=" | "<" | "<="), version] => {
+            let name = canonical_browser(browser, data)?;
+            let limit = Version::parse(version)?;
+            let op = *op;
+            filter_versions(name, &data.browsers[name], |v| match op {
+                ">" => v > limit,
+                ">=" => v >= limit,
+                "<" => v < limit,
+                _ => v <= limit,
+            })
+        }
+        [browser, version] => {
+            let name = canonical_browser(browser, data)?;
+            if let Some((from, to)) = version.split_once('-') {
+                let from = Version::parse(from)?;
+                let to = Version::parse(to)?;
+                return filter_versions(name, &data.browsers[name], |v| v >= from && v <= to);
+            }
+            let exact = Version::parse(version)?;
+            let found = filter_versions(name, &data.browsers[name], |v| v == exact)?;
+            if found.is_empty() {
+                return Err(format!("Unknown version {} of {}", version, name));
+            }
+            Ok(found)
+        }
+        _ => Err(format!("Unknown browser query '{}'", atom)),
+    }
+}
+
+fn parse_count(raw: &str, atom: &str) -> Result<usize, String> {
+    raw.parse()
+        .map_err(|_| format!("Invalid version count in browser query '{}'", atom))
+}
+
+fn compare(value: f64, op: &str, limit: f64) -> bool {
+    match op {
+        ">" => value > limit,
+        ">=" => value >= limit,
+        "<" => value < limit,
+        _ => value <= limit,
+    }
+}
+
+fn select(data: &CompatData, keep: impl Fn(&BrowserData, f64) -> bool) -> Selection {
+    let mut out = Selection::new();
+    for (name, browser) in &data.browsers {
+        for (version, usage) in &browser.versions {
+            if keep(browser, *usage)
+                && let Ok(version) = Version::parse(version)
+            {
+                out.insert((name.clone(), version));
+            }
+        }
+    }
+    out
+}
+
+fn last_versions(name: &str, browser: &BrowserData, count: usize) -> Result<Selection, String> {
+    let mut out = Selection::new();
+    for (version, _) in browser.versions.iter().rev().take(count) {
+        out.insert((name.to_string(), Version::parse(version)?));
+    }
+    Ok(out)
+}
+
+fn filter_versions(
+    name: &str,
+    browser: &BrowserData,
+    keep: impl Fn(Version) -> bool,
+) -> Result<Selection, String> {
+    let mut out = Selection::new();
+    for (version, _) in &browser.versions {
+        let version = Version::parse(version)?;
+        if keep(version) {
+            out.insert((name.to_string(), version));
+        }
+    }
+    Ok(out)
+}
+
+fn canonical_browser<'a>(raw: &str, data: &'a CompatData) -> Result<&'a str, String> {
+    let name = match raw {
+        "ff" => "firefox",
+        "ios" | "ios_safari" => "ios_saf",
+        "explorer" => "ie",
+        "chromeandroid" => "and_chr",
+        "firefoxandroid" => "and_ff",
+        other => other,
+    };
+    data.browsers
+        .get_key_value(name)
+        .map(|(key, _)| key.as_str())
+        .ok_or_else(|| format!("Unknown browser '{}' in browser query", raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_skip_obsolete_prefixes() {
+        let targets = Targets::defaults();
+        assert!(!targets.needs_prefix("css-border-radius", "-moz-"));
+        assert!(!targets.needs_prefix("css-boxshadow", "-webkit-"));
+        assert!(targets.needs_prefix("user-select-none", "-webkit-"));
+    }
+
+    #[test]
+    fn old_targets_need_prefixes() {
+        let targets = Targets::parse("safari >= 6, ie 10").expect("parse query");
+        assert!(targets.needs_prefix("transforms2d", "-webkit-"));
+        assert!(targets.needs_prefix("flexbox-2012", "-ms-"));
+        assert!(!targets.needs_prefix("css-selection", "-moz-"));
+    }
+
+    #[test]
+    fn unsupported_features_follow_targets() {
+        assert!(
+            Targets::parse("chrome 131, safari 18.2")
+                .unwrap()
+                .supports("css-oklch")
+        );
+        let old = Targets::parse("safari 15.2").expect("parse query");
+        assert!(!old.supports("css-oklch"));
+        assert!(!old.supports("css-color-mix"));
+        assert!(old.supports("not-in-the-table"));
+    }
+
+    #[test]
+    fn not_dead_removes_ie() {
+        let targets = Targets::parse("ie 11, chrome 131, not dead").expect("parse query");
+        assert_eq!(targets.browsers(), vec![("chrome".into(), "131".into())]);
+    }
+
+    #[test]
+    fn unknown_query_errors() {
+        let err = Targets::parse("netscape 4").unwrap_err();
+        assert!(err.contains("Unknown browser 'netscape'"));
+    }
+}
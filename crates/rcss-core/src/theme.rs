@@ -1,24 +1,125 @@
+#[cfg(feature = "builtin-theme")]
+use crate::builtin;
+use crate::dtcg;
+use crate::presets::{self, Preset};
+use crate::suggest;
+use crate::token_index::TokenIndex;
+use crate::token_refs;
+use crate::tokens::{self, Token, TokenCollection, TokenValue};
 use serde::Deserialize;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
 pub struct Theme {
-    pub collections: HashMap<String, Value>,
+    pub collections: HashMap<String, TokenCollection>,
     pub properties: HashMap<String, PropertyMapping>,
-    #[serde(default)]
+    /// Alternative property names (`color` → `textColor`). Aliases take
+    /// precedence over a property of the same name.
+    pub aliases: HashMap<String, String>,
     pub shorthands: HashMap<String, ShorthandDef>,
+    /// Named `%preset` bundles from `presets.json`.
+    pub presets: HashMap<String, Preset>,
+    /// Precomputed CSS for every property/token pair; rebuilt by
+    /// [`Theme::rebuild_index`] after collections change.
+    pub index: TokenIndex,
+    /// Keys of tokens (and overrides) with light/dark values.
+    pub mode_tokens: Vec<String>,
+    /// This theme with every light/dark token set to its dark value; `None`
+    /// when no token has modes.
+    pub dark: Option<Box<Theme>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone)]
 pub struct PropertyMapping {
     pub collection: String,
+    pub overrides: HashMap<String, TokenValue>,
+}
+
+/// `theme.json`: the parent theme directories this one builds on, relative
+/// to it and applied in order.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
     #[serde(default)]
-    pub overrides: HashMap<String, Value>,
+    extends: Parents,
 }
 
+#[derive(Debug, Default, Deserialize)]
+#[serde(untagged)]
+enum Parents {
+    #[default]
+    None,
+    One(String),
+    Many(Vec<String>),
+}
+
+/// `tokens.json` as written, before token values are typed. A `null` entry
+/// removes what a parent theme defined under that key.
+#[derive(Debug, Default, Deserialize)]
+struct RawTheme {
+    #[serde(default)]
+    collections: HashMap<String, Value>,
+    #[serde(default)]
+    properties: HashMap<String, Option<RawPropertyPatch>>,
+    #[serde(default)]
+    aliases: HashMap<String, Option<String>>,
+}
+
+#[derive(Debug)]
+struct RawPropertyMapping {
+    collection: String,
+    overrides: HashMap<String, Value>,
+}
+
+/// A `properties` entry, merged over the parent theme's mapping of the same
+/// property: a missing `collection` keeps the parent's, and overrides merge
+/// key by key, `null` removing one.
 #[derive(Debug, Deserialize)]
+struct RawPropertyPatch {
+    collection: Option<String>,
+    #[serde(default)]
+    overrides: HashMap<String, Option<Value>>,
+}
+
+/// One file from `theme/tokens/`: `<collection>.json` or
+/// `<collection>.<layer>.json`, with the collection name in kebab-case.
+#[derive(Debug)]
+pub(crate) struct TokenFile {
+    pub path: String,
+    pub collection: String,
+    pub layered: bool,
+    pub raw: Value,
+}
+
+/// Precedence of a token source within one theme directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rank {
+    TokensJson,
+    Dtcg,
+    CollectionFile,
+    LayerFile,
+}
+
+/// Theme directories merged parents first, before cross-references are
+/// checked. Collections merge token by token, properties and shorthands
+/// field by field, aliases and presets by key.
+#[derive(Default)]
+struct Layers {
+    collections: HashMap<String, TokenCollection>,
+    properties: HashMap<String, RawPropertyMapping>,
+    aliases: HashMap<String, String>,
+    shorthands: HashMap<String, ShorthandDef>,
+    presets: HashMap<String, Preset>,
+    /// Where each token of the directory being loaded was last set.
+    defined_in: HashMap<(String, String), (Rank, Arc<str>)>,
+    errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct ShorthandStep {
     pub property: String,
     pub template: String,
@@ -28,67 +129,973 @@ pub struct ShorthandStep {
     pub optional: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ShorthandDef {
     pub steps: Vec<ShorthandStep>,
     pub order: Option<Vec<String>>,
 }
 
+/// A shorthand as written. The object form is merged over the parent
+/// theme's shorthand of the same name: `steps` or `order` left out keep the
+/// parent's.
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 enum RawShorthand {
     Steps(Vec<ShorthandStep>),
     Object {
-        steps: Vec<ShorthandStep>,
-        #[serde(default)]
-        order: Vec<String>,
+        steps: Option<Vec<ShorthandStep>>,
+        order: Option<Vec<String>>,
     },
 }
 
 impl Theme {
+    /// Load a theme directory: its parents from `theme.json`, then
+    /// `tokens.json`, W3C design token files (`*.tokens.json`), the
+    /// per-collection files in `tokens/`, `shorthands.json` and
+    /// `presets.json`.
     pub fn load_from_dir(dir: &str) -> Result<Self, String> {
-        let path = format!("{}/tokens.json", dir);
-        let mut theme = Self::load(&path)?;
-
-        // Load shorthands if present
-        let shorthand_path = format!("{}/shorthands.json", dir);
-        if let Ok(data) = fs::read_to_string(&shorthand_path) {
-            let raw: serde_json::Value = serde_json::from_str(&data)
-                .map_err(|e| format!("Invalid JSON in {}: {}", shorthand_path, e))?;
-            let mut shorthands = HashMap::new();
-
-            let obj = raw.as_object().ok_or_else(|| {
-                format!(
-                    "Invalid JSON in {}: root should be an object",
-                    shorthand_path
-                )
-            })?;
-
-            for (key, val) in obj {
-                let parsed: RawShorthand = serde_json::from_value(val.clone())
-                    .map_err(|e| format!("Invalid shorthand '{}': {}", key, e))?;
-
-                let def = match parsed {
-                    RawShorthand::Steps(steps) => ShorthandDef { steps, order: None },
-                    RawShorthand::Object { steps, order } => {
-                        let order = if order.is_empty() { None } else { Some(order) };
-                        ShorthandDef { steps, order }
-                    }
+        Self::load_layers(None, dir)
+    }
+
+    /// The default theme compiled into the crate: the repository's `theme/`
+    /// directory.
+    #[cfg(feature = "builtin-theme")]
+    pub fn builtin() -> Result<Self, String> {
+        let mut layers = Layers::default();
+        layers.load_dir(&builtin::files()?)?;
+        layers
+            .finish()
+            .map_err(|errors| format!("Invalid built-in theme:\n  {}", errors.join("\n  ")))
+    }
+
+    /// Load a theme directory (and its parents) over [`Theme::builtin`], so
+    /// it only needs to define what it adds or changes.
+    #[cfg(feature = "builtin-theme")]
+    pub fn load_over_builtin(dir: &str) -> Result<Self, String> {
+        Self::load_layers(Some(builtin::files()?), dir)
+    }
+
+    fn load_layers(base: Option<ThemeFiles>, dir: &str) -> Result<Self, String> {
+        let mut layers = Layers::default();
+        if let Some(base) = &base {
+            layers.load_dir(base)?;
+        }
+        for theme_dir in theme_dirs(Path::new(dir))? {
+            let has_parent = theme_dir.extends || base.is_some();
+            let files = ThemeFiles::read(&theme_dir.path, has_parent)?;
+            if base.as_ref().is_some_and(|base| base.same_contents(&files)) {
+                continue;
+            }
+            layers.load_dir(&files)?;
+        }
+        layers
+            .finish()
+            .map_err(|errors| format!("Invalid tokens in {}:\n  {}", dir, errors.join("\n  ")))
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let raw = read_json(path)?;
+        let mut layers = Layers::default();
+        layers.add_tokens_json(raw, path);
+        layers
+            .finish()
+            .map_err(|errors| format!("Invalid tokens in {}:\n  {}", path, errors.join("\n  ")))
+    }
+
+    pub fn rebuild_index(&mut self) {
+        self.index = TokenIndex::build(&self.collections, &self.properties, &self.aliases);
+    }
+
+    /// Record the light/dark tokens and derive [`Theme::dark`] from them.
+    fn build_dark_variant(&mut self) {
+        let is_modal = |value: &TokenValue| matches!(value, TokenValue::Modes { .. });
+        let mut keys: Vec<String> = self
+            .collections
+            .values()
+            .flat_map(|tokens| tokens.iter())
+            .filter(|(_, token)| is_modal(&token.value))
+            .map(|(key, _)| key.clone())
+            .chain(self.properties.values().flat_map(|mapping| {
+                mapping
+                    .overrides
+                    .iter()
+                    .filter(|(_, value)| is_modal(value))
+                    .map(|(key, _)| key.clone())
+            }))
+            .collect();
+        keys.sort();
+        keys.dedup();
+        if keys.is_empty() {
+            return;
+        }
+
+        let dark_value = |value: &TokenValue| value.for_mode(true).clone();
+        let mut dark = Theme {
+            collections: self
+                .collections
+                .iter()
+                .map(|(name, tokens)| {
+                    let tokens = tokens
+                        .iter()
+                        .map(|(key, token)| {
+                            let token = Token {
+                                value: dark_value(&token.value),
+                                ..token.clone()
+                            };
+                            (key.clone(), token)
+                        })
+                        .collect();
+                    (name.clone(), tokens)
+                })
+                .collect(),
+            properties: self
+                .properties
+                .iter()
+                .map(|(name, mapping)| {
+                    let overrides = mapping
+                        .overrides
+                        .iter()
+                        .map(|(key, value)| (key.clone(), dark_value(value)))
+                        .collect();
+                    let mapping = PropertyMapping {
+                        collection: mapping.collection.clone(),
+                        overrides,
+                    };
+                    (name.clone(), mapping)
+                })
+                .collect(),
+            aliases: self.aliases.clone(),
+            shorthands: self.shorthands.clone(),
+            presets: self.presets.clone(),
+            index: TokenIndex::default(),
+            mode_tokens: Vec::new(),
+            dark: None,
+        };
+        dark.rebuild_index();
+        self.mode_tokens = keys;
+        self.dark = Some(Box::new(dark));
+    }
+
+    /// The mapping for a camelCase property key, following aliases.
+    pub fn property(&self, key: &str) -> Option<&PropertyMapping> {
+        let key = self.aliases.get(key).map(String::as_str).unwrap_or(key);
+        self.properties.get(key)
+    }
+
+    /// Look up `key` (dash-joined path such as `slate-500`) in a collection.
+    pub fn token(&self, collection: &str, key: &str) -> Result<&TokenValue, String> {
+        let tokens = self.collections.get(collection).ok_or_else(|| {
+            format!(
+                "Unknown collection '{}'{}",
+                collection,
+                suggest::did_you_mean(collection, self.collections.keys().map(String::as_str), "")
+            )
+        })?;
+        tokens.get(key).map(|token| &token.value).ok_or_else(|| {
+            format!(
+                "Unknown token '{}' in {}{}",
+                key,
+                collection,
+                suggest::did_you_mean(key, tokens.keys().map(String::as_str), "")
+            )
+        })
+    }
+
+    /// The file a token came from, e.g. `brand/tokens/colors.json` when a
+    /// child theme overrides its parent.
+    pub fn token_source(&self, collection: &str, key: &str) -> Option<&str> {
+        self.collections
+            .get(collection)?
+            .get(key)?
+            .source
+            .as_deref()
+    }
+}
+
+impl Layers {
+    /// Apply one theme directory over what its parents defined.
+    fn load_dir(&mut self, files: &ThemeFiles) -> Result<(), String> {
+        if let Some((path, data)) = &files.tokens_json {
+            let raw = serde_json::from_str(data)
+                .map_err(|e| format!("Invalid JSON in {}: {}", path, e))?;
+            self.add_tokens_json(raw, path);
+        }
+        self.add_dtcg(&files.dtcg);
+        for file in &files.token_files {
+            let rank = if file.layered {
+                Rank::LayerFile
+            } else {
+                Rank::CollectionFile
+            };
+            self.add_collection(&file.collection, &file.raw, &file.path, rank);
+        }
+
+        if let Some((path, data)) = &files.shorthands {
+            self.add_shorthands(data, path)?;
+        }
+        if let Some((path, data)) = &files.presets {
+            for (name, preset) in presets::parse_layer(data, path)? {
+                match preset {
+                    Some(preset) => self.presets.insert(name, preset),
+                    None => self.presets.remove(&name),
                 };
+            }
+        }
+
+        self.defined_in.clear();
+        Ok(())
+    }
+
+    fn add_tokens_json(&mut self, raw: RawTheme, path: &str) {
+        for (name, value) in &raw.collections {
+            self.add_collection(name, value, path, Rank::TokensJson);
+        }
+        for (name, patch) in raw.properties {
+            let Some(patch) = patch else {
+                self.properties.remove(&name);
+                continue;
+            };
+            let inherited = self.properties.remove(&name);
+            let Some(collection) = patch
+                .collection
+                .or_else(|| inherited.as_ref().map(|m| m.collection.clone()))
+            else {
+                self.errors.push(format!(
+                    "{}: properties.{}: missing \"collection\"",
+                    path, name
+                ));
+                continue;
+            };
+            let mut overrides = inherited.map(|m| m.overrides).unwrap_or_default();
+            for (key, value) in patch.overrides {
+                match value {
+                    Some(value) => overrides.insert(key, value),
+                    None => overrides.remove(&key),
+                };
+            }
+            let mapping = RawPropertyMapping {
+                collection,
+                overrides,
+            };
+            self.properties.insert(name, mapping);
+        }
+        for (alias, target) in raw.aliases {
+            match target {
+                Some(target) => self.aliases.insert(alias, target),
+                None => self.aliases.remove(&alias),
+            };
+        }
+    }
+
+    /// Convert W3C design token files and merge their collections, mapping
+    /// the usual properties to each collection unless the theme already maps
+    /// them (or has an alias of that name).
+    fn add_dtcg(&mut self, files: &[(String, Value)]) {
+        if files.is_empty() {
+            return;
+        }
+        let (converted, errors) = dtcg::convert(files);
+        self.errors.extend(errors);
+        for ((path, _), collections) in files.iter().zip(converted) {
+            for (name, raw) in &collections {
+                self.add_collection(name, raw, path, Rank::Dtcg);
+                for property in dtcg::default_properties(name) {
+                    if self.aliases.contains_key(*property) {
+                        continue;
+                    }
+                    self.properties
+                        .entry(property.to_string())
+                        .or_insert_with(|| RawPropertyMapping {
+                            collection: name.clone(),
+                            overrides: HashMap::new(),
+                        });
+                }
+            }
+        }
+    }
+
+    /// Merge one collection from `source` token by token. Within a theme
+    /// directory the precedence is `tokens.json` < `<collection>.json` <
+    /// `<collection>.<layer>.json`; two files of the same rank that define a
+    /// token differently are reported with both paths.
+    fn add_collection(&mut self, name: &str, raw: &Value, source: &str, rank: Rank) {
+        if raw.is_null() {
+            self.collections.remove(name);
+            return;
+        }
+        let mut removed = Vec::new();
+        let raw = strip_removals(raw, &mut Vec::new(), &mut removed);
+        let mut errors = Vec::new();
+        let parsed = tokens::parse_collection(name, &raw, &mut errors);
+        self.errors
+            .extend(errors.into_iter().map(|e| format!("{}: {}", source, e)));
+
+        let target = self.collections.entry(name.to_string()).or_default();
+        for path in removed {
+            target.retain(|key, token| {
+                let keep = !token.path.starts_with(&path);
+                if !keep {
+                    self.defined_in.remove(&(name.to_string(), key.clone()));
+                }
+                keep
+            });
+        }
+        let source: Arc<str> = Arc::from(source);
+        for (key, token) in parsed {
+            let id = (name.to_string(), key.clone());
+            if let Some((other_rank, other)) = self.defined_in.get(&id)
+                && *other_rank == rank
+                && target
+                    .get(&key)
+                    .is_some_and(|existing| !existing.value.equivalent(&token.value))
+            {
+                self.errors.push(format!(
+                    "{}.{}: defined differently in {} and {}",
+                    name,
+                    token.path.join("."),
+                    other,
+                    source
+                ));
+                continue;
+            }
+            self.defined_in.insert(id, (rank, source.clone()));
+            // Keep the first spelling of an equivalent value (`0.1`, not `0.10`).
+            if !target
+                .get(&key)
+                .is_some_and(|existing| existing.value.equivalent(&token.value))
+            {
+                target.insert(
+                    key,
+                    Token {
+                        source: Some(source.clone()),
+                        ..token
+                    },
+                );
+            }
+        }
+    }
+
+    fn add_shorthands(&mut self, data: &str, shorthand_path: &str) -> Result<(), String> {
+        let raw: Value = serde_json::from_str(data)
+            .map_err(|e| format!("Invalid JSON in {}: {}", shorthand_path, e))?;
+
+        let obj = raw.as_object().ok_or_else(|| {
+            format!(
+                "Invalid JSON in {}: root should be an object",
+                shorthand_path
+            )
+        })?;
 
-                shorthands.insert(key.clone(), def);
+        for (key, val) in obj {
+            if val.is_null() {
+                self.shorthands.remove(key);
+                continue;
             }
+            let parsed: RawShorthand = serde_json::from_value(val.clone())
+                .map_err(|e| format!("Invalid shorthand '{}': {}", key, e))?;
 
-            theme.shorthands = shorthands;
+            let def = match parsed {
+                RawShorthand::Steps(steps) => ShorthandDef { steps, order: None },
+                RawShorthand::Object { steps, order } => {
+                    let inherited = self.shorthands.remove(key);
+                    let order = match order {
+                        Some(order) => Some(order).filter(|order| !order.is_empty()),
+                        None => inherited.as_ref().and_then(|def| def.order.clone()),
+                    };
+                    let steps = steps
+                        .or_else(|| inherited.map(|def| def.steps))
+                        .ok_or_else(|| format!("Invalid shorthand '{}': missing steps", key))?;
+                    ShorthandDef { steps, order }
+                }
+            };
+
+            self.shorthands.insert(key.clone(), def);
+        }
+        Ok(())
+    }
+
+    /// Type every property override and check cross-references, collecting
+    /// all invalid entries.
+    fn finish(self) -> Result<Theme, Vec<String>> {
+        let mut errors = self.errors;
+        let mut collections = self.collections;
+        token_refs::resolve_all(&mut collections, &mut errors);
+
+        let mut properties = HashMap::new();
+        for (name, mapping) in self.properties {
+            if !collections.contains_key(&mapping.collection) {
+                errors.push(format!(
+                    "properties.{}: unknown collection '{}'",
+                    name, mapping.collection
+                ));
+            }
+            let mut overrides = HashMap::new();
+            for (key, value) in &mapping.overrides {
+                let parsed = tokens::parse_value(&mapping.collection, value).and_then(|parsed| {
+                    token_refs::resolve_value(&collections, &mapping.collection, parsed)
+                });
+                match parsed {
+                    Ok(parsed) => {
+                        overrides.insert(key.clone(), parsed);
+                    }
+                    Err(e) => errors.push(format!("properties.{}.overrides.{}: {}", name, key, e)),
+                }
+            }
+            properties.insert(
+                name,
+                PropertyMapping {
+                    collection: mapping.collection,
+                    overrides,
+                },
+            );
         }
 
+        for (alias, target) in &self.aliases {
+            if !properties.contains_key(target) {
+                errors.push(format!("aliases.{}: unknown property '{}'", alias, target));
+            }
+        }
+        errors.extend(
+            presets::validate(&self.presets)
+                .into_iter()
+                .map(|e| format!("presets.{}", e)),
+        );
+
+        if !errors.is_empty() {
+            errors.sort();
+            return Err(errors);
+        }
+
+        let mut theme = Theme {
+            collections,
+            properties,
+            aliases: self.aliases,
+            shorthands: self.shorthands,
+            presets: self.presets,
+            index: TokenIndex::default(),
+            mode_tokens: Vec::new(),
+            dark: None,
+        };
+        theme.rebuild_index();
+        theme.build_dark_variant();
         Ok(theme)
     }
+}
 
-    pub fn load(path: &str) -> Result<Self, String> {
-        let data =
-            fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+/// A theme directory to load and whether its `theme.json` extends others.
+pub(crate) struct ThemeDir {
+    pub path: PathBuf,
+    pub extends: bool,
+    canonical: PathBuf,
+}
+
+/// The files of one theme layer, read from a directory or embedded in the
+/// crate. Paths are kept for error messages and token sources.
+pub(crate) struct ThemeFiles {
+    /// `tokens.json` as `(path, contents)`.
+    pub tokens_json: Option<(String, String)>,
+    pub dtcg: Vec<(String, Value)>,
+    pub token_files: Vec<TokenFile>,
+    pub shorthands: Option<(String, String)>,
+    pub presets: Option<(String, String)>,
+}
+
+impl ThemeFiles {
+    /// Read a theme directory. `tokens.json` is required unless a parent
+    /// theme is applied first or the directory has DTCG files.
+    pub(crate) fn read(dir: &Path, has_parent: bool) -> Result<Self, String> {
+        let dtcg = read_dtcg_files(dir)?;
+        let tokens_path = dir.join("tokens.json");
+        let tokens_json = if (!has_parent && dtcg.is_empty()) || tokens_path.exists() {
+            Some(read_with_path(&tokens_path)?)
+        } else {
+            None
+        };
+        let optional = |name: &str| {
+            let path = dir.join(name);
+            path.exists().then(|| read_with_path(&path)).transpose()
+        };
+        Ok(ThemeFiles {
+            tokens_json,
+            dtcg,
+            token_files: read_token_files(&dir.join("tokens"))?,
+            shorthands: optional("shorthands.json")?,
+            presets: optional("presets.json")?,
+        })
+    }
+
+    /// Whether `other` holds the same files with the same contents, wherever
+    /// either was read from: a checkout's own `theme/` is the built-in theme
+    /// again, and layering it over that would define every token twice.
+    pub(crate) fn same_contents(&self, other: &ThemeFiles) -> bool {
+        fn name(path: &str) -> &str {
+            path.rsplit(['/', '\\', ':']).next().unwrap_or(path)
+        }
+        fn same_file(a: &Option<(String, String)>, b: &Option<(String, String)>) -> bool {
+            match (a, b) {
+                (Some((a_path, a)), Some((b_path, b))) => name(a_path) == name(b_path) && a == b,
+                (None, None) => true,
+                _ => false,
+            }
+        }
+        same_file(&self.tokens_json, &other.tokens_json)
+            && same_file(&self.shorthands, &other.shorthands)
+            && same_file(&self.presets, &other.presets)
+            && self.dtcg.len() == other.dtcg.len()
+            && self
+                .dtcg
+                .iter()
+                .zip(&other.dtcg)
+                .all(|((a_path, a), (b_path, b))| name(a_path) == name(b_path) && a == b)
+            && self.token_files.len() == other.token_files.len()
+            && self
+                .token_files
+                .iter()
+                .zip(&other.token_files)
+                .all(|(a, b)| name(&a.path) == name(&b.path) && a.raw == b.raw)
+    }
+}
+
+fn read_with_path(path: &Path) -> Result<(String, String), String> {
+    Ok((path.display().to_string(), read_file(path)?))
+}
+
+/// `dir` preceded by the parents listed in its `theme.json`, recursively, in
+/// the order they apply. A directory reached twice (two parents extending
+/// the same base) applies once, at its first position.
+pub(crate) fn theme_dirs(dir: &Path) -> Result<Vec<ThemeDir>, String> {
+    let mut dirs = Vec::new();
+    collect_theme_dirs(dir, &mut Vec::new(), &mut dirs)?;
+    Ok(dirs)
+}
+
+/// `stack` holds the directories being visited, to report `extends` cycles.
+fn collect_theme_dirs(
+    dir: &Path,
+    stack: &mut Vec<PathBuf>,
+    dirs: &mut Vec<ThemeDir>,
+) -> Result<(), String> {
+    let canonical = fs::canonicalize(dir)
+        .map_err(|e| format!("Could not read theme {}: {}", dir.display(), e))?;
+    if let Some(start) = stack.iter().position(|seen| *seen == canonical) {
+        let chain: Vec<String> = stack[start..]
+            .iter()
+            .chain([&canonical])
+            .map(|path| path.display().to_string())
+            .collect();
+        return Err(format!("Theme extends cycle: {}", chain.join(" → ")));
+    }
+    if dirs.iter().any(|added| added.canonical == canonical) {
+        return Ok(());
+    }
+    stack.push(canonical);
+
+    let manifest_path = dir.join("theme.json");
+    let manifest: Manifest = if manifest_path.exists() {
+        let data = read_file(&manifest_path)?;
+        serde_json::from_str(&data)
+            .map_err(|e| format!("Invalid JSON in {}: {}", manifest_path.display(), e))?
+    } else {
+        Manifest::default()
+    };
+    let parents = match manifest.extends {
+        Parents::None => Vec::new(),
+        Parents::One(parent) => vec![parent],
+        Parents::Many(parents) => parents,
+    };
+    for parent in &parents {
+        collect_theme_dirs(&dir.join(parent), stack, dirs)?;
+    }
+
+    let canonical = stack.pop().unwrap();
+    dirs.push(ThemeDir {
+        path: dir.to_path_buf(),
+        extends: !parents.is_empty(),
+        canonical,
+    });
+    Ok(())
+}
+
+pub(crate) fn read_file(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))
+}
+
+fn read_json(path: &str) -> Result<RawTheme, String> {
+    let data = read_file(Path::new(path))?;
+    serde_json::from_str(&data).map_err(|e| format!("Invalid JSON in {}: {}", path, e))
+}
+
+/// Copy `value` without its `null` entries, recording the path of each so
+/// the tokens (or whole groups) they name can be removed.
+pub(crate) fn strip_removals(
+    value: &Value,
+    path: &mut Vec<String>,
+    removed: &mut Vec<Vec<String>>,
+) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut kept = serde_json::Map::new();
+            for (key, child) in map {
+                path.push(key.clone());
+                if child.is_null() {
+                    removed.push(path.clone());
+                } else {
+                    kept.insert(key.clone(), strip_removals(child, path, removed));
+                }
+                path.pop();
+            }
+            Value::Object(kept)
+        }
+        other => other.clone(),
+    }
+}
+
+/// Read the W3C design token files (`*.tokens.json`) at the top of a theme
+/// directory, in name order.
+pub(crate) fn read_dtcg_files(dir: &Path) -> Result<Vec<(String, Value)>, String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.ends_with(".tokens.json"))
+        })
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let display = path.display().to_string();
+            let raw = serde_json::from_str(&read_file(&path)?)
+                .map_err(|e| format!("Invalid JSON in {}: {}", display, e))?;
+            Ok((display, raw))
+        })
+        .collect()
+}
+
+/// Parse one split token file named `<stem>.json`; `None` when it is empty.
+pub(crate) fn token_file(
+    path: String,
+    stem: &str,
+    data: &str,
+) -> Result<Option<TokenFile>, String> {
+    if data.trim().is_empty() {
+        return Ok(None);
+    }
+    let raw = serde_json::from_str(data).map_err(|e| format!("Invalid JSON in {}: {}", path, e))?;
+    let (name, layered) = match stem.split_once('.') {
+        Some((name, _layer)) => (name, true),
+        None => (stem, false),
+    };
+    Ok(Some(TokenFile {
+        path,
+        collection: tokens::camel_case(name),
+        layered,
+        raw,
+    }))
+}
+
+/// Read every `*.json` file in `dir`, skipping empty ones. A missing
+/// directory means the theme has no split files.
+pub(crate) fn read_token_files(dir: &Path) -> Result<Vec<TokenFile>, String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    let mut files = Vec::new();
+    for path in paths {
+        let display = path.display().to_string();
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        if let Some(file) = token_file(display, stem, &read_file(&path)?)? {
+            files.push(file);
+        }
+    }
+    // Collection files before layer files, each in name order.
+    files.sort_by_key(|file| file.layered);
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `files` (relative path, contents) into a fresh theme directory.
+    fn theme_dir(name: &str, files: &[(&str, &str)]) -> String {
+        let dir = std::env::temp_dir().join(format!("rcss-theme-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, contents) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir.display().to_string()
+    }
+
+    const BASE: &str = r##"{
+        "collections": { "colors": { "brand": "#000000", "ink": "#111111" } },
+        "properties": { "textColor": { "collection": "colors" } }
+    }"##;
+
+    #[test]
+    fn split_files_merge_over_tokens_json() {
+        let dir = theme_dir(
+            "merge",
+            &[
+                ("tokens.json", BASE),
+                (
+                    "tokens/colors.json",
+                    r##"{ "brand": "#222222", "paper": "#ffffff" }"##,
+                ),
+                ("tokens/colors.acme.json", r##"{ "brand": "#ff0000" }"##),
+                ("tokens/ring-width.json", r#"{ "2": "2px" }"#),
+                (
+                    "tokens/font-size.json",
+                    r#"{ "sm": { "size": "0.875rem", "lineHeight": "1.25rem" } }"#,
+                ),
+                ("tokens/empty.json", ""),
+            ],
+        );
+        let theme = Theme::load_from_dir(&dir).unwrap();
+        let css = |collection: &str, key: &str| theme.token(collection, key).unwrap().to_css();
+        assert_eq!(css("colors", "brand"), "#ff0000");
+        assert_eq!(css("colors", "ink"), "#111111");
+        assert_eq!(css("colors", "paper"), "#ffffff");
+        assert_eq!(css("ringWidth", "2"), "2px");
+        assert_eq!(css("fontSize", "sm"), "0.875rem");
+        assert_eq!(
+            theme.index.collection_value("colors", "brand"),
+            Some("#ff0000")
+        );
+        assert!(!theme.collections.contains_key("empty"));
+    }
+
+    #[test]
+    fn conflicting_split_files_are_reported_with_paths() {
+        let dir = theme_dir(
+            "conflict",
+            &[
+                ("tokens.json", BASE),
+                ("tokens/opacity.json", r#"{ "10": "0.1" }"#),
+                ("tokens/opacity.alt.json", r#"{ "10": "0.10" }"#),
+                ("tokens/colors.acme.json", r##"{ "brand": "#ff0000" }"##),
+                ("tokens/colors.zeta.json", r##"{ "brand": "#00ff00" }"##),
+            ],
+        );
+        let err = Theme::load_from_dir(&dir).unwrap_err();
+        assert!(err.contains(&format!(
+            "colors.brand: defined differently in {dir}/tokens/colors.acme.json and {dir}/tokens/colors.zeta.json"
+        )), "{err}");
+        assert!(!err.contains("opacity"), "{err}");
+    }
+
+    #[test]
+    fn tokens_removed_by_an_earlier_layer_can_be_redefined() {
+        let dir = theme_dir(
+            "removed",
+            &[
+                ("tokens.json", BASE),
+                ("tokens/colors.a.json", r##"{ "navy": "#0000dd" }"##),
+                ("tokens/colors.b.json", r#"{ "navy": null }"#),
+                ("tokens/colors.c.json", r##"{ "navy": "#0000ee" }"##),
+            ],
+        );
+        let theme = Theme::load_from_dir(&dir).unwrap();
+        assert_eq!(theme.token("colors", "navy").unwrap().to_css(), "#0000ee");
+    }
+
+    #[test]
+    fn child_themes_merge_over_parents() {
+        let root = theme_dir(
+            "extends",
+            &[
+                (
+                    "base/tokens.json",
+                    r##"{
+                        "collections": { "colors": { "brand": "#000000", "ink": "#111111", "slate": { "100": "#f1f5f9" } } },
+                        "properties": {
+                            "textColor": { "collection": "colors" },
+                            "backgroundColor": { "collection": "colors" }
+                        }
+                    }"##,
+                ),
+                (
+                    "base/shorthands.json",
+                    r#"{ "ring": [{ "property": "box-shadow", "template": "0 0 0 @{ringWidth}" }] }"#,
+                ),
+                (
+                    "base/presets.json",
+                    r#"{ "base-16": { "declarations": { "font-size": "16px" } }, "print": { "extends": "base-16" } }"#,
+                ),
+                ("brand/theme.json", r#"{ "extends": "../base" }"#),
+                (
+                    "brand/tokens.json",
+                    r##"{
+                        "collections": { "colors": { "brand": "#ff0000", "ink": null, "slate": null } },
+                        "properties": { "backgroundColor": null }
+                    }"##,
+                ),
+                ("brand/presets.json", r#"{ "print": null }"#),
+            ],
+        );
+        let theme = Theme::load_from_dir(&format!("{root}/brand")).unwrap();
+        assert_eq!(theme.token("colors", "brand").unwrap().to_css(), "#ff0000");
+        assert_eq!(
+            theme.token_source("colors", "brand"),
+            Some(format!("{root}/brand/tokens.json").as_str())
+        );
+        assert!(theme.token("colors", "ink").is_err());
+        assert!(theme.token("colors", "slate-100").is_err());
+        assert!(theme.property("backgroundColor").is_none());
+        assert!(theme.shorthands.contains_key("ring"));
+        assert!(theme.presets.contains_key("base-16"));
+        assert!(!theme.presets.contains_key("print"));
+    }
+
+    #[test]
+    fn child_properties_and_shorthands_merge_field_by_field() {
+        let root = theme_dir(
+            "deep-merge",
+            &[
+                (
+                    "base/tokens.json",
+                    r##"{
+                        "collections": { "colors": { "ink": "#111111" }, "spacing": { "1": "4px" } },
+                        "properties": {
+                            "width": { "collection": "spacing", "overrides": { "full": "100%", "screen": "100vw" } }
+                        }
+                    }"##,
+                ),
+                (
+                    "base/shorthands.json",
+                    r#"{ "ring": { "steps": [{ "property": "box-shadow", "template": "0 0 0 @{width}" }], "order": ["width"] } }"#,
+                ),
+                ("brand/theme.json", r#"{ "extends": "../base" }"#),
+                (
+                    "brand/tokens.json",
+                    r#"{ "properties": { "width": { "overrides": { "half": "50%", "screen": null } } } }"#,
+                ),
+                (
+                    "brand/shorthands.json",
+                    r#"{ "ring": { "steps": [{ "property": "outline", "template": "@{width} solid" }] } }"#,
+                ),
+            ],
+        );
+        let theme = Theme::load_from_dir(&format!("{root}/brand")).unwrap();
+        let width = theme.property("width").unwrap();
+        assert_eq!(width.collection, "spacing");
+        let mut overrides: Vec<_> = width.overrides.keys().map(String::as_str).collect();
+        overrides.sort();
+        assert_eq!(overrides, ["full", "half"]);
+        let ring = &theme.shorthands["ring"];
+        assert_eq!(ring.steps[0].property, "outline");
+        assert_eq!(ring.order.as_deref(), Some(&["width".to_string()][..]));
+    }
+
+    #[test]
+    fn diamond_parents_apply_once() {
+        let root = theme_dir(
+            "diamond",
+            &[
+                ("base/tokens.json", BASE),
+                ("a/theme.json", r#"{ "extends": "../base" }"#),
+                (
+                    "a/tokens.json",
+                    r##"{ "collections": { "colors": { "brand": "#ff0000" } } }"##,
+                ),
+                ("b/theme.json", r#"{ "extends": "../base" }"#),
+                (
+                    "b/tokens.json",
+                    r##"{ "collections": { "colors": { "accent": "#00ff00" } } }"##,
+                ),
+                ("c/theme.json", r#"{ "extends": ["../a", "../b"] }"#),
+            ],
+        );
+        let dirs = theme_dirs(Path::new(&format!("{root}/c"))).unwrap();
+        assert_eq!(dirs.len(), 4);
+        let theme = Theme::load_from_dir(&format!("{root}/c")).unwrap();
+        assert_eq!(theme.token("colors", "brand").unwrap().to_css(), "#ff0000");
+        assert_eq!(theme.token("colors", "accent").unwrap().to_css(), "#00ff00");
+    }
+
+    #[test]
+    fn extends_cycles_are_reported() {
+        let root = theme_dir(
+            "cycle",
+            &[
+                ("a/theme.json", r#"{ "extends": "../b" }"#),
+                ("b/theme.json", r#"{ "extends": ["../a"] }"#),
+            ],
+        );
+        let err = Theme::load_from_dir(&format!("{root}/a")).unwrap_err();
+        assert!(err.starts_with("Theme extends cycle: "), "{err}");
+        assert!(err.ends_with("/a"), "{err}");
+        assert_eq!(err.matches(" → ").count(), 2, "{err}");
+    }
+
+    #[test]
+    fn reference_errors_name_the_file_and_path() {
+        let dir = theme_dir(
+            "references",
+            &[
+                ("tokens.json", BASE),
+                (
+                    "tokens/colors.semantic.json",
+                    r#"{ "primary": "{colors.brand}", "ghost": "{colors.nope}" }"#,
+                ),
+            ],
+        );
+        let err = Theme::load_from_dir(&dir).unwrap_err();
+        assert!(
+            err.contains(&format!(
+                "{dir}/tokens/colors.semantic.json: colors.ghost: unknown token {{colors.nope}}"
+            )),
+            "{err}"
+        );
+        assert!(!err.contains("primary"), "{err}");
+    }
+
+    #[test]
+    fn dtcg_files_load_with_default_properties() {
+        let root = theme_dir(
+            "dtcg",
+            &[(
+                "figma.tokens.json",
+                r##"{
+                    "color": {
+                        "$type": "color",
+                        "blue": { "600": { "$value": "#155dfc" } },
+                        "primary": { "$value": "{color.blue.600}" }
+                    },
+                    "spacing": { "$type": "dimension", "4": { "$value": { "value": 1, "unit": "rem" } } }
+                }"##,
+            )],
+        );
+        let theme = Theme::load_from_dir(&root).unwrap();
+        let primary = &theme.collections["colors"]["primary"];
+        assert_eq!(primary.value.to_css(), "#155dfc");
+        assert_eq!(primary.alias_of.as_deref(), Some("colors.blue.600"));
+        assert_eq!(
+            theme.token_source("colors", "primary"),
+            Some(format!("{root}/figma.tokens.json").as_str())
+        );
 
-        serde_json::from_str(&data).map_err(|e| format!("Invalid JSON in {}: {}", path, e))
+        let sheet = crate::parser::parse(".a { color: @primary; padding: @4; }").unwrap();
+        let sheet = crate::resolver::resolve(sheet, &theme).unwrap();
+        let css = crate::emitter::emit_css(&sheet);
+        assert!(css.contains("color: #155dfc;\n    padding: 1rem;"), "{css}");
     }
 }
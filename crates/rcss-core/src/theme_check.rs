@@ -0,0 +1,866 @@
+#[cfg(feature = "builtin-theme")]
+use crate::builtin;
+use crate::dtcg;
+use crate::theme::{self, Theme, ThemeFiles};
+use crate::token_refs;
+use crate::tokens::{self, Token, TokenCollection, TokenValue};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+/// Collections the resolver reads without going through a property:
+/// `screens` for `screen(@md)` and `opacity` for `/50` alpha suffixes.
+const DIRECT_COLLECTIONS: [&str; 2] = ["screens", "opacity"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The theme fails to load, or a stylesheet using the entry fails.
+    Error,
+    /// Loads fine but is most likely a mistake (dead or shadowed tokens).
+    Warning,
+}
+
+/// A problem found by [`check_dir`], located by file and JSON pointer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub file: String,
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}#{}: {}", self.file, self.pointer, self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Location {
+    file: String,
+    pointer: String,
+}
+
+impl Location {
+    fn new(file: &str, pointer: &str) -> Self {
+        Location {
+            file: file.to_string(),
+            pointer: pointer.to_string(),
+        }
+    }
+
+    /// The location of `segment` inside this one.
+    fn child(&self, segment: &str) -> Self {
+        Location {
+            file: self.file.clone(),
+            pointer: format!("{}/{}", self.pointer, escape(segment)),
+        }
+    }
+}
+
+struct Entry {
+    location: Location,
+    token: Token,
+}
+
+struct Collection {
+    /// Where the collection was first defined.
+    location: Location,
+    tokens: BTreeMap<String, Entry>,
+}
+
+/// The theme as its files define it, merged like [`Theme::load_from_dir`]
+/// does but remembering where every entry came from.
+#[derive(Default)]
+struct Checker {
+    collections: BTreeMap<String, Collection>,
+    properties: BTreeMap<String, (Location, Value)>,
+    aliases: BTreeMap<String, (Location, Value)>,
+    shorthands: BTreeMap<String, (Location, Value)>,
+    issues: Vec<Issue>,
+}
+
+/// Validate the theme in `dir` and its parents: every property's collection
+/// and overrides, alias targets, shorthand steps, placeholders and `order`
+/// entries, and every token value and `{reference}`. Also warns about
+/// collections nothing can reach and keys written both dashed and nested.
+pub fn check_dir(dir: &str) -> Vec<Issue> {
+    check(None, Some(dir), || Theme::load_from_dir(dir))
+}
+
+/// [`check_dir`] for a directory layered over [`Theme::builtin`], or for the
+/// built-in theme alone when `dir` is `None`.
+#[cfg(feature = "builtin-theme")]
+pub fn check_over_builtin(dir: Option<&str>) -> Vec<Issue> {
+    let base = match builtin::files() {
+        Ok(files) => files,
+        Err(e) => {
+            return vec![Issue {
+                severity: Severity::Error,
+                file: "builtin:".to_string(),
+                pointer: String::new(),
+                message: e,
+            }];
+        }
+    };
+    check(Some(base), dir, || match dir {
+        Some(dir) => Theme::load_over_builtin(dir),
+        None => Theme::builtin(),
+    })
+}
+
+/// `load` loads the same theme the normal way, to catch whatever the checks
+/// here do not cover.
+fn check(
+    base: Option<ThemeFiles>,
+    dir: Option<&str>,
+    load: impl FnOnce() -> Result<Theme, String>,
+) -> Vec<Issue> {
+    let mut checker = Checker::default();
+    if let Some(base) = &base {
+        checker.add_files(base);
+    }
+    if let Some(dir) = dir {
+        match theme::theme_dirs(Path::new(dir)) {
+            Ok(dirs) => {
+                for theme_dir in &dirs {
+                    let has_parent = theme_dir.extends || base.is_some();
+                    match ThemeFiles::read(&theme_dir.path, has_parent) {
+                        Ok(files)
+                            if base.as_ref().is_some_and(|base| base.same_contents(&files)) => {}
+                        Ok(files) => checker.add_files(&files),
+                        Err(e) => {
+                            let location = theme_dir.path.display().to_string();
+                            checker.error(&Location::new(&location, ""), e);
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                let manifest = Path::new(dir).join("theme.json");
+                checker.error(&Location::new(&manifest.display().to_string(), ""), e);
+            }
+        }
+    }
+    checker.check_references();
+    checker.check_properties();
+    checker.check_aliases();
+    checker.check_shorthands();
+    checker.check_reachable();
+
+    // Anything the loader still rejects (presets, conflicting files) is
+    // reported as it words it.
+    let mut issues = checker.issues;
+    if !issues.iter().any(|issue| issue.severity == Severity::Error)
+        && let Err(e) = load()
+    {
+        issues.push(Issue {
+            severity: Severity::Error,
+            file: dir.unwrap_or("builtin:").to_string(),
+            pointer: String::new(),
+            message: e,
+        });
+    }
+    issues.sort_by(|a, b| (&a.file, &a.pointer).cmp(&(&b.file, &b.pointer)));
+    issues
+}
+
+impl Checker {
+    fn error(&mut self, location: &Location, message: String) {
+        self.push(Severity::Error, location, message);
+    }
+
+    fn warning(&mut self, location: &Location, message: String) {
+        self.push(Severity::Warning, location, message);
+    }
+
+    fn push(&mut self, severity: Severity, location: &Location, message: String) {
+        self.issues.push(Issue {
+            severity,
+            file: location.file.clone(),
+            pointer: location.pointer.clone(),
+            message,
+        });
+    }
+
+    fn add_files(&mut self, files: &ThemeFiles) {
+        if let Some((path, data)) = &files.tokens_json
+            && let Some(raw) = self.parse_json(path, data)
+        {
+            self.add_tokens_json(&raw, path);
+        }
+        self.add_dtcg(&files.dtcg);
+        for file in &files.token_files {
+            let location = Location::new(&file.path, "");
+            self.add_collection(&file.collection, &file.raw, location);
+        }
+
+        if let Some((path, data)) = &files.shorthands
+            && let Some(raw) = self.parse_json(path, data)
+        {
+            let root = Location::new(path, "");
+            match raw.as_object() {
+                Some(shorthands) => {
+                    for (name, value) in shorthands {
+                        if value.is_null() {
+                            self.shorthands.remove(name);
+                            continue;
+                        }
+                        let value = match (self.shorthands.remove(name), value) {
+                            (Some((_, Value::Array(steps))), Value::Object(_)) => {
+                                merge_fields(&serde_json::json!({ "steps": steps }), value, &[])
+                            }
+                            (Some((_, inherited)), _) => merge_fields(&inherited, value, &[]),
+                            (None, _) => value.clone(),
+                        };
+                        self.shorthands
+                            .insert(name.clone(), (root.child(name), value));
+                    }
+                }
+                None => self.error(&root, "expected an object of shorthands".to_string()),
+            }
+        }
+    }
+
+    fn parse_json(&mut self, path: &str, data: &str) -> Option<Value> {
+        match serde_json::from_str(data) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.error(&Location::new(path, ""), format!("invalid JSON: {}", e));
+                None
+            }
+        }
+    }
+
+    fn add_tokens_json(&mut self, raw: &Value, file: &str) {
+        let root = Location::new(file, "");
+        let Some(sections) = raw.as_object() else {
+            self.error(&root, "expected an object".to_string());
+            return;
+        };
+        for (section, value) in sections {
+            let location = root.child(section);
+            let Some(entries) = value.as_object() else {
+                self.error(&location, "expected an object".to_string());
+                continue;
+            };
+            let (target, deep) = match section.as_str() {
+                "collections" => {
+                    for (name, raw) in entries {
+                        self.add_collection(name, raw, location.child(name));
+                    }
+                    continue;
+                }
+                "properties" => (&mut self.properties, &["overrides"][..]),
+                "aliases" => (&mut self.aliases, &[][..]),
+                other => {
+                    let message = format!(
+                        "unknown section '{}' (expected collections, properties or aliases)",
+                        other
+                    );
+                    self.error(&location, message);
+                    continue;
+                }
+            };
+            for (name, value) in entries {
+                if value.is_null() {
+                    target.remove(name);
+                    continue;
+                }
+                let value = match target.remove(name) {
+                    Some((_, inherited)) => merge_fields(&inherited, value, deep),
+                    None => value.clone(),
+                };
+                target.insert(name.clone(), (location.child(name), value));
+            }
+        }
+    }
+
+    /// Merge converted DTCG collections, mapping their usual properties the
+    /// way the loader does. DTCG token paths are JSON pointers already.
+    fn add_dtcg(&mut self, files: &[(String, Value)]) {
+        if files.is_empty() {
+            return;
+        }
+        let (converted, errors) = dtcg::convert(files);
+        for error in errors {
+            let (file, rest) = files
+                .iter()
+                .find_map(|(path, _)| {
+                    let rest = error.strip_prefix(path.as_str())?.strip_prefix(": ")?;
+                    Some((path.as_str(), rest))
+                })
+                .unwrap_or(("", error.as_str()));
+            let (location, message) = match rest.split_once(": ") {
+                Some((path, message)) => {
+                    let pointer: String =
+                        path.split('.').map(|s| format!("/{}", escape(s))).collect();
+                    (Location::new(file, &pointer), message)
+                }
+                None => (Location::new(file, ""), rest),
+            };
+            self.error(&location, message.to_string());
+        }
+        for ((path, _), collections) in files.iter().zip(converted) {
+            for (name, raw) in &collections {
+                self.add_collection(name, raw, Location::new(path, ""));
+                for property in dtcg::default_properties(name) {
+                    if self.aliases.contains_key(*property) {
+                        continue;
+                    }
+                    self.properties
+                        .entry(property.to_string())
+                        .or_insert_with(|| {
+                            (
+                                Location::new(path, ""),
+                                serde_json::json!({ "collection": name }),
+                            )
+                        });
+                }
+            }
+        }
+    }
+
+    fn add_collection(&mut self, name: &str, raw: &Value, location: Location) {
+        if raw.is_null() {
+            self.collections.remove(name);
+            return;
+        }
+        let mut removed = Vec::new();
+        let raw = theme::strip_removals(raw, &mut Vec::new(), &mut removed);
+        let mut parsed = Vec::new();
+        if let Err(e) =
+            tokens::visit_collection(name, &raw, &mut |path, value| parsed.push((path, value)))
+        {
+            self.error(&location, e);
+            return;
+        }
+
+        let collection = self
+            .collections
+            .entry(name.to_string())
+            .or_insert_with(|| Collection {
+                location: location.clone(),
+                tokens: BTreeMap::new(),
+            });
+        for path in removed {
+            collection
+                .tokens
+                .retain(|_, entry| !entry.token.path.starts_with(&path));
+        }
+
+        let mut issues = Vec::new();
+        for (path, value) in parsed {
+            let token_location = path.iter().fold(location.clone(), |location, segment| {
+                location.child(segment)
+            });
+            let value = match value {
+                Ok(value) => value,
+                Err(message) => {
+                    issues.push((Severity::Error, token_location, message));
+                    continue;
+                }
+            };
+            let key = path.join("-");
+            if let Some(existing) = collection.tokens.get(&key)
+                && existing.token.path != path
+            {
+                let message = format!(
+                    "{}.{} shadows {}.{} at {}#{} (both are @{})",
+                    name,
+                    path.join("."),
+                    name,
+                    existing.token.path.join("."),
+                    existing.location.file,
+                    existing.location.pointer,
+                    key
+                );
+                issues.push((Severity::Warning, token_location.clone(), message));
+            }
+            let token = Token {
+                path,
+                value,
+                source: None,
+                alias_of: None,
+            };
+            collection.tokens.insert(
+                key,
+                Entry {
+                    location: token_location,
+                    token,
+                },
+            );
+        }
+        for (severity, location, message) in issues {
+            self.push(severity, &location, message);
+        }
+    }
+
+    fn token_collections(&self) -> HashMap<String, TokenCollection> {
+        self.collections
+            .iter()
+            .map(|(name, collection)| {
+                let tokens = collection
+                    .tokens
+                    .iter()
+                    .map(|(key, entry)| (key.clone(), entry.token.clone()))
+                    .collect();
+                (name.clone(), tokens)
+            })
+            .collect()
+    }
+
+    fn check_references(&mut self) {
+        let collections = self.token_collections();
+        let mut issues = Vec::new();
+        for (name, collection) in &self.collections {
+            for entry in collection.tokens.values() {
+                if let Err(e) =
+                    token_refs::resolve_value(&collections, name, entry.token.value.clone())
+                {
+                    issues.push((entry.location.clone(), e));
+                }
+            }
+        }
+        for (location, message) in issues {
+            self.error(&location, message);
+        }
+    }
+
+    fn check_properties(&mut self) {
+        let collections = self.token_collections();
+        let mut issues = Vec::new();
+        for (location, mapping) in self.properties.values() {
+            let Some(fields) = mapping.as_object() else {
+                issues.push((
+                    location.clone(),
+                    "expected { \"collection\": ... }".to_string(),
+                ));
+                continue;
+            };
+            for field in fields.keys() {
+                if field != "collection" && field != "overrides" {
+                    issues.push((
+                        location.child(field),
+                        format!(
+                            "unknown field '{}' (expected collection or overrides)",
+                            field
+                        ),
+                    ));
+                }
+            }
+            let Some(collection) = fields.get("collection").and_then(Value::as_str) else {
+                issues.push((location.clone(), "missing \"collection\"".to_string()));
+                continue;
+            };
+            if !collections.contains_key(collection) {
+                issues.push((
+                    location.child("collection"),
+                    format!("unknown collection '{}'", collection),
+                ));
+            }
+            match fields.get("overrides") {
+                None => {}
+                Some(Value::Object(overrides)) => {
+                    for (key, value) in overrides {
+                        let parsed = tokens::parse_value(collection, value).and_then(|parsed| {
+                            token_refs::resolve_value(&collections, collection, parsed)
+                        });
+                        if let Err(e) = parsed {
+                            issues.push((location.child("overrides").child(key), e));
+                        }
+                    }
+                }
+                Some(_) => issues.push((
+                    location.child("overrides"),
+                    "expected an object of tokens".to_string(),
+                )),
+            }
+        }
+        for (location, message) in issues {
+            self.error(&location, message);
+        }
+    }
+
+    fn check_aliases(&mut self) {
+        let mut issues = Vec::new();
+        for (location, target) in self.aliases.values() {
+            match target.as_str() {
+                Some(target) if self.properties.contains_key(target) => {}
+                Some(target) => {
+                    issues.push((location.clone(), format!("unknown property '{}'", target)))
+                }
+                None => issues.push((location.clone(), "expected a property name".to_string())),
+            }
+        }
+        for (location, message) in issues {
+            self.error(&location, message);
+        }
+    }
+
+    fn is_property(&self, name: &str) -> bool {
+        let name = tokens::camel_case(name);
+        self.aliases.contains_key(&name) || self.properties.contains_key(&name)
+    }
+
+    fn check_shorthands(&mut self) {
+        let mut issues = Vec::new();
+        for (name, (location, raw)) in &self.shorthands {
+            let (steps, steps_location, order) = match raw {
+                Value::Array(steps) => (steps, location.clone(), None),
+                Value::Object(fields) => {
+                    for field in fields.keys() {
+                        if field != "steps" && field != "order" {
+                            issues.push((
+                                Severity::Error,
+                                location.child(field),
+                                format!("unknown field '{}' (expected steps or order)", field),
+                            ));
+                        }
+                    }
+                    let Some(Value::Array(steps)) = fields.get("steps") else {
+                        issues.push((
+                            Severity::Error,
+                            location.child("steps"),
+                            "expected an array of steps".to_string(),
+                        ));
+                        continue;
+                    };
+                    (steps, location.child("steps"), fields.get("order"))
+                }
+                _ => {
+                    issues.push((
+                        Severity::Error,
+                        location.clone(),
+                        "expected an array of steps or { \"steps\", \"order\" }".to_string(),
+                    ));
+                    continue;
+                }
+            };
+
+            let mut placeholders = HashSet::new();
+            for (index, step) in steps.iter().enumerate() {
+                let step_location = steps_location.child(&index.to_string());
+                let Some(fields) = step.as_object() else {
+                    let message = "expected { \"property\", \"template\" }".to_string();
+                    issues.push((Severity::Error, step_location, message));
+                    continue;
+                };
+                for (field, value) in fields {
+                    let (valid, expected) = match field.as_str() {
+                        "property" | "template" => (value.is_string(), "a string"),
+                        "append" | "optional" => (value.is_boolean(), "true or false"),
+                        _ => {
+                            issues.push((
+                                Severity::Error,
+                                step_location.child(field),
+                                format!(
+                                    "unknown field '{}' (expected property, template, append or optional)",
+                                    field
+                                ),
+                            ));
+                            continue;
+                        }
+                    };
+                    if !valid {
+                        let message = format!("expected {}", expected);
+                        issues.push((Severity::Error, step_location.child(field), message));
+                    }
+                }
+                for field in ["property", "template"] {
+                    if !fields.contains_key(field) {
+                        let message = format!("missing \"{}\"", field);
+                        issues.push((Severity::Error, step_location.clone(), message));
+                    }
+                }
+                let Some(template) = fields.get("template").and_then(Value::as_str) else {
+                    continue;
+                };
+                let template_location = step_location.child("template");
+                let (names, problem) = placeholder_names(template);
+                if let Some(problem) = problem {
+                    issues.push((Severity::Error, template_location.clone(), problem));
+                }
+                for placeholder in names {
+                    if placeholder == "token" {
+                        if !self.is_property(name) {
+                            let message = format!(
+                                "@{{token}} resolves against the '{}' property, which does not exist",
+                                name
+                            );
+                            issues.push((Severity::Error, template_location.clone(), message));
+                        }
+                    } else if !self.is_property(&placeholder) {
+                        let message = format!(
+                            "@{{{}}} names no property or alias, so it only takes literal values",
+                            placeholder
+                        );
+                        issues.push((Severity::Warning, template_location.clone(), message));
+                    }
+                    placeholders.insert(placeholder);
+                }
+            }
+
+            let Some(order) = order else {
+                continue;
+            };
+            let order_location = location.child("order");
+            let Some(entries) = order.as_array() else {
+                let message = "expected an array of placeholder names".to_string();
+                issues.push((Severity::Error, order_location, message));
+                continue;
+            };
+            let mut seen = HashSet::new();
+            for (index, entry) in entries.iter().enumerate() {
+                let entry_location = order_location.child(&index.to_string());
+                let message = match entry.as_str() {
+                    None => "expected a placeholder name".to_string(),
+                    Some(entry) if !seen.insert(entry) => format!("'{}' is listed twice", entry),
+                    Some(entry) if !placeholders.contains(entry) => {
+                        format!("'{}' is not a placeholder of any step", entry)
+                    }
+                    Some(_) => continue,
+                };
+                issues.push((Severity::Error, entry_location, message));
+            }
+        }
+        for (severity, location, message) in issues {
+            self.push(severity, &location, message);
+        }
+    }
+
+    /// Warn about collections no property maps to, no reference points into
+    /// and the resolver does not read directly.
+    fn check_reachable(&mut self) {
+        let mut reachable: HashSet<String> = DIRECT_COLLECTIONS
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        for (_, mapping) in self.properties.values() {
+            if let Some(collection) = mapping.get("collection").and_then(Value::as_str) {
+                reachable.insert(collection.to_string());
+            }
+        }
+        for collection in self.collections.values() {
+            for entry in collection.tokens.values() {
+                referenced_collections(&entry.token.value, &mut reachable);
+            }
+        }
+
+        let unreachable: Vec<(Location, String)> = self
+            .collections
+            .iter()
+            .filter(|(name, _)| !reachable.contains(*name))
+            .map(|(name, collection)| {
+                let message = format!(
+                    "collection '{}' is not used by any property or reference",
+                    name
+                );
+                (collection.location.clone(), message)
+            })
+            .collect();
+        for (location, message) in unreachable {
+            self.warning(&location, message);
+        }
+    }
+}
+
+fn referenced_collections(value: &TokenValue, out: &mut HashSet<String>) {
+    match value {
+        TokenValue::Reference { target, .. } => {
+            if let Some((collection, _)) = target.split_once('.') {
+                out.insert(collection.to_string());
+            }
+        }
+        TokenValue::Modes { light, dark } => {
+            referenced_collections(light, out);
+            referenced_collections(dark, out);
+        }
+        _ => {}
+    }
+}
+
+/// The `@{name}` placeholders of a shorthand template, in order, and the
+/// problem that stopped the scan early, if any.
+fn placeholder_names(template: &str) -> (Vec<String>, Option<String>) {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("@{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            return (
+                names,
+                Some(format!("unclosed placeholder in '{}'", template)),
+            );
+        };
+        let name = &after[..end];
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return (names, Some(format!("invalid placeholder '@{{{}}}'", name)));
+        }
+        names.push(name.to_string());
+        rest = &after[end + 1..];
+    }
+    (names, None)
+}
+
+/// Escape a JSON pointer segment (RFC 6901).
+/// `patch` merged over `inherited` field by field, the way the loader merges
+/// a child theme's property or shorthand over its parent's; the entries of
+/// the `deep` fields merge one level further, `null` removing one. Anything
+/// but two objects is replaced.
+fn merge_fields(inherited: &Value, patch: &Value, deep: &[&str]) -> Value {
+    let (Value::Object(inherited), Value::Object(patch)) = (inherited, patch) else {
+        return patch.clone();
+    };
+    let mut merged = inherited.clone();
+    for (field, value) in patch {
+        let value = match (merged.remove(field), value) {
+            (Some(Value::Object(mut entries)), Value::Object(patch))
+                if deep.contains(&field.as_str()) =>
+            {
+                for (key, value) in patch {
+                    if value.is_null() {
+                        entries.remove(key);
+                    } else {
+                        entries.insert(key.clone(), value.clone());
+                    }
+                }
+                Value::Object(entries)
+            }
+            _ => value.clone(),
+        };
+        merged.insert(field.clone(), value);
+    }
+    Value::Object(merged)
+}
+
+fn escape(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn theme_dir(name: &str, files: &[(&str, &str)]) -> String {
+        let dir = std::env::temp_dir().join(format!("rcss-check-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, contents) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir.display().to_string()
+    }
+
+    #[test]
+    fn problems_are_located_by_json_pointer() {
+        let dir = theme_dir(
+            "broken",
+            &[
+                (
+                    "tokens.json",
+                    r##"{
+                        "collections": {
+                            "colors": {
+                                "blue": { "500": "#3b82f6" },
+                                "blue-500": "#2563eb",
+                                "ink": "12px",
+                                "link": "{colors.navy}"
+                            },
+                            "spacing": { "4": "1rem" },
+                            "unused": { "a": "1px" }
+                        },
+                        "properties": {
+                            "textColor": { "collection": "colors", "overides": {} },
+                            "padding": { "collection": "spaceing" },
+                            "gap": { "collection": "spacing", "overrides": { "px": "{spacing.9}" } }
+                        },
+                        "aliases": { "color": "textColour" }
+                    }"##,
+                ),
+                (
+                    "shorthands.json",
+                    r#"{
+                        "ring": {
+                            "order": ["width", "colour"],
+                            "steps": [{ "property": "box-shadow", "template": "0 0 0 @{width} @{color" }]
+                        },
+                        "pad": [{ "property": "padding", "template": "@{padding} @{inset}" }]
+                    }"#,
+                ),
+            ],
+        );
+        let issues: Vec<String> = check_dir(&dir)
+            .iter()
+            .map(|issue| {
+                let text = issue.to_string().replace(dir.as_str(), "");
+                match issue.severity {
+                    Severity::Error => text,
+                    Severity::Warning => format!("{} (warning)", text),
+                }
+            })
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                "/shorthands.json#/pad/0/template: @{inset} names no property or alias, so it only takes literal values (warning)",
+                "/shorthands.json#/ring/order/1: 'colour' is not a placeholder of any step",
+                "/shorthands.json#/ring/steps/0/template: unclosed placeholder in '0 0 0 @{width} @{color'",
+                "/shorthands.json#/ring/steps/0/template: @{width} names no property or alias, so it only takes literal values (warning)",
+                "/tokens.json#/aliases/color: unknown property 'textColour'",
+                "/tokens.json#/collections/colors/blue-500: colors.blue-500 shadows colors.blue.500 at /tokens.json#/collections/colors/blue/500 (both are @blue-500) (warning)",
+                "/tokens.json#/collections/colors/ink: expected a color, got '12px'",
+                "/tokens.json#/collections/colors/link: unknown token {colors.navy}",
+                "/tokens.json#/collections/unused: collection 'unused' is not used by any property or reference (warning)",
+                "/tokens.json#/properties/gap/overrides/px: unknown token {spacing.9}",
+                "/tokens.json#/properties/padding/collection: unknown collection 'spaceing'",
+                "/tokens.json#/properties/textColor/overides: unknown field 'overides' (expected collection or overrides)",
+            ]
+        );
+    }
+
+    #[test]
+    fn child_entries_merge_over_parents() {
+        let dir = theme_dir(
+            "extends",
+            &[
+                (
+                    "base/tokens.json",
+                    r##"{
+                        "collections": { "spacing": { "1": "4px" } },
+                        "properties": { "width": { "collection": "spacing" } }
+                    }"##,
+                ),
+                (
+                    "base/shorthands.json",
+                    r#"{ "pad": [{ "property": "padding", "template": "@{width}" }] }"#,
+                ),
+                ("brand/theme.json", r#"{ "extends": "../base" }"#),
+                (
+                    "brand/tokens.json",
+                    r#"{ "properties": { "width": { "overrides": { "half": "50%" } } } }"#,
+                ),
+                (
+                    "brand/shorthands.json",
+                    r#"{ "pad": { "order": ["width"] } }"#,
+                ),
+            ],
+        );
+        let issues = check_dir(&format!("{dir}/brand"));
+        assert!(issues.is_empty(), "{issues:?}");
+    }
+
+    #[test]
+    fn shipped_theme_has_no_errors() {
+        let dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let errors: Vec<String> = check_dir(&dir)
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .map(|issue| issue.to_string())
+            .collect();
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+}
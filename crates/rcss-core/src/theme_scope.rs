@@ -0,0 +1,237 @@
+use crate::ast::{Declaration, MediaBlock, Rule, Stylesheet};
+use crate::emitter::{is_keyframes, scope_selector};
+use std::collections::{HashMap, HashSet};
+
+/// Where a declaration sits: selector, media query and property.
+type Slot = (String, Option<String>, String);
+
+/// Combine one stylesheet resolved against several named themes into a
+/// single sheet. The first theme's output is kept whole as the fallback for
+/// pages that set no theme; every theme, the first included, repeats its
+/// themed declarations under `:where([attribute="name"])`. Declarations
+/// that resolve the same in every theme are left out of the scopes.
+///
+/// The `:where()` adds no specificity, so each scoped copy is written right
+/// after the rule it comes from: it overrides that rule, and whatever comes
+/// later in the sheet still overrides both, as it would unscoped.
+pub fn scope_by_theme(
+    resolved: Vec<(String, Stylesheet)>,
+    attribute: &str,
+) -> Result<Stylesheet, String> {
+    let (_, fallback) = resolved
+        .first()
+        .ok_or_else(|| "No themes to scope".to_string())?;
+    let mut combined = fallback.clone();
+
+    let values: Vec<HashMap<Slot, Vec<&str>>> = resolved
+        .iter()
+        .map(|(_, sheet)| {
+            let mut values = HashMap::new();
+            collect_values(&sheet.rules, &mut values);
+            values
+        })
+        .collect();
+    let slots: HashSet<&Slot> = values.iter().flat_map(HashMap::keys).collect();
+    let varying: HashSet<&Slot> = slots
+        .into_iter()
+        .filter(|slot| {
+            let first = values[0].get(*slot);
+            values[1..].iter().any(|other| other.get(*slot) != first)
+        })
+        .collect();
+
+    let markers: Vec<String> = resolved
+        .iter()
+        .map(|(name, _)| format!(":where([{}=\"{}\"])", attribute, name))
+        .collect();
+    let themes: Vec<(&str, &[Rule])> = markers
+        .iter()
+        .zip(&resolved)
+        .map(|(marker, (_, sheet))| (marker.as_str(), sheet.rules.as_slice()))
+        .collect();
+    let mut unmatched = Vec::new();
+    place_scoped(&mut combined.rules, &themes, &varying, &mut unmatched);
+    combined.rules.extend(unmatched);
+    Ok(combined)
+}
+
+/// The values each slot of `rules` and their children takes, in order.
+fn collect_values<'a>(rules: &'a [Rule], out: &mut HashMap<Slot, Vec<&'a str>>) {
+    for rule in rules {
+        let mut add = |query: Option<&String>, declarations: &'a [Declaration]| {
+            for decl in declarations {
+                let slot = (rule.selector.clone(), query.cloned(), decl.property.clone());
+                out.entry(slot).or_default().push(&decl.value);
+            }
+        };
+        add(None, &rule.declarations);
+        for block in &rule.media {
+            add(Some(&block.query), &block.declarations);
+        }
+        collect_values(&rule.children, out);
+    }
+}
+
+/// Give each rule of the fallback the scoped copies of its counterpart in
+/// every theme as its first children, so they are written after the rule's
+/// own declarations and before anything nested in it. Theme rules are
+/// paired with the fallback's by selector, in order; the ones left without
+/// a partner (a preset only one theme has) are scoped into `unmatched`.
+fn place_scoped(
+    rules: &mut [Rule],
+    themes: &[(&str, &[Rule])],
+    varying: &HashSet<&Slot>,
+    unmatched: &mut Vec<Rule>,
+) {
+    let mut cursors = vec![0; themes.len()];
+    for rule in rules.iter_mut() {
+        if is_keyframes(&rule.selector) {
+            continue;
+        }
+        let mut copies = Vec::new();
+        let mut nested = Vec::new();
+        for (&(marker, theme_rules), cursor) in themes.iter().zip(&mut cursors) {
+            let rest = &theme_rules[*cursor..];
+            let Some(offset) = rest.iter().position(|r| r.selector == rule.selector) else {
+                continue;
+            };
+            scoped_rules(&rest[..offset], marker, varying, unmatched);
+            *cursor += offset + 1;
+            copies.extend(scoped_copy(&rest[offset], marker, varying));
+            nested.push((marker, rest[offset].children.as_slice()));
+        }
+        place_scoped(&mut rule.children, &nested, varying, unmatched);
+        rule.children.splice(0..0, copies);
+    }
+    for (&(marker, theme_rules), cursor) in themes.iter().zip(&cursors) {
+        scoped_rules(&theme_rules[*cursor..], marker, varying, unmatched);
+    }
+}
+
+/// Top-level copies of `rules` and their children, as [`scoped_copy`].
+fn scoped_rules(rules: &[Rule], marker: &str, varying: &HashSet<&Slot>, out: &mut Vec<Rule>) {
+    for rule in rules {
+        if is_keyframes(&rule.selector) {
+            continue;
+        }
+        out.extend(scoped_copy(rule, marker, varying));
+        scoped_rules(&rule.children, marker, varying, out);
+    }
+}
+
+/// A copy of `rule` holding only the themed declarations whose slot varies
+/// between themes, with its selector scoped by `marker`; `None` when there
+/// are none.
+fn scoped_copy(rule: &Rule, marker: &str, varying: &HashSet<&Slot>) -> Option<Rule> {
+    let keep = |query: Option<&String>, declarations: &[Declaration]| -> Vec<Declaration> {
+        declarations
+            .iter()
+            .filter(|decl| {
+                let slot = (rule.selector.clone(), query.cloned(), decl.property.clone());
+                decl.themed && varying.contains(&slot)
+            })
+            .cloned()
+            .collect()
+    };
+    let declarations = keep(None, &rule.declarations);
+    let media: Vec<_> = rule
+        .media
+        .iter()
+        .filter_map(|block| {
+            let declarations = keep(Some(&block.query), &block.declarations);
+            (!declarations.is_empty()).then(|| MediaBlock {
+                query: block.query.clone(),
+                declarations,
+            })
+        })
+        .collect();
+
+    (!declarations.is_empty() || !media.is_empty()).then(|| Rule {
+        selector: scope_selector(&rule.selector, marker, marker),
+        declarations,
+        media,
+        children: Vec::new(),
+        nested_header: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emitter::{EmitOptions, emit_css_with_options};
+    use crate::targets::Targets;
+    use crate::{parser, resolver, theme::Theme};
+
+    /// `source` resolved against the default theme ("a") and a child theme
+    /// with a different blue-500 ("b"), scoped by `data-brand`.
+    fn scoped_css(test: &str, source: &str) -> String {
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        let brand_dir =
+            std::env::temp_dir().join(format!("rcss-scope-{}-{}", test, std::process::id()));
+        std::fs::create_dir_all(&brand_dir).unwrap();
+        std::fs::write(
+            brand_dir.join("theme.json"),
+            format!("{{ \"extends\": {:?} }}", theme_dir),
+        )
+        .unwrap();
+        std::fs::write(
+            brand_dir.join("tokens.json"),
+            r##"{ "collections": { "colors": { "blue": { "500": "#0000ff" } } } }"##,
+        )
+        .unwrap();
+
+        let sheet = parser::parse(source).expect("parse rc");
+        let resolved = [
+            ("a", theme_dir.clone()),
+            ("b", brand_dir.display().to_string()),
+        ]
+        .map(|(name, dir)| {
+            let theme = Theme::load_from_dir(&dir).expect("load theme");
+            let sheet = resolver::resolve(sheet.clone(), &theme).expect("resolve");
+            (name.to_string(), sheet)
+        })
+        .into();
+        let combined = scope_by_theme(resolved, "data-brand").unwrap();
+        emit_css_with_options(
+            &combined,
+            &EmitOptions {
+                targets: Targets::parse("chrome 120").expect("parse targets"),
+                ..EmitOptions::default()
+            },
+        )
+    }
+
+    #[test]
+    fn only_declarations_that_vary_are_scoped() {
+        let css = scoped_css(
+            "vary",
+            "$gap: @4;\n.a { padding: $gap; margin: 0; color: @blue-500; &:hover { color: red; } }",
+        );
+        // The first theme stays unscoped as the fallback, and gets a scope
+        // like every other theme; the base preset and padding are the same
+        // in both themes, so only the color is repeated.
+        assert!(css.starts_with(":root {\n"), "{css}");
+        assert!(
+            css.ends_with(
+                ":where([data-brand=\"a\"]) .a {\n    color: oklch(62.3% 0.214 259.815);\n}\n\n\
+                 :where([data-brand=\"b\"]) .a {\n    color: #0000ff;\n}\n\n\
+                 .a:hover {\n    color: red;\n}\n\n"
+            ),
+            "{css}"
+        );
+    }
+
+    #[test]
+    fn later_rules_still_override_scoped_copies() {
+        // On `class="a b"` the text is red unscoped; the scoped copies of
+        // `.a` have the same specificity as `.b`, so they must come before it.
+        let css = scoped_css("order", ".a { color: @blue-500; }\n.b { color: red; }");
+        assert!(
+            css.ends_with(
+                ":where([data-brand=\"b\"]) .a {\n    color: #0000ff;\n}\n\n\
+                 .b {\n    color: red;\n}\n\n"
+            ),
+            "{css}"
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use crate::theme::PropertyMapping;
+use crate::tokens::{self, TokenCollection};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// CSS text of every token in one collection, shared by all properties that
+/// render it the same way.
+type CssTable = Arc<HashMap<String, Arc<str>>>;
+
+/// Flattened lookup built when the theme loads: property name (camelCase,
+/// kebab-case and aliases) → collection → token key → CSS string.
+#[derive(Debug, Default)]
+pub struct TokenIndex {
+    properties: HashMap<String, Arc<IndexedProperty>>,
+    collections: HashMap<String, CssTable>,
+}
+
+#[derive(Debug)]
+pub struct IndexedProperty {
+    pub collection: String,
+    tokens: CssTable,
+    overrides: HashMap<String, Arc<str>>,
+}
+
+impl IndexedProperty {
+    pub fn get(&self, token: &str) -> Option<&str> {
+        self.overrides
+            .get(token)
+            .or_else(|| self.tokens.get(token))
+            .map(|css| &**css)
+    }
+}
+
+impl TokenIndex {
+    pub fn build(
+        collections: &HashMap<String, TokenCollection>,
+        properties: &HashMap<String, PropertyMapping>,
+        aliases: &HashMap<String, String>,
+    ) -> Self {
+        // One table per (collection, rendering) pair; most properties share one.
+        let mut tables: HashMap<(String, bool), CssTable> = HashMap::new();
+        let mut table_for = |collection: &str, property: &str| -> CssTable {
+            let chained = tokens::renders_per_layer(property);
+            tables
+                .entry((collection.to_string(), chained))
+                .or_insert_with(|| {
+                    let mut table = HashMap::new();
+                    if let Some(tokens) = collections.get(collection) {
+                        for (key, token) in tokens {
+                            if let Some(css) = tokens::value_css(&token.value, property) {
+                                table.insert(key.clone(), Arc::from(css));
+                            }
+                        }
+                    }
+                    Arc::new(table)
+                })
+                .clone()
+        };
+
+        let mut index = TokenIndex::default();
+        for name in collections.keys() {
+            index
+                .collections
+                .insert(name.clone(), table_for(name, name));
+        }
+
+        for (name, mapping) in properties {
+            let overrides = mapping
+                .overrides
+                .iter()
+                .filter_map(|(key, value)| {
+                    tokens::value_css(value, name).map(|css| (key.clone(), Arc::from(css)))
+                })
+                .collect();
+            let entry = Arc::new(IndexedProperty {
+                collection: mapping.collection.clone(),
+                tokens: table_for(&mapping.collection, name),
+                overrides,
+            });
+            index.insert_property(name, entry);
+        }
+
+        // Aliases win over same-named properties, matching CSS usage
+        // (`size` means font size, `color` means text color).
+        for (alias, target) in aliases {
+            if let Some(entry) = index.properties.get(target.as_str()).cloned() {
+                index.insert_property(alias, entry);
+            }
+        }
+
+        index
+    }
+
+    fn insert_property(&mut self, name: &str, entry: Arc<IndexedProperty>) {
+        let kebab = tokens::kebab_case(name);
+        if kebab != name {
+            self.properties.insert(kebab, entry.clone());
+        }
+        self.properties.insert(name.to_string(), entry);
+    }
+
+    /// The mapping used by a property, accepting camelCase or kebab-case.
+    pub fn property(&self, name: &str) -> Option<&IndexedProperty> {
+        self.properties.get(name).map(|entry| &**entry)
+    }
+
+    /// Every property name the index accepts, aliases included.
+    pub fn property_names(&self) -> impl Iterator<Item = &str> {
+        self.properties.keys().map(String::as_str)
+    }
+
+    pub fn collection_value(&self, collection: &str, token: &str) -> Option<&str> {
+        self.collections
+            .get(collection)
+            .and_then(|table| table.get(token))
+            .map(|css| &**css)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::theme::Theme;
+
+    fn theme() -> Theme {
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        Theme::load_from_dir(&theme_dir).expect("load theme")
+    }
+
+    #[test]
+    fn index_accepts_kebab_camel_and_aliases() {
+        let theme = theme();
+        let index = &theme.index;
+        let camel = index
+            .property("backgroundColor")
+            .and_then(|m| m.get("blue-500"));
+        let kebab = index
+            .property("background-color")
+            .and_then(|m| m.get("blue-500"));
+        let alias = index.property("background").and_then(|m| m.get("blue-500"));
+        assert_eq!(camel, Some("oklch(62.3% 0.214 259.815)"));
+        assert_eq!(camel, kebab);
+        assert_eq!(camel, alias);
+        // `size` is an alias for font size even though a `size` property exists.
+        assert_eq!(index.property("size").unwrap().collection, "fontSize");
+        assert_eq!(
+            index.property("border-top-left-radius").unwrap().collection,
+            "borderRadius"
+        );
+    }
+
+    #[test]
+    fn index_skips_non_value_tokens() {
+        let theme = theme();
+        assert!(theme.index.collection_value("keyframes", "spin").is_none());
+        assert!(
+            theme
+                .index
+                .collection_value("fontFamily", "sans")
+                .unwrap()
+                .contains("system-ui")
+        );
+    }
+}
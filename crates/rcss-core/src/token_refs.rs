@@ -0,0 +1,299 @@
+use crate::color;
+use crate::tokens::{self, Token, TokenCollection, TokenValue};
+use std::collections::HashMap;
+
+type TokenId = (String, String);
+
+/// Replace every `{collection.path}` reference in `collections` with the
+/// value it points at, pushing `source: collection.path: problem` messages
+/// for unknown targets, cycles and values that do not fit the collection.
+pub fn resolve_all(collections: &mut HashMap<String, TokenCollection>, errors: &mut Vec<String>) {
+    let mut pending: Vec<TokenId> = collections
+        .iter()
+        .flat_map(|(name, tokens)| {
+            tokens
+                .iter()
+                .filter(|(_, token)| has_reference(&token.value))
+                .map(move |(key, _)| (name.clone(), key.clone()))
+        })
+        .collect();
+    pending.sort();
+
+    let mut resolved: HashMap<TokenId, TokenValue> = HashMap::new();
+    let mut failed = Vec::new();
+    for id in &pending {
+        if let Err(e) = resolve_token(collections, id, &mut Vec::new(), &mut resolved) {
+            let token = &collections[&id.0][&id.1];
+            failed.push(located(token, &id.0, &e));
+        }
+    }
+    errors.extend(failed);
+
+    for ((name, key), value) in resolved {
+        if let Some(token) = collections.get_mut(&name).and_then(|c| c.get_mut(&key)) {
+            if let TokenValue::Reference { target, .. } = &token.value {
+                token.alias_of = Some(target.clone());
+            }
+            token.value = value;
+        }
+    }
+}
+
+/// Resolve a value that is not itself addressable, such as a property
+/// override, as a value of `collection`.
+pub fn resolve_value(
+    collections: &HashMap<String, TokenCollection>,
+    collection: &str,
+    value: TokenValue,
+) -> Result<TokenValue, String> {
+    if !has_reference(&value) {
+        return Ok(value);
+    }
+    resolve_in(
+        collections,
+        collection,
+        &value,
+        &mut Vec::new(),
+        &mut HashMap::new(),
+    )
+}
+
+fn has_reference(value: &TokenValue) -> bool {
+    match value {
+        TokenValue::Reference { .. } => true,
+        TokenValue::Modes { light, dark } => has_reference(light) || has_reference(dark),
+        _ => false,
+    }
+}
+
+fn resolve_token(
+    collections: &HashMap<String, TokenCollection>,
+    id: &TokenId,
+    stack: &mut Vec<TokenId>,
+    resolved: &mut HashMap<TokenId, TokenValue>,
+) -> Result<TokenValue, String> {
+    if let Some(value) = resolved.get(id) {
+        return Ok(value.clone());
+    }
+    let token = &collections[&id.0][&id.1];
+    if !has_reference(&token.value) {
+        return Ok(token.value.clone());
+    }
+
+    if let Some(start) = stack.iter().position(|seen| seen == id) {
+        let chain: Vec<String> = stack[start..]
+            .iter()
+            .chain([id])
+            .map(|(name, key)| format!("{}.{}", name, collections[name][key].path.join(".")))
+            .collect();
+        return Err(format!("reference cycle {}", chain.join(" → ")));
+    }
+
+    stack.push(id.clone());
+    let value = resolve_in(collections, &id.0, &token.value, stack, resolved)?;
+    stack.pop();
+
+    resolved.insert(id.clone(), value.clone());
+    Ok(value)
+}
+
+/// Replace the references in `value`, a value of `collection`. Each side of a
+/// light/dark pair takes the matching side of a mode-aware target.
+fn resolve_in(
+    collections: &HashMap<String, TokenCollection>,
+    collection: &str,
+    value: &TokenValue,
+    stack: &mut Vec<TokenId>,
+    resolved: &mut HashMap<TokenId, TokenValue>,
+) -> Result<TokenValue, String> {
+    match value {
+        TokenValue::Reference { target, alpha } => {
+            let target_id = lookup(collections, target)?;
+            let value = resolve_token(collections, &target_id, stack, resolved)?;
+            finish(collections, collection, target, alpha.as_deref(), value)
+        }
+        TokenValue::Modes { light, dark } => {
+            let light = resolve_in(collections, collection, light, stack, resolved)?;
+            let dark = resolve_in(collections, collection, dark, stack, resolved)?;
+            Ok(TokenValue::Modes {
+                light: Box::new(light.for_mode(false).clone()),
+                dark: Box::new(dark.for_mode(true).clone()),
+            })
+        }
+        other => Ok(other.clone()),
+    }
+}
+
+/// Apply the `/alpha` suffix and fit the value to the referencing collection.
+fn finish(
+    collections: &HashMap<String, TokenCollection>,
+    collection: &str,
+    target: &str,
+    alpha: Option<&str>,
+    value: TokenValue,
+) -> Result<TokenValue, String> {
+    let value = match alpha {
+        None => value,
+        Some(alpha) => with_alpha(collections, target, alpha, &value)?,
+    };
+    tokens::coerce(collection, &value).map_err(|e| format!("{{{}}}: {}", target, e))
+}
+
+fn with_alpha(
+    collections: &HashMap<String, TokenCollection>,
+    target: &str,
+    alpha: &str,
+    value: &TokenValue,
+) -> Result<TokenValue, String> {
+    match value {
+        TokenValue::Color(css) => {
+            let opacity = collections
+                .get("opacity")
+                .and_then(|opacity| opacity.get(alpha))
+                .ok_or_else(|| {
+                    format!("unknown opacity '{}' in {{{}}}/{}", alpha, target, alpha)
+                })?;
+            Ok(TokenValue::Color(color::inject_alpha(
+                css,
+                &opacity.value.to_css(),
+            )?))
+        }
+        TokenValue::Modes { light, dark } => Ok(TokenValue::Modes {
+            light: Box::new(with_alpha(collections, target, alpha, light)?),
+            dark: Box::new(with_alpha(collections, target, alpha, dark)?),
+        }),
+        other => Err(format!(
+            "alpha '/{}' needs a color, {{{}}} is a {}",
+            alpha,
+            target,
+            other.kind()
+        )),
+    }
+}
+
+/// Find the token a `collection.path.to.token` target names. Path segments
+/// may themselves contain dots (`spacing.0.5`) or be dash-joined
+/// (`colors.blue-500`).
+fn lookup(collections: &HashMap<String, TokenCollection>, target: &str) -> Result<TokenId, String> {
+    let unknown = || format!("unknown token {{{}}}", target);
+    let (name, rest) = target.split_once('.').ok_or_else(unknown)?;
+    let tokens = collections.get(name).ok_or_else(unknown)?;
+    let key = rest.replace('.', "-");
+    if tokens.contains_key(&key) {
+        return Ok((name.to_string(), key));
+    }
+    tokens
+        .iter()
+        .find(|(key, token)| *key == rest || token.path.join(".") == rest)
+        .map(|(key, _)| (name.to_string(), key.clone()))
+        .ok_or_else(unknown)
+}
+
+fn located(token: &Token, collection: &str, message: &str) -> String {
+    let path = format!("{}.{}: {}", collection, token.path.join("."), message);
+    match &token.source {
+        Some(source) => format!("{}: {}", source, path),
+        None => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn collections(raw: serde_json::Value) -> HashMap<String, TokenCollection> {
+        let mut errors = Vec::new();
+        let collections = raw
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.clone(),
+                    tokens::parse_collection(name, value, &mut errors),
+                )
+            })
+            .collect();
+        assert!(errors.is_empty(), "{errors:?}");
+        collections
+    }
+
+    #[test]
+    fn references_follow_chains_and_alpha() {
+        let mut collections = collections(json!({
+            "colors": {
+                "blue": { "500": "oklch(62.3% 0.214 259.815)" },
+                "primary": "{colors.blue.500}",
+                "accent": "{colors.primary}",
+                "ring": "{colors.accent}/50"
+            },
+            "opacity": { "50": "0.5" },
+            "spacing": { "0.5": "0.125rem", "4": "1rem" },
+            "fontSize": { "body": "{spacing.4}" },
+            "gap": { "hair": "{spacing.0.5}" }
+        }));
+        let mut errors = Vec::new();
+        resolve_all(&mut collections, &mut errors);
+        assert!(errors.is_empty(), "{errors:?}");
+
+        let css = |name: &str, key: &str| collections[name][key].value.to_css();
+        assert_eq!(css("colors", "accent"), "oklch(62.3% 0.214 259.815)");
+        assert_eq!(
+            collections["colors"]["accent"].alias_of.as_deref(),
+            Some("colors.primary")
+        );
+        assert_eq!(css("colors", "ring"), "oklch(62.3% 0.214 259.815 / 0.5)");
+        assert!(matches!(
+            collections["fontSize"]["body"].value,
+            TokenValue::FontSize { .. }
+        ));
+        assert_eq!(css("gap", "hair"), "0.125rem");
+    }
+
+    #[test]
+    fn mode_pairs_resolve_each_side() {
+        let mut collections = collections(json!({
+            "colors": {
+                "white": "#ffffff",
+                "zinc": { "900": "#18181b" },
+                "surface": { "light": "{colors.white}", "dark": "{colors.zinc.900}" },
+                "overlay": "{colors.surface}/50"
+            },
+            "opacity": { "50": "0.5" }
+        }));
+        let mut errors = Vec::new();
+        resolve_all(&mut collections, &mut errors);
+        assert!(errors.is_empty(), "{errors:?}");
+
+        let overlay = &collections["colors"]["overlay"].value;
+        assert_eq!(overlay.for_mode(false).to_css(), "rgba(255,255,255,0.5)");
+        assert_eq!(overlay.for_mode(true).to_css(), "rgba(24,24,27,0.5)");
+        assert_eq!(collections["colors"]["surface"].value.to_css(), "#ffffff");
+    }
+
+    #[test]
+    fn broken_references_are_reported_at_their_path() {
+        let mut collections = collections(json!({
+            "colors": {
+                "a": "{colors.b}",
+                "b": "{colors.a}",
+                "ghost": "{colors.nope}",
+                "wide": "{spacing.4}"
+            },
+            "spacing": { "4": "1rem", "tint": "{spacing.4}/50" }
+        }));
+        let mut errors = Vec::new();
+        resolve_all(&mut collections, &mut errors);
+        assert_eq!(
+            errors,
+            vec![
+                "colors.a: reference cycle colors.a → colors.b → colors.a",
+                "colors.b: reference cycle colors.b → colors.a → colors.b",
+                "colors.ghost: unknown token {colors.nope}",
+                "colors.wide: {spacing.4}: expected a color, got '1rem'",
+                "spacing.tint: alpha '/50' needs a color, {spacing.4} is a length",
+            ]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use crate::export::natural_cmp;
+use crate::theme::Theme;
+use crate::tokens::{Token, TokenValue};
+
+/// One token as `rcss tokens list` and `rcss tokens search` show it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenEntry {
+    pub collection: String,
+    pub key: String,
+    /// The light (or only) value as CSS.
+    pub css: String,
+    /// The dark value of a light/dark token.
+    pub dark: Option<String>,
+    /// The theme file that defined the token.
+    pub source: Option<String>,
+    /// The `{collection.path}` reference the value was taken from.
+    pub alias_of: Option<String>,
+}
+
+impl TokenEntry {
+    fn new(collection: &str, key: &str, token: &Token) -> Self {
+        let dark = match &token.value {
+            TokenValue::Modes { dark, .. } => Some(dark.to_css()),
+            _ => None,
+        };
+        TokenEntry {
+            collection: collection.to_string(),
+            key: key.to_string(),
+            css: token.value.to_css(),
+            dark,
+            source: token.source.as_deref().map(str::to_string),
+            alias_of: token.alias_of.clone(),
+        }
+    }
+}
+
+/// A collection, its size and the properties mapped to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectionSummary {
+    pub name: String,
+    pub tokens: usize,
+    pub properties: Vec<String>,
+}
+
+/// Every collection of the theme, by name.
+pub fn collections(theme: &Theme) -> Vec<CollectionSummary> {
+    let mut summaries: Vec<CollectionSummary> = theme
+        .collections
+        .iter()
+        .map(|(name, tokens)| {
+            let mut properties: Vec<String> = theme
+                .properties
+                .iter()
+                .filter(|(_, mapping)| &mapping.collection == name)
+                .map(|(property, _)| property.clone())
+                .collect();
+            properties.sort();
+            CollectionSummary {
+                name: name.clone(),
+                tokens: tokens.len(),
+                properties,
+            }
+        })
+        .collect();
+    summaries.sort_by(|a, b| a.name.cmp(&b.name));
+    summaries
+}
+
+/// The tokens of one collection, in counting order (`amber-50` before
+/// `amber-100`).
+pub fn list(theme: &Theme, collection: &str) -> Result<Vec<TokenEntry>, String> {
+    let tokens = theme
+        .collections
+        .get(collection)
+        .ok_or_else(|| format!("Unknown collection '{}'", collection))?;
+    let mut entries: Vec<TokenEntry> = tokens
+        .iter()
+        .map(|(key, token)| TokenEntry::new(collection, key, token))
+        .collect();
+    entries.sort_by(|a, b| natural_cmp(&a.key, &b.key));
+    Ok(entries)
+}
+
+/// Tokens matching every whitespace-separated word of `query`, best first.
+/// Words match fuzzily against `collection.key` and the token's CSS value,
+/// so `blu5` finds `blue-500` and `#3b82f6` finds it by value.
+pub fn search(theme: &Theme, query: &str) -> Vec<TokenEntry> {
+    let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+
+    let mut matches: Vec<(u32, TokenEntry)> = Vec::new();
+    for (collection, tokens) in &theme.collections {
+        for (key, token) in tokens {
+            let entry = TokenEntry::new(collection, key, token);
+            let qualified = format!("{}.{}", collection, key).to_lowercase();
+            let key = key.to_lowercase();
+            let css = entry.css.to_lowercase();
+            let score = terms.iter().try_fold(0, |total, term| {
+                // Names count for more than values.
+                let best = [
+                    fuzzy_score(term, &key).map(|s| s + 2),
+                    fuzzy_score(term, &qualified),
+                    fuzzy_score(term, &css).map(|s| s * 3 / 4),
+                ]
+                .into_iter()
+                .flatten()
+                .max()?;
+                Some(total + best)
+            });
+            if let Some(score) = score {
+                matches.push((score, entry));
+            }
+        }
+    }
+
+    matches.sort_by(|(a_score, a), (b_score, b)| {
+        b_score
+            .cmp(a_score)
+            .then_with(|| a.collection.cmp(&b.collection))
+            .then_with(|| natural_cmp(&a.key, &b.key))
+    });
+    matches.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// How well `term` matches `text`: exact beats substring beats a
+/// subsequence, and within each, earlier and tighter matches win. `None`
+/// when the letters of `term` do not all appear in order.
+fn fuzzy_score(term: &str, text: &str) -> Option<u32> {
+    if text == term {
+        return Some(1000);
+    }
+    if let Some(start) = text.find(term) {
+        let boundary = if is_boundary(text, start) { 100 } else { 0 };
+        let offset = start.min(100) as u32;
+        let slack = (text.len() - term.len()).min(200) as u32;
+        return Some(700 + boundary - offset - slack / 2);
+    }
+
+    let mut score = 0u32;
+    let mut rest = term.chars().peekable();
+    let mut previous_matched = false;
+    for (i, c) in text.char_indices() {
+        let Some(&wanted) = rest.peek() else {
+            break;
+        };
+        if c == wanted {
+            score += 10;
+            if previous_matched {
+                score += 15;
+            }
+            if is_boundary(text, i) {
+                score += 10;
+            }
+            previous_matched = true;
+            rest.next();
+        } else {
+            previous_matched = false;
+        }
+    }
+    (rest.peek().is_none()).then(|| score.min(500))
+}
+
+/// Whether a word starts at byte `i` of `text`.
+fn is_boundary(text: &str, i: usize) -> bool {
+    let Some(previous) = text[..i].chars().next_back() else {
+        return true;
+    };
+    let current = text[i..].chars().next().unwrap_or(' ');
+    !previous.is_alphanumeric() || previous.is_ascii_digit() != current.is_ascii_digit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme() -> Theme {
+        let theme_dir = format!("{}/../../theme", env!("CARGO_MANIFEST_DIR"));
+        Theme::load_from_dir(&theme_dir).expect("load theme")
+    }
+
+    #[test]
+    fn lists_collections_and_their_tokens_in_counting_order() {
+        let theme = theme();
+        let colors = collections(&theme)
+            .into_iter()
+            .find(|c| c.name == "colors")
+            .expect("colors collection");
+        assert!(colors.properties.contains(&"backgroundColor".to_string()));
+
+        let keys: Vec<String> = list(&theme, "colors")
+            .expect("list colors")
+            .into_iter()
+            .map(|entry| entry.key)
+            .filter(|key| key.starts_with("amber-"))
+            .collect();
+        assert_eq!(keys[..3], ["amber-50", "amber-100", "amber-200"]);
+        assert_eq!(
+            list(&theme, "nope").unwrap_err(),
+            "Unknown collection 'nope'"
+        );
+    }
+
+    #[test]
+    fn search_matches_keys_fuzzily_and_values() {
+        let theme = theme();
+        let keys = |query: &str| -> Vec<String> {
+            search(&theme, query)
+                .into_iter()
+                .take(10)
+                .map(|entry| format!("{}.{}", entry.collection, entry.key))
+                .collect()
+        };
+
+        let exact = keys("blue-500");
+        assert!(exact[..2].iter().all(|key| key.ends_with(".blue-500")));
+        assert!(exact.contains(&"colors.blue-500".to_string()));
+        assert!(keys("blu5").contains(&"colors.blue-500".to_string()));
+        assert!(keys("spacing 4").contains(&"spacing.4".to_string()));
+        let blue = list(&theme, "colors")
+            .unwrap()
+            .into_iter()
+            .find(|entry| entry.key == "blue-500")
+            .unwrap();
+        assert!(keys(&blue.css).contains(&"colors.blue-500".to_string()));
+        assert!(search(&theme, "zzzqqq").is_empty());
+    }
+}
@@ -0,0 +1,680 @@
+use crate::color::{self, Color};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Tokens of one collection keyed by their dash-joined path, so nested
+/// `colors.slate.500` is stored as `slate-500`.
+pub type TokenCollection = BTreeMap<String, Token>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    /// Path segments inside the collection (`["slate", "500"]`).
+    pub path: Vec<String>,
+    pub value: TokenValue,
+    /// The theme file that defined the token, once loaded into a theme.
+    pub source: Option<Arc<str>>,
+    /// The `{collection.path}` reference the value was taken from, if any.
+    pub alias_of: Option<String>,
+}
+
+/// A theme value parsed and validated once when the theme loads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenValue {
+    Color(String),
+    Length(String),
+    Number(String),
+    Duration(String),
+    FontStack(Vec<String>),
+    ShadowList(Vec<String>),
+    FontSize {
+        size: String,
+        line_height: Option<String>,
+        letter_spacing: Option<String>,
+        font_weight: Option<String>,
+    },
+    Keyframes(Vec<Keyframe>),
+    Raw(String),
+    /// `{ "light": ..., "dark": ... }`: a value per color scheme. Written as
+    /// the light value; the resolver adds the dark one.
+    Modes {
+        light: Box<TokenValue>,
+        dark: Box<TokenValue>,
+    },
+    /// `{collection.path}` or `{collection.path}/alpha`; replaced by the
+    /// target's value while the theme loads.
+    Reference {
+        target: String,
+        alpha: Option<String>,
+    },
+}
+
+/// One step of a keyframes token: a selector (`0%, 100%`) and its
+/// declarations with kebab-case property names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe {
+    pub selector: String,
+    pub declarations: Vec<(String, String)>,
+}
+
+/// What a collection holds, decided by its name. Collections without a
+/// dedicated kind infer each value from its shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CollectionKind {
+    Color,
+    Duration,
+    Length,
+    Number,
+    FontStack,
+    FontSize,
+    Shadows,
+    Keyframes,
+    Inferred,
+}
+
+impl CollectionKind {
+    fn for_collection(name: &str) -> Self {
+        match name {
+            "fontFamily" => CollectionKind::FontStack,
+            "fontSize" => CollectionKind::FontSize,
+            "boxShadow" | "dropShadow" => CollectionKind::Shadows,
+            "keyframes" => CollectionKind::Keyframes,
+            "screens" => CollectionKind::Length,
+            "opacity" | "ringOpacity" => CollectionKind::Number,
+            "transitionDuration" | "transitionDelay" | "animationDuration" | "animationDelay" => {
+                CollectionKind::Duration
+            }
+            name if name == "colors" || name.ends_with("Color") => CollectionKind::Color,
+            _ => CollectionKind::Inferred,
+        }
+    }
+
+    /// Objects nest further token groups unless they are a light/dark pair
+    /// or the kind itself is an object (keyframes, or a font size written as
+    /// `{ "size": ... }`).
+    fn leaf_is_object(&self, map: &serde_json::Map<String, Value>) -> bool {
+        if is_mode_pair(map) {
+            return true;
+        }
+        match self {
+            CollectionKind::Keyframes => true,
+            CollectionKind::FontSize => map.contains_key("size"),
+            _ => false,
+        }
+    }
+}
+
+impl TokenValue {
+    /// The value as written into a declaration. Font sizes yield only the
+    /// size; keyframes yield their block body.
+    pub fn to_css(&self) -> String {
+        match self {
+            TokenValue::Color(v)
+            | TokenValue::Length(v)
+            | TokenValue::Number(v)
+            | TokenValue::Duration(v)
+            | TokenValue::Raw(v) => v.clone(),
+            TokenValue::FontStack(families) => families.join(", "),
+            TokenValue::ShadowList(layers) => layers.join(", "),
+            TokenValue::FontSize { size, .. } => size.clone(),
+            TokenValue::Modes { light, .. } => light.to_css(),
+            TokenValue::Reference { target, alpha } => match alpha {
+                Some(alpha) => format!("{{{}}}/{}", target, alpha),
+                None => format!("{{{}}}", target),
+            },
+            TokenValue::Keyframes(frames) => frames
+                .iter()
+                .map(|frame| {
+                    let body: Vec<String> = frame
+                        .declarations
+                        .iter()
+                        .map(|(prop, value)| format!("{}: {};", prop, value))
+                        .collect();
+                    format!("{} {{ {} }}", frame.selector, body.join(" "))
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+
+    /// Whether two definitions mean the same thing, so `0.1` and `0.10` are
+    /// not reported as conflicting.
+    pub fn equivalent(&self, other: &TokenValue) -> bool {
+        match (self, other) {
+            (TokenValue::Number(a), TokenValue::Number(b)) => {
+                a.parse::<f64>().ok() == b.parse::<f64>().ok()
+            }
+            _ => self == other,
+        }
+    }
+
+    /// The value for one color scheme; values without modes are the same in
+    /// both.
+    pub fn for_mode(&self, dark: bool) -> &TokenValue {
+        match self {
+            TokenValue::Modes {
+                light,
+                dark: dark_value,
+            } => {
+                if dark {
+                    dark_value
+                } else {
+                    light
+                }
+            }
+            other => other,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TokenValue::Color(_) => "color",
+            TokenValue::Length(_) => "length",
+            TokenValue::Number(_) => "number",
+            TokenValue::Duration(_) => "duration",
+            TokenValue::FontStack(_) => "font stack",
+            TokenValue::ShadowList(_) => "shadow list",
+            TokenValue::FontSize { .. } => "font size",
+            TokenValue::Keyframes(_) => "keyframes",
+            TokenValue::Raw(_) => "raw value",
+            TokenValue::Modes { light, .. } => light.kind(),
+            TokenValue::Reference { .. } => "reference",
+        }
+    }
+}
+
+/// Whether `property` wraps each shadow layer in its own function, as the
+/// `filter` shorthand does with `drop-shadow(@{dropShadow})`.
+pub fn renders_per_layer(property: &str) -> bool {
+    property == "dropShadow"
+}
+
+/// CSS text for a token used by `property` (camelCase). `None` for values
+/// that cannot appear in a declaration, such as keyframes.
+pub fn value_css(value: &TokenValue, property: &str) -> Option<String> {
+    match value {
+        TokenValue::Raw(raw) => Some(
+            match raw.strip_prefix("@(").and_then(|s| s.strip_suffix(')')) {
+                Some(url) => format!("url(\"{}\")", url),
+                None => raw.clone(),
+            },
+        ),
+        TokenValue::ShadowList(layers) if renders_per_layer(property) => {
+            Some(layers.join(") drop-shadow("))
+        }
+        TokenValue::Keyframes(_) => None,
+        TokenValue::Modes { light, .. } => value_css(light, property),
+        other => Some(other.to_css()),
+    }
+}
+
+/// Parse every token of `collection`, pushing `collection.path: problem`
+/// messages onto `errors` instead of stopping at the first one.
+pub fn parse_collection(
+    collection: &str,
+    raw: &Value,
+    errors: &mut Vec<String>,
+) -> TokenCollection {
+    let mut tokens = TokenCollection::new();
+    let visited = visit_collection(collection, raw, &mut |path, parsed| match parsed {
+        Ok(value) => {
+            tokens.insert(
+                path.join("-"),
+                Token {
+                    path,
+                    value,
+                    source: None,
+                    alias_of: None,
+                },
+            );
+        }
+        Err(message) => errors.push(format!("{}.{}: {}", collection, path.join("."), message)),
+    });
+    if let Err(e) = visited {
+        errors.push(format!("{}: {}", collection, e));
+    }
+    tokens
+}
+
+/// Parse every token of `collection`, handing each one's path segments and
+/// parse result to `visit`, for callers that locate problems themselves.
+pub fn visit_collection(
+    collection: &str,
+    raw: &Value,
+    visit: &mut dyn FnMut(Vec<String>, Result<TokenValue, String>),
+) -> Result<(), String> {
+    let kind = CollectionKind::for_collection(collection);
+    let Value::Object(map) = raw else {
+        return Err("expected an object of tokens".to_string());
+    };
+    for (key, value) in map {
+        visit_tokens(kind, vec![key.clone()], value, visit);
+    }
+    Ok(())
+}
+
+/// Parse a single value the way `collection` would, e.g. for property
+/// overrides that point at that collection.
+pub fn parse_value(collection: &str, value: &Value) -> Result<TokenValue, String> {
+    parse_leaf(CollectionKind::for_collection(collection), value)
+}
+
+fn visit_tokens(
+    kind: CollectionKind,
+    path: Vec<String>,
+    value: &Value,
+    visit: &mut dyn FnMut(Vec<String>, Result<TokenValue, String>),
+) {
+    if let Value::Object(map) = value
+        && !kind.leaf_is_object(map)
+    {
+        for (key, child) in map {
+            let mut child_path = path.clone();
+            child_path.push(key.clone());
+            visit_tokens(kind, child_path, child, visit);
+        }
+        return;
+    }
+    visit(path, parse_leaf(kind, value));
+}
+
+fn parse_leaf(kind: CollectionKind, value: &Value) -> Result<TokenValue, String> {
+    if let Value::String(raw) = value
+        && let Some(reference) = parse_reference(raw)
+    {
+        return Ok(reference);
+    }
+    if let Value::Object(map) = value
+        && is_mode_pair(map)
+    {
+        let light = parse_leaf(kind, &map["light"]).map_err(|e| format!("light: {}", e))?;
+        let dark = parse_leaf(kind, &map["dark"]).map_err(|e| format!("dark: {}", e))?;
+        return Ok(TokenValue::Modes {
+            light: Box::new(light),
+            dark: Box::new(dark),
+        });
+    }
+    match kind {
+        CollectionKind::FontStack => parse_font_stack(value),
+        CollectionKind::FontSize => parse_font_size(value),
+        CollectionKind::Shadows => parse_shadows(value),
+        CollectionKind::Keyframes => parse_keyframes(value),
+        CollectionKind::Color => {
+            let raw = scalar(value)?;
+            if is_color(&raw) {
+                Ok(TokenValue::Color(raw))
+            } else {
+                Err(format!("expected a color, got '{}'", raw))
+            }
+        }
+        CollectionKind::Duration => {
+            let raw = scalar(value)?;
+            if is_duration(&raw) {
+                Ok(TokenValue::Duration(raw))
+            } else {
+                Err(format!("expected a duration, got '{}'", raw))
+            }
+        }
+        CollectionKind::Length => {
+            let raw = scalar(value)?;
+            if is_length(&raw) {
+                Ok(TokenValue::Length(raw))
+            } else {
+                Err(format!("expected a length, got '{}'", raw))
+            }
+        }
+        CollectionKind::Number => {
+            let raw = scalar(value)?;
+            if raw.parse::<f64>().is_ok() {
+                Ok(TokenValue::Number(raw))
+            } else {
+                Err(format!("expected a number, got '{}'", raw))
+            }
+        }
+        CollectionKind::Inferred => infer(value),
+    }
+}
+
+/// An object with exactly `light` and `dark` keys is one mode-aware token
+/// rather than a group of two.
+fn is_mode_pair(map: &serde_json::Map<String, Value>) -> bool {
+    map.len() == 2 && map.contains_key("light") && map.contains_key("dark")
+}
+
+/// `{colors.blue.500}` or `{colors.blue.500}/50`.
+fn parse_reference(raw: &str) -> Option<TokenValue> {
+    let rest = raw.trim().strip_prefix('{')?;
+    let (target, after) = rest.split_once('}')?;
+    if target.is_empty() || target.contains(char::is_whitespace) || !target.contains('.') {
+        return None;
+    }
+    let alpha = match after {
+        "" => None,
+        _ => Some(
+            after
+                .strip_prefix('/')
+                .filter(|a| !a.is_empty())?
+                .to_string(),
+        ),
+    };
+    Some(TokenValue::Reference {
+        target: target.to_string(),
+        alpha,
+    })
+}
+
+/// Fit a value taken from another collection to `collection`'s type, e.g. a
+/// spacing length used as a font size.
+pub fn coerce(collection: &str, value: &TokenValue) -> Result<TokenValue, String> {
+    if let TokenValue::Modes { light, dark } = value {
+        return Ok(TokenValue::Modes {
+            light: Box::new(coerce(collection, light)?),
+            dark: Box::new(coerce(collection, dark)?),
+        });
+    }
+    let kind = CollectionKind::for_collection(collection);
+    let fits = match kind {
+        CollectionKind::Inferred => true,
+        CollectionKind::Color => matches!(value, TokenValue::Color(_)),
+        CollectionKind::Duration => matches!(value, TokenValue::Duration(_)),
+        CollectionKind::Length => matches!(value, TokenValue::Length(_)),
+        CollectionKind::Number => matches!(value, TokenValue::Number(_)),
+        CollectionKind::FontStack => matches!(value, TokenValue::FontStack(_)),
+        CollectionKind::FontSize => matches!(value, TokenValue::FontSize { .. }),
+        CollectionKind::Shadows => matches!(value, TokenValue::ShadowList(_)),
+        CollectionKind::Keyframes => matches!(value, TokenValue::Keyframes(_)),
+    };
+    if fits {
+        return Ok(value.clone());
+    }
+    match value {
+        TokenValue::Keyframes(_) => Err(format!("expected a value, got {}", value.kind())),
+        _ => parse_leaf(kind, &Value::String(value.to_css())),
+    }
+}
+
+fn infer(value: &Value) -> Result<TokenValue, String> {
+    let raw = scalar(value)?;
+    Ok(if raw.parse::<f64>().is_ok() {
+        TokenValue::Number(raw)
+    } else if is_duration(&raw) {
+        TokenValue::Duration(raw)
+    } else if is_length(&raw) {
+        TokenValue::Length(raw)
+    } else if Color::parse(&raw).is_ok() {
+        TokenValue::Color(raw)
+    } else {
+        TokenValue::Raw(raw)
+    })
+}
+
+fn scalar(value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(_) | Value::Null => Err(format!("expected a string or number, got {}", value)),
+        Value::Array(_) => Err("unexpected array".to_string()),
+        Value::Object(_) => Err("unexpected object".to_string()),
+    }
+}
+
+fn parse_font_stack(value: &Value) -> Result<TokenValue, String> {
+    let families = match value {
+        Value::String(s) => color::split_top_level(s, ',')
+            .into_iter()
+            .map(|family| family.trim().to_string())
+            .filter(|family| !family.is_empty())
+            .collect(),
+        // Tailwind allows `[[...families], { fontFeatureSettings }]`.
+        Value::Array(items) if matches!(items.first(), Some(Value::Array(_))) => {
+            return parse_font_stack(&items[0]);
+        }
+        Value::Array(items) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| format!("font family entries must be strings, got {}", item))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        other => return Err(format!("expected a font stack, got {}", other)),
+    };
+    if families.is_empty() {
+        return Err("font stack is empty".to_string());
+    }
+    Ok(TokenValue::FontStack(families))
+}
+
+/// `"1rem"`, `["1rem", "1.5rem"]`,
+/// `["1rem", { "lineHeight": ..., "letterSpacing": ..., "fontWeight": ... }]`
+/// or `{ "size": "1rem", "lineHeight": ... }`.
+fn parse_font_size(value: &Value) -> Result<TokenValue, String> {
+    if let Value::Object(map) = value {
+        let mut metrics = map.clone();
+        let size = metrics.remove("size").unwrap_or(Value::Null);
+        return parse_font_size(&Value::Array(vec![size, Value::Object(metrics)]));
+    }
+    let (size, metrics) = match value {
+        Value::Array(items) if !items.is_empty() && items.len() <= 2 => (&items[0], items.get(1)),
+        Value::Array(_) => return Err("expected [size] or [size, metrics]".to_string()),
+        other => (other, None),
+    };
+    let size = scalar(size)?;
+    if !is_length(&size) {
+        return Err(format!("expected a font size length, got '{}'", size));
+    }
+
+    let mut line_height = None;
+    let mut letter_spacing = None;
+    let mut font_weight = None;
+    match metrics {
+        None => {}
+        Some(Value::Object(map)) => {
+            for (key, metric) in map {
+                let metric = Some(scalar(metric)?);
+                match key.as_str() {
+                    "lineHeight" => line_height = metric,
+                    "letterSpacing" => letter_spacing = metric,
+                    "fontWeight" => font_weight = metric,
+                    other => return Err(format!("unknown font size metric '{}'", other)),
+                }
+            }
+        }
+        Some(other) => line_height = Some(scalar(other)?),
+    }
+
+    Ok(TokenValue::FontSize {
+        size,
+        line_height,
+        letter_spacing,
+        font_weight,
+    })
+}
+
+fn parse_shadows(value: &Value) -> Result<TokenValue, String> {
+    let layers: Vec<String> = match value {
+        Value::String(s) => color::split_top_level(s, ',')
+            .into_iter()
+            .map(|layer| layer.trim().to_string())
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| format!("shadow layers must be strings, got {}", item))
+            })
+            .collect::<Result<_, _>>()?,
+        other => return Err(format!("expected a shadow, got {}", other)),
+    };
+    if layers.iter().any(|layer| layer.is_empty()) {
+        return Err("shadow list has an empty layer".to_string());
+    }
+    Ok(TokenValue::ShadowList(layers))
+}
+
+fn parse_keyframes(value: &Value) -> Result<TokenValue, String> {
+    let Value::Object(frames) = value else {
+        return Err(format!("expected keyframes object, got {}", value));
+    };
+    let mut parsed = Vec::new();
+    for (selector, body) in frames {
+        let Value::Object(decls) = body else {
+            return Err(format!("keyframe '{}' must be an object", selector));
+        };
+        let mut declarations = Vec::new();
+        for (prop, val) in decls {
+            let val =
+                scalar(val).map_err(|e| format!("keyframe '{}' {}: {}", selector, prop, e))?;
+            declarations.push((kebab_case(prop), val));
+        }
+        parsed.push(Keyframe {
+            selector: selector.clone(),
+            declarations,
+        });
+    }
+    Ok(TokenValue::Keyframes(parsed))
+}
+
+pub fn kebab_case(name: &str) -> String {
+    let mut out = String::new();
+    for ch in name.chars() {
+        if ch.is_ascii_uppercase() {
+            out.push('-');
+            out.push(ch.to_ascii_lowercase());
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// `ring-offset-width` → `ringOffsetWidth`.
+pub fn camel_case(name: &str) -> String {
+    let mut out = String::new();
+    let mut upper = false;
+    for ch in name.chars() {
+        if ch == '-' || ch == '_' {
+            upper = !out.is_empty();
+        } else if upper {
+            out.push(ch.to_ascii_uppercase());
+            upper = false;
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+fn is_color(raw: &str) -> bool {
+    let lower = raw.trim().to_ascii_lowercase();
+    Color::parse(&lower).is_ok()
+        || lower.starts_with("var(")
+        || matches!(
+            lower.as_str(),
+            "currentcolor" | "inherit" | "initial" | "unset" | "transparent"
+        )
+        // Named colors beyond the basic set are passed through.
+        || (!lower.is_empty() && lower.chars().all(|c| c.is_ascii_lowercase()))
+}
+
+fn split_number(raw: &str) -> Option<(f64, &str)> {
+    let raw = raw.trim();
+    let end = raw
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(raw.len());
+    let number = raw[..end].parse::<f64>().ok()?;
+    Some((number, &raw[end..]))
+}
+
+fn is_duration(raw: &str) -> bool {
+    matches!(split_number(raw), Some((_, "ms" | "s")))
+}
+
+fn is_length(raw: &str) -> bool {
+    const UNITS: [&str; 19] = [
+        "px", "rem", "em", "%", "vh", "vw", "vmin", "vmax", "svh", "lvh", "dvh", "svw", "lvw",
+        "dvw", "ch", "ex", "pt", "cqw", "cqh",
+    ];
+    let raw = raw.trim();
+    if raw.starts_with("calc(") || raw.starts_with("var(") || raw.starts_with("clamp(") {
+        return true;
+    }
+    match split_number(raw) {
+        Some((_, "")) => raw.parse::<f64>().is_ok(),
+        Some((_, unit)) => UNITS.contains(&unit),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn typed_collections() {
+        let mut errors = Vec::new();
+        let sizes = parse_collection(
+            "fontSize",
+            &json!({ "lg": ["1.125rem", { "lineHeight": "1.75rem" }], "xs": "0.75rem" }),
+            &mut errors,
+        );
+        assert!(errors.is_empty());
+        assert_eq!(
+            sizes["lg"].value,
+            TokenValue::FontSize {
+                size: "1.125rem".into(),
+                line_height: Some("1.75rem".into()),
+                letter_spacing: None,
+                font_weight: None,
+            }
+        );
+
+        let colors = parse_collection(
+            "colors",
+            &json!({ "slate": { "500": "oklch(55.4% 0.046 257.417)" } }),
+            &mut errors,
+        );
+        assert_eq!(colors["slate-500"].path, vec!["slate", "500"]);
+        assert!(matches!(colors["slate-500"].value, TokenValue::Color(_)));
+
+        let stack = parse_collection(
+            "fontFamily",
+            &json!({ "sans": ["Inter", "sans-serif"] }),
+            &mut errors,
+        );
+        assert_eq!(stack["sans"].value.to_css(), "Inter, sans-serif");
+
+        let object_form = parse_collection(
+            "fontSize",
+            &json!({ "lg": { "size": "1.125rem", "lineHeight": "1.75rem" } }),
+            &mut errors,
+        );
+        assert!(errors.is_empty());
+        assert_eq!(object_form["lg"], sizes["lg"]);
+    }
+
+    #[test]
+    fn invalid_entries_are_all_reported() {
+        let mut errors = Vec::new();
+        parse_collection(
+            "colors",
+            &json!({ "brand": "12px", "ok": "#fff", "accent": { "500": true } }),
+            &mut errors,
+        );
+        parse_collection(
+            "transitionDuration",
+            &json!({ "fast": "quick" }),
+            &mut errors,
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "colors.accent.500: expected a string or number, got true",
+                "colors.brand: expected a color, got '12px'",
+                "transitionDuration.fast: expected a duration, got 'quick'",
+            ]
+        );
+    }
+}
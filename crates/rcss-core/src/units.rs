@@ -0,0 +1,252 @@
+use crate::ast::{Declaration, Rule, Stylesheet};
+use crate::color;
+use crate::math::Quantity;
+
+/// The root font size browsers use unless the stylesheet sets one.
+pub const DEFAULT_ROOT_FONT_SIZE: f64 = 16.0;
+
+/// Properties [`PxToRem`] leaves in px unless told otherwise: hairline
+/// borders, outlines and shadows should not grow with the text.
+pub const DEFAULT_KEEP_PX: [&str; 3] = ["border*", "outline*", "*shadow"];
+
+/// Which declarations px → rem conversion touches. Property names match
+/// whole, or by prefix with a trailing `*` (`border*`) and by suffix with a
+/// leading one (`*shadow`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PxToRem {
+    /// Convert only these properties; empty converts every property.
+    pub only: Vec<String>,
+    /// Keep these in px, even when `only` lists them.
+    pub keep_px: Vec<String>,
+}
+
+impl Default for PxToRem {
+    fn default() -> Self {
+        PxToRem {
+            only: Vec::new(),
+            keep_px: DEFAULT_KEEP_PX.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+impl PxToRem {
+    pub fn converts(&self, property: &str) -> bool {
+        let matches =
+            |pattern: &String| match (pattern.strip_suffix('*'), pattern.strip_prefix('*')) {
+                (Some(prefix), _) => property.starts_with(prefix),
+                (_, Some(suffix)) => property.ends_with(suffix),
+                _ => property == pattern,
+            };
+        (self.only.is_empty() || self.only.iter().any(matches)) && !self.keep_px.iter().any(matches)
+    }
+}
+
+/// The root font size in px, as the last `font-size` of a top-level `:root`
+/// or `html` rule sets it (the `%base-*` presets write one), or the browser
+/// default when there is none or it is not absolute (`clamp(...)`).
+pub fn root_font_size(rules: &[Rule]) -> f64 {
+    rules
+        .iter()
+        .filter(|rule| is_root(&rule.selector))
+        .flat_map(|rule| &rule.declarations)
+        .filter(|decl| decl.property == "font-size")
+        .filter_map(|decl| {
+            let size = Quantity::parse(&decl.value)?;
+            match size.unit.as_str() {
+                "px" => Some(size.value),
+                "rem" | "em" => Some(size.value * DEFAULT_ROOT_FONT_SIZE),
+                "%" => Some(size.value / 100.0 * DEFAULT_ROOT_FONT_SIZE),
+                _ => None,
+            }
+        })
+        .next_back()
+        .filter(|size| *size > 0.0)
+        .unwrap_or(DEFAULT_ROOT_FONT_SIZE)
+}
+
+fn is_root(selector: &str) -> bool {
+    matches!(selector.trim(), ":root" | "html")
+}
+
+/// Rewrite the px lengths of every declaration `options` converts as rem of
+/// the stylesheet's root font size. The root font size itself stays as
+/// written, since rem is relative to it.
+pub fn convert_stylesheet(stylesheet: &Stylesheet, options: &PxToRem) -> Stylesheet {
+    let root = root_font_size(&stylesheet.rules);
+    let mut converted = stylesheet.clone();
+    for rule in &mut converted.rules {
+        convert_rule(rule, options, root, true);
+    }
+    converted
+}
+
+fn convert_rule(rule: &mut Rule, options: &PxToRem, root: f64, top_level: bool) {
+    let keep_font_size = top_level && is_root(&rule.selector);
+    let convert = |decl: &mut Declaration| {
+        if options.converts(&decl.property) && !(keep_font_size && decl.property == "font-size") {
+            decl.value = px_to_rem(&decl.value, root);
+        }
+    };
+    rule.declarations.iter_mut().for_each(convert);
+    for media in &mut rule.media {
+        media.declarations.iter_mut().for_each(convert);
+    }
+    for child in &mut rule.children {
+        convert_rule(child, options, root, false);
+    }
+}
+
+/// `value` with its px lengths as rem (`24px` → `1.5rem` at 16px). Quoted
+/// strings and `url()` are left alone.
+pub fn px_to_rem(value: &str, root: f64) -> String {
+    let mut out = String::new();
+    let mut idx = 0;
+    let mut quote = None;
+    while idx < value.len() {
+        let rest = &value[idx..];
+        let ch = rest.chars().next().unwrap();
+        match quote {
+            Some(q) if ch == q => quote = None,
+            Some(_) => {}
+            None if ch == '"' || ch == '\'' => quote = Some(ch),
+            None if rest.len() >= 4 && rest[..4].eq_ignore_ascii_case("url(") => {
+                let end = rest.find(')').map_or(rest.len(), |i| i + 1);
+                out.push_str(&rest[..end]);
+                idx += end;
+                continue;
+            }
+            None if starts_number(value, idx) => {
+                let end = dimension_end(rest);
+                let dimension = &rest[..end];
+                match Quantity::parse(dimension) {
+                    Some(px) if px.unit == "px" => out.push_str(&rem(px.value, root)),
+                    _ => out.push_str(dimension),
+                }
+                idx += end;
+                continue;
+            }
+            None => {}
+        }
+        out.push(ch);
+        idx += ch.len_utf8();
+    }
+    out
+}
+
+/// Whether a number starts at `idx` rather than inside a word or a hex
+/// color (`#0a0`, `h1`, `translate3d`).
+fn starts_number(value: &str, idx: usize) -> bool {
+    let mut chars = value[idx..].chars();
+    let starts = match chars.next() {
+        Some(c) if c.is_ascii_digit() => true,
+        Some('-' | '+' | '.') => chars.next().is_some_and(|c| c.is_ascii_digit() || c == '.'),
+        _ => false,
+    };
+    starts
+        && !value[..idx].chars().next_back().is_some_and(|prev| {
+            prev.is_ascii_alphanumeric() || matches!(prev, '-' | '_' | '#' | '.')
+        })
+}
+
+/// The length of the number and unit at the start of `rest`.
+fn dimension_end(rest: &str) -> usize {
+    rest.char_indices()
+        .skip(1)
+        .find(|&(_, c)| !(c.is_ascii_alphanumeric() || c == '.' || c == '%'))
+        .map_or(rest.len(), |(i, _)| i)
+}
+
+fn rem(px: f64, root: f64) -> String {
+    if px == 0.0 {
+        return "0".to_string();
+    }
+    format!("{}rem", color::format_number(px / root, 4))
+}
+
+/// `value` with every `rem(...)` call replaced by its rem length:
+/// `rem(24px)` is `1.5rem` at a 16px root. The argument must be a px length
+/// (or already rem).
+pub(crate) fn replace_rem_calls(value: &str, root: f64) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("rem(") {
+        let is_call = !rest[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|prev| prev.is_ascii_alphanumeric() || matches!(prev, '-' | '_'));
+        if !is_call {
+            out.push_str(&rest[..start + 4]);
+            rest = &rest[start + 4..];
+            continue;
+        }
+        let open = start + 3;
+        let mut depth = 0;
+        let close = rest[open..]
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })
+            .map(|(i, _)| open + i)
+            .ok_or_else(|| "unterminated rem(...)".to_string())?;
+        let arg = rest[open + 1..close].trim();
+        let length = match Quantity::parse(arg) {
+            Some(q) if q.unit == "px" => rem(q.value, root),
+            Some(q) if q.unit == "rem" => q.to_string(),
+            Some(q) if q.value == 0.0 && q.unit.is_empty() => "0".to_string(),
+            _ => return Err(format!("rem() takes a px length, not '{}'", arg)),
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(&length);
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_px_lengths_to_rem() {
+        assert_eq!(px_to_rem("24px 8px", 16.0), "1.5rem 0.5rem");
+        assert_eq!(px_to_rem("calc(100% - 18px)", 18.0), "calc(100% - 1rem)");
+        assert_eq!(px_to_rem("-4px 0px 1em", 16.0), "-0.25rem 0 1em");
+        assert_eq!(
+            px_to_rem("url(a-16px.png) \"12px\" #12px", 16.0),
+            "url(a-16px.png) \"12px\" #12px"
+        );
+
+        assert_eq!(replace_rem_calls("rem(24px)", 16.0).unwrap(), "1.5rem");
+        assert_eq!(
+            replace_rem_calls("calc(rem(9px) + 1em) rem(2rem)", 18.0).unwrap(),
+            "calc(0.5rem + 1em) 2rem"
+        );
+        assert_eq!(replace_rem_calls("1rem", 16.0).unwrap(), "1rem");
+        assert_eq!(
+            replace_rem_calls("rem(1em)", 16.0).unwrap_err(),
+            "rem() takes a px length, not '1em'"
+        );
+    }
+
+    #[test]
+    fn filters_properties() {
+        let options = PxToRem::default();
+        assert!(options.converts("padding"));
+        assert!(!options.converts("border-top-width"));
+        assert!(!options.converts("box-shadow"));
+
+        let options = PxToRem {
+            only: vec!["font-size".into(), "margin*".into()],
+            keep_px: vec!["margin-top".into()],
+        };
+        assert!(options.converts("margin-left"));
+        assert!(!options.converts("margin-top"));
+        assert!(!options.converts("padding"));
+    }
+}